@@ -53,6 +53,19 @@ async fn main() {
     .await
     .unwrap();
 
+    // List applied migrations without creating the migrations table
+    let applied = geni::applied_migrations(
+        "sqlite://./test.db".to_string(), // Database URL
+        None,                             // Database Token
+        "migrations".to_string(),         // Migration Table
+        "./migrations".to_string(),       // Migration Folder
+        "schema.sql".to_string(),         // Schema File
+        Some(30),                         // Wait timeout for the database to be ready
+    )
+    .await
+    .unwrap();
+    println!("{:?}", applied);
+
     geni::new_migration(
         "./migration".to_string(), // Migration Folder
         &"test".to_string(),       // New migration name
@@ -1,14 +1,49 @@
+use chrono::{DateTime, Utc};
+use geni::audit::AuditLog;
+use geni::hooks::ShellHooks;
+use geni::observer::{MigrationObserver, Observers};
+use geni::status::MigrationState;
 use log::{error, info};
-use simplelog::{ColorChoice, Config, LevelFilter, TermLogger, TerminalMode};
+use opentelemetry::context::FutureExt;
+use std::io::{self, Write};
 
 mod config;
+mod logging;
+mod output;
+mod telemetry;
 mod tunnel;
 
+// Exits with 1 so CI and readiness probes never mistake a missing connection for success
 async fn resolve_database_connection_or_exit(
     matches: &clap::ArgMatches,
-) -> Option<config::ResolvedDatabaseConnection> {
+) -> config::ResolvedDatabaseConnection {
     match config::resolve_database_connection(matches).await {
-        Ok(connection) => Some(connection),
+        Ok(connection) => connection,
+        Err(err) => {
+            error!("{:?}", err);
+            exit(1);
+        }
+    }
+}
+
+fn observers(database_url: &str) -> anyhow::Result<Observers> {
+    let mut observers: Vec<Box<dyn MigrationObserver>> =
+        vec![Box::new(ShellHooks::new(config::hooks(), database_url)?)];
+
+    if let Some(path) = config::audit_log() {
+        observers.push(Box::new(AuditLog::new(
+            path,
+            config::audit_log_max_bytes()?,
+            database_url,
+        )));
+    }
+
+    Ok(Observers(observers))
+}
+
+fn observers_or_exit(database_url: &str) -> Option<Observers> {
+    match observers(database_url) {
+        Ok(observers) => Some(observers),
         Err(err) => {
             error!("{:?}", err);
             None
@@ -16,41 +51,68 @@ async fn resolve_database_connection_or_exit(
     }
 }
 
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// Flushes the traces first, std::process::exit skips every destructor
+fn exit(code: i32) -> ! {
+    telemetry::shutdown();
+    std::process::exit(code)
+}
+
 #[tokio::main]
 async fn main() {
-    TermLogger::init(
-        LevelFilter::Info,
-        Config::default(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )
-    .expect("Failed to initialize logger");
-
     let matches = config::cli_command().get_matches();
 
+    logging::init(&matches);
+    let context = telemetry::init();
+
+    run(matches).with_context(context).await;
+    telemetry::shutdown();
+}
+
+async fn run(matches: clap::ArgMatches) {
     let migration_path = config::migration_folder();
     let wait_timeout = config::wait_timeout();
     let migrations_table = config::migrations_table();
     let migrations_folder = config::migration_folder();
     let schema_file = config::schema_file();
     let dump_schema = config::dump_schema_file();
+    let dump_options = config::dump_options(&matches);
 
     match matches.subcommand() {
         Some(("new", query_matches)) => {
             let name = query_matches.get_one::<String>("name").unwrap();
-            match geni::new_migration(migration_path, name).await {
+            let version_scheme = match config::version_scheme() {
+                Ok(v) => v,
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+            };
+            match geni::new_migration_with_version_scheme(migration_path, name, version_scheme)
+                .await
+            {
                 Err(err) => {
                     error!("{:?}", err);
-                    std::process::exit(1);
+                    exit(1);
                 }
                 Ok(_) => info!("Success"),
             };
         }
         Some(("create", ..)) => {
-            let Some(database_connection) = resolve_database_connection_or_exit(&matches).await
-            else {
-                return;
-            };
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
             let database_url = database_connection.database_url.clone();
             let database_token = database_connection.database_token.clone();
 
@@ -66,16 +128,13 @@ async fn main() {
             {
                 Err(err) => {
                     error!("{:?}", err);
-                    std::process::exit(1);
+                    exit(1);
                 }
                 Ok(_) => info!("Success"),
             };
         }
         Some(("drop", ..)) => {
-            let Some(database_connection) = resolve_database_connection_or_exit(&matches).await
-            else {
-                return;
-            };
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
             let database_url = database_connection.database_url.clone();
             let database_token = database_connection.database_token.clone();
 
@@ -91,20 +150,23 @@ async fn main() {
             {
                 Err(err) => {
                     error!("{:?}", err);
-                    std::process::exit(1);
+                    exit(1);
                 }
                 Ok(_) => info!("Success"),
             };
         }
         Some(("up", ..)) => {
-            let Some(database_connection) = resolve_database_connection_or_exit(&matches).await
-            else {
-                return;
-            };
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
             let database_url = database_connection.database_url.clone();
             let database_token = database_connection.database_token.clone();
+            let Some(mut observers) =
+                observers_or_exit(&database_connection.configured_database_url)
+            else {
+                drop(database_connection);
+                exit(1);
+            };
 
-            match geni::migrate_database(
+            match geni::migrate_database_with_observer(
                 database_url,
                 database_token,
                 migrations_table,
@@ -112,21 +174,20 @@ async fn main() {
                 schema_file,
                 Some(wait_timeout),
                 dump_schema,
+                dump_options,
+                &mut observers,
             )
             .await
             {
                 Err(err) => {
                     error!("{:?}", err);
-                    std::process::exit(1);
+                    exit(1);
                 }
                 Ok(_) => info!("Success"),
             };
         }
         Some(("down", query_matches)) => {
-            let Some(database_connection) = resolve_database_connection_or_exit(&matches).await
-            else {
-                return;
-            };
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
             let database_url = database_connection.database_url.clone();
             let database_token = database_connection.database_token.clone();
             let rollback_amount = query_matches
@@ -134,8 +195,14 @@ async fn main() {
                 .unwrap_or(&"1".to_string())
                 .parse::<i64>()
                 .expect("Couldn't parse amount, is it a number?");
+            let Some(mut observers) =
+                observers_or_exit(&database_connection.configured_database_url)
+            else {
+                drop(database_connection);
+                exit(1);
+            };
 
-            match geni::migate_down(
+            match geni::migrate_down_with_observer(
                 database_url,
                 database_token,
                 migrations_table,
@@ -143,26 +210,80 @@ async fn main() {
                 schema_file,
                 Some(wait_timeout),
                 dump_schema,
+                dump_options,
                 rollback_amount,
+                &mut observers,
             )
             .await
             {
                 Err(err) => {
                     error!("{:?}", err);
-                    std::process::exit(1);
+                    exit(1);
                 }
                 Ok(_) => info!("Success"),
             };
         }
         Some(("status", query_matches)) => {
-            let Some(database_connection) = resolve_database_connection_or_exit(&matches).await
-            else {
-                return;
-            };
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
             let database_url = database_connection.database_url.clone();
             let database_token = database_connection.database_token.clone();
 
-            let verbose = query_matches.contains_id("verbose");
+            let verbose = query_matches.get_flag("log-verbose");
+            let check = query_matches.get_flag("check");
+            let output = query_matches.get_one::<String>("output");
+
+            if check || output.is_some() {
+                let statuses = match geni::migration_status(
+                    database_url,
+                    database_token,
+                    migrations_table,
+                    migrations_folder,
+                    schema_file,
+                    Some(wait_timeout),
+                )
+                .await
+                {
+                    Ok(s) => s,
+                    Err(err) => {
+                        error!("{:?}", err);
+                        exit(1);
+                    }
+                };
+
+                if let Some(output) = output {
+                    match output::format_status(&statuses, output) {
+                        Ok(o) => println!("{}", o),
+                        Err(err) => {
+                            error!("{:?}", err);
+                            exit(1);
+                        }
+                    }
+                } else {
+                    for s in statuses.iter() {
+                        match s.state {
+                            MigrationState::Applied => {}
+                            MigrationState::MissingFile => {
+                                error!("Migration {} is applied but missing locally", s.id)
+                            }
+                            _ => info!("Pending {}", s.id),
+                        }
+                    }
+                }
+
+                if check {
+                    let code = output::check_exit_code(&statuses);
+                    if code != 0 {
+                        drop(database_connection);
+                        exit(code);
+                    }
+
+                    if output.is_none() {
+                        info!("Database is up to date");
+                    }
+                }
+
+                return;
+            }
 
             if let Err(err) = geni::status_migrations(
                 database_url,
@@ -176,30 +297,355 @@ async fn main() {
             .await
             {
                 error!("{:?}", err);
-                std::process::exit(1);
+                exit(1);
             }
         }
-        Some(("dump", ..)) => {
-            let Some(database_connection) = resolve_database_connection_or_exit(&matches).await
-            else {
+        Some(("history", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+
+            let migrations = match geni::migration_history(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                query_matches.get_one::<DateTime<Utc>>("since").copied(),
+                query_matches.get_one::<DateTime<Utc>>("until").copied(),
+                query_matches.get_one::<usize>("limit").copied(),
+            )
+            .await
+            {
+                Ok(m) => m,
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+            };
+
+            let output = query_matches.get_one::<String>("output").unwrap();
+            match output::format_history(&migrations, output) {
+                Ok(o) => println!("{}", o),
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+            }
+        }
+        Some(("dump", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+
+            if query_matches.get_flag("check") {
+                match geni::check_dump(
+                    database_url,
+                    database_token,
+                    migrations_table,
+                    migrations_folder,
+                    schema_file,
+                    Some(wait_timeout),
+                    dump_options,
+                )
+                .await
+                {
+                    Err(err) => {
+                        error!("{:?}", err);
+                        drop(database_connection);
+                        exit(1);
+                    }
+                    Ok(Some(diff)) => {
+                        print!("{}", diff);
+                        drop(database_connection);
+                        exit(2);
+                    }
+                    Ok(None) => info!("The schema file is up to date"),
+                };
                 return;
+            }
+
+            match geni::dump_database_with_options(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                dump_options,
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("inspect", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+
+            let schema = match geni::inspect_schema(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+            )
+            .await
+            {
+                Ok(s) => s,
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
             };
+
+            let output = query_matches.get_one::<String>("output").unwrap();
+            match output::format_schema(&schema, output) {
+                Ok(o) => println!("{}", o),
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+            }
+        }
+        Some(("diff-migration", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
             let database_url = database_connection.database_url.clone();
             let database_token = database_connection.database_token.clone();
+            let name = query_matches.get_one::<String>("name").unwrap();
+            let version_scheme = match config::version_scheme() {
+                Ok(v) => v,
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+            };
+
+            match geni::diff_migration(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                config::desired_schema_file(),
+                name,
+                version_scheme,
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("drift", ..)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+
+            match geni::schema_drift(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                dump_options,
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    drop(database_connection);
+                    exit(1);
+                }
+                Ok(Some(diff)) => {
+                    print!("{}", diff);
+                    drop(database_connection);
+                    exit(2);
+                }
+                Ok(None) => info!("The database matches the schema file"),
+            };
+        }
+        Some(("load", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+            let version = query_matches.get_one::<i64>("version").copied();
+
+            match geni::load_database(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                version,
+                dump_options.split,
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("squash", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+            let version = *query_matches.get_one::<i64>("version").unwrap();
+            let assume_yes = query_matches.get_flag("yes");
+
+            match geni::squash_migrations(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                version,
+                |files| {
+                    assume_yes
+                        || confirm(&format!(
+                            "Replace {} migration files with a baseline, continue?",
+                            files.len()
+                        ))
+                },
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("baseline", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+            let version = *query_matches.get_one::<i64>("version").unwrap();
+            let assume_yes = query_matches.get_flag("yes");
+
+            match geni::baseline_database(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                version,
+                |migrations| {
+                    assume_yes
+                        || confirm(&format!(
+                            "The migrations table already contains {} migrations, continue?",
+                            migrations.len()
+                        ))
+                },
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("mark-applied", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+            let id = *query_matches.get_one::<i64>("id").unwrap();
+
+            match geni::mark_applied(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                id,
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("mark-unapplied", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+            let id = *query_matches.get_one::<i64>("id").unwrap();
+
+            match geni::mark_unapplied(
+                database_url,
+                database_token,
+                migrations_table,
+                migrations_folder,
+                schema_file,
+                Some(wait_timeout),
+                id,
+            )
+            .await
+            {
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+                Ok(_) => info!("Success"),
+            };
+        }
+        Some(("renumber", query_matches)) => {
+            let database_connection = resolve_database_connection_or_exit(&matches).await;
+            let database_url = database_connection.database_url.clone();
+            let database_token = database_connection.database_token.clone();
+            let migrations = query_matches
+                .get_many::<String>("migrations")
+                .unwrap()
+                .cloned()
+                .collect::<Vec<String>>();
+            let version_scheme = match config::version_scheme() {
+                Ok(v) => v,
+                Err(err) => {
+                    error!("{:?}", err);
+                    exit(1);
+                }
+            };
 
-            match geni::dump_database(
+            match geni::renumber_migrations(
                 database_url,
                 database_token,
                 migrations_table,
                 migrations_folder,
                 schema_file,
                 Some(wait_timeout),
+                migrations,
+                version_scheme,
             )
             .await
             {
                 Err(err) => {
                     error!("{:?}", err);
-                    std::process::exit(1);
+                    exit(1);
                 }
                 Ok(_) => info!("Success"),
             };
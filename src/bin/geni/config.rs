@@ -1,9 +1,15 @@
 use crate::tunnel::{self, SshTunnelConfig, SshTunnelGuard};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use clap::{
     crate_authors, crate_description, crate_version, value_parser, Arg, ArgAction, ArgMatches,
     Command,
 };
+use geni::audit;
+use geni::config::VersionScheme;
+use geni::database_drivers::DumpOptions;
+use geni::hooks::Hooks;
+use log::LevelFilter;
 use std::env;
 use std::path::PathBuf;
 
@@ -17,6 +23,8 @@ pub struct DatabaseOptions {
 
 pub struct ResolvedDatabaseConnection {
     pub database_url: String,
+    // The url as configured, database_url points at the local tunnel end when there is one
+    pub configured_database_url: String,
     pub database_token: Option<String>,
     _tunnel: Option<SshTunnelGuard>,
 }
@@ -34,11 +42,13 @@ impl DatabaseOptions {
 
                 Ok(ResolvedDatabaseConnection {
                     database_url: tunnel.database_url,
+                    configured_database_url: self.database_url,
                     database_token: self.database_token,
                     _tunnel: Some(tunnel.guard),
                 })
             }
             None => Ok(ResolvedDatabaseConnection {
+                configured_database_url: self.database_url.clone(),
                 database_url: self.database_url,
                 database_token: self.database_token,
                 _tunnel: None,
@@ -56,6 +66,15 @@ pub fn cli_command() -> Command {
         .name("geni")
         .author(crate_authors!())
         .args(database_args())
+        .args(logging_args())
+        .arg(
+            Arg::new("split")
+                .long("split")
+                .global(true)
+                .env("DATABASE_DUMP_SPLIT")
+                .help("Write and read the schema dump as one file per object in a directory named after the schema file")
+                .action(ArgAction::SetTrue),
+        )
         .subcommands([
             Command::new("new")
                 .about("Create new migration")
@@ -76,14 +95,132 @@ pub fn cli_command() -> Command {
             Command::new("status")
                 .about("Show current migrations to apply")
                 .arg(
-                    Arg::new("verbose")
-                        .short('v')
-                        .long("verbose")
-                        .help("Include migration content for the non applied migrations")
-                        .action(ArgAction::Set)
-                        .num_args(0..=1),
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Print every local and applied migration in the given format")
+                        .value_parser(["json", "yaml", "table"]),
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Exit with 2 when migrations are pending and 3 when applied migrations are missing locally")
+                        .action(ArgAction::SetTrue),
+                ),
+            Command::new("history")
+                .about("Show the applied migrations in the order they were applied")
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help("Only show migrations applied at or after this date (YYYY-MM-DD or RFC 3339)")
+                        .value_parser(parse_datetime),
+                )
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .help("Only show migrations applied before this date (YYYY-MM-DD or RFC 3339)")
+                        .value_parser(parse_datetime),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .help("Only show the last N applied migrations")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output format")
+                        .value_parser(["json", "yaml", "table"])
+                        .default_value("table"),
+                ),
+            Command::new("dump").about("Dump database structure").arg(
+                Arg::new("check")
+                    .long("check")
+                    .help("Don't write the schema file, exit with 2 when it differs from the dump")
+                    .action(ArgAction::SetTrue),
+            ),
+            Command::new("drift")
+                .about("Compare the live database with the schema file, exit with 2 when they differ"),
+            Command::new("inspect")
+                .about("Print the tables, indexes, constraints, views, sequences and functions of the database")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output format")
+                        .value_parser(["json", "yaml"])
+                        .default_value("json"),
+                ),
+            Command::new("diff-migration")
+                .about("Generate a migration turning the database into the schema of the desired schema file")
+                .arg(Arg::new("name").required(true).index(1)),
+            Command::new("load")
+                .about("Create the database from the schema dump and mark the migrations it contains as applied")
+                .arg(
+                    Arg::new("version")
+                        .long("version")
+                        .help("Newest migration the schema dump contains, when it has no migration rows. Defaults to every local migration")
+                        .value_parser(value_parser!(i64)),
+                ),
+            Command::new("squash")
+                .about("Squash all migrations up to a version into a single baseline migration")
+                .arg(
+                    Arg::new("version")
+                        .long("version")
+                        .help("Newest migration to include in the baseline")
+                        .value_parser(value_parser!(i64))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Don't ask for confirmation before replacing the migration files")
+                        .action(ArgAction::SetTrue),
+                ),
+            Command::new("baseline")
+                .about("Mark all migrations up to a version as applied without running them")
+                .arg(
+                    Arg::new("version")
+                        .long("version")
+                        .help("Newest migration to mark as applied")
+                        .value_parser(value_parser!(i64))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .help("Don't ask for confirmation when the migrations table isn't empty")
+                        .action(ArgAction::SetTrue),
+                ),
+            Command::new("mark-applied")
+                .about("Mark a migration as applied without running it")
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .index(1)
+                        .value_parser(value_parser!(i64)),
+                ),
+            Command::new("mark-unapplied")
+                .about("Mark a migration as not applied without rolling it back")
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .index(1)
+                        .value_parser(value_parser!(i64)),
+                ),
+            Command::new("renumber")
+                .about("Give unapplied migrations new versions after the newest migration")
+                .arg(
+                    Arg::new("migrations")
+                        .required(true)
+                        .index(1)
+                        .num_args(1..)
+                        .help("Migrations to renumber as id, <id>_<name> or file name, in the order they should run"),
                 ),
-            Command::new("dump").about("Dump database structure"),
         ])
 }
 
@@ -139,6 +276,60 @@ pub async fn resolve_database_connection(
     database_options(matches)?.resolve().await
 }
 
+fn logging_args() -> [Arg; 4] {
+    [
+        Arg::new("log-format")
+            .long("log-format")
+            .global(true)
+            .help("Log format, json writes one object per line to stderr")
+            .value_parser(["text", "json"])
+            .default_value("text"),
+        Arg::new("log-level")
+            .long("log-level")
+            .global(true)
+            .help("Minimum level to log")
+            .value_parser(["error", "warn", "info", "debug"])
+            .default_value("info"),
+        Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .global(true)
+            .help("Only log errors, overrides --log-level")
+            .action(ArgAction::SetTrue),
+        // status also prints the content of the pending migrations with it
+        Arg::new("log-verbose")
+            .short('v')
+            .long("verbose")
+            .global(true)
+            .help("Log debug messages, overrides --log-level")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("quiet"),
+    ]
+}
+
+pub fn log_format(matches: &ArgMatches) -> &str {
+    matches
+        .get_one::<String>("log-format")
+        .map(|f| f.as_str())
+        .unwrap_or("text")
+}
+
+pub fn log_level(matches: &ArgMatches) -> LevelFilter {
+    if matches.get_flag("quiet") {
+        return LevelFilter::Error;
+    }
+    if matches.get_flag("log-verbose") {
+        return LevelFilter::Debug;
+    }
+
+    match matches.get_one::<String>("log-level").map(|l| l.as_str()) {
+        Some("error") => LevelFilter::Error,
+        Some("warn") => LevelFilter::Warn,
+        Some("debug") => LevelFilter::Debug,
+        _ => LevelFilter::Info,
+    }
+}
+
 fn database_args() -> [Arg; 8] {
     [
         Arg::new("database-url")
@@ -188,6 +379,20 @@ fn database_args() -> [Arg; 8] {
     ]
 }
 
+fn parse_datetime(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_time(NaiveTime::MIN).and_utc()),
+        Err(_) => Err(format!(
+            "{} is not a date (YYYY-MM-DD) or RFC 3339 timestamp",
+            value
+        )),
+    }
+}
+
 pub fn migration_folder() -> String {
     if let Ok(v) = env::var("DATABASE_MIGRATIONS_FOLDER") {
         if !v.is_empty() {
@@ -230,6 +435,19 @@ pub fn dump_schema_file() -> bool {
     true
 }
 
+fn enabled_env(key: &str) -> bool {
+    env::var(key).map(|v| v == "true").unwrap_or(false)
+}
+
+// What the schema dump includes next to the schema, --split falls back to DATABASE_DUMP_SPLIT
+pub fn dump_options(matches: &ArgMatches) -> DumpOptions {
+    DumpOptions {
+        user_version: enabled_env("DATABASE_DUMP_USER_VERSION"),
+        migrations: enabled_env("DATABASE_DUMP_MIGRATIONS"),
+        split: matches.get_flag("split"),
+    }
+}
+
 pub fn schema_file() -> String {
     if let Ok(v) = env::var("DATABASE_SCHEMA_FILE") {
         if !v.is_empty() {
@@ -240,6 +458,16 @@ pub fn schema_file() -> String {
     "schema.sql".to_string()
 }
 
+pub fn desired_schema_file() -> String {
+    if let Ok(v) = env::var("DATABASE_DESIRED_SCHEMA_FILE") {
+        if !v.is_empty() {
+            return v;
+        }
+    }
+
+    "desired.sql".to_string()
+}
+
 pub fn migrations_table() -> String {
     if let Ok(v) = env::var("DATABASE_MIGRATIONS_TABLE") {
         if !v.is_empty() {
@@ -250,6 +478,77 @@ pub fn migrations_table() -> String {
     "schema_migrations".to_string()
 }
 
+pub fn version_scheme() -> Result<VersionScheme> {
+    if let Ok(v) = env::var("DATABASE_MIGRATIONS_VERSION_SCHEME") {
+        if !v.is_empty() {
+            return VersionScheme::new(&v);
+        }
+    }
+
+    Ok(VersionScheme::Timestamp)
+}
+
+fn non_empty_env(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|v| !v.is_empty())
+}
+
+// Follows the OpenTelemetry exporter environment variables
+pub fn otlp_traces_endpoint() -> Option<String> {
+    if let Some(endpoint) = non_empty_env("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+        return Some(endpoint);
+    }
+
+    non_empty_env("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .map(|endpoint| format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+}
+
+pub fn otlp_headers() -> Vec<(String, String)> {
+    non_empty_env("OTEL_EXPORTER_OTLP_HEADERS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|header| header.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+pub fn otel_service_name() -> String {
+    non_empty_env("OTEL_SERVICE_NAME").unwrap_or_else(|| "geni".to_string())
+}
+
+pub fn traceparent() -> Option<String> {
+    non_empty_env("TRACEPARENT")
+}
+
+fn hook(name: &str) -> Option<String> {
+    non_empty_env(&format!("DATABASE_HOOK_{}", name))
+}
+
+pub fn hooks() -> Hooks {
+    Hooks {
+        before_up: hook("BEFORE_UP"),
+        after_up: hook("AFTER_UP"),
+        before_each_up: hook("BEFORE_EACH_UP"),
+        after_each_up: hook("AFTER_EACH_UP"),
+        before_down: hook("BEFORE_DOWN"),
+        after_down: hook("AFTER_DOWN"),
+        before_each_down: hook("BEFORE_EACH_DOWN"),
+        after_each_down: hook("AFTER_EACH_DOWN"),
+    }
+}
+
+pub fn audit_log() -> Option<String> {
+    non_empty_env("DATABASE_AUDIT_LOG")
+}
+
+pub fn audit_log_max_bytes() -> Result<u64> {
+    match non_empty_env("DATABASE_AUDIT_LOG_MAX_BYTES") {
+        Some(v) => v
+            .parse::<u64>()
+            .with_context(|| format!("Couldn't parse DATABASE_AUDIT_LOG_MAX_BYTES {}", v)),
+        None => Ok(audit::DEFAULT_MAX_BYTES),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,6 +594,354 @@ mod tests {
         assert_eq!(matches.subcommand_name(), Some("new"));
     }
 
+    #[test]
+    fn test_squash_subcommand_requires_version() {
+        let result = cli_command().try_get_matches_from(["geni", "squash"]);
+        assert!(result.is_err());
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "squash", "--version", "1700000000"])
+            .unwrap();
+        let (_, squash_matches) = matches.subcommand().unwrap();
+
+        assert_eq!(
+            squash_matches.get_one::<i64>("version").copied(),
+            Some(1700000000)
+        );
+        assert!(!squash_matches.get_flag("yes"));
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "squash", "--version", "1700000000", "-y"])
+            .unwrap();
+        let (_, squash_matches) = matches.subcommand().unwrap();
+        assert!(squash_matches.get_flag("yes"));
+    }
+
+    #[test]
+    fn test_baseline_subcommand_args() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "baseline", "--version", "1700000000", "--yes"])
+            .unwrap();
+        let (_, baseline_matches) = matches.subcommand().unwrap();
+
+        assert_eq!(
+            baseline_matches.get_one::<i64>("version").copied(),
+            Some(1700000000)
+        );
+        assert!(baseline_matches.get_flag("yes"));
+    }
+
+    #[test]
+    fn test_dump_check_flag() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "dump", "--check"])
+            .unwrap();
+        let (_, dump_matches) = matches.subcommand().unwrap();
+        assert!(dump_matches.get_flag("check"));
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "dump"])
+            .unwrap();
+        let (_, dump_matches) = matches.subcommand().unwrap();
+        assert!(!dump_matches.get_flag("check"));
+    }
+
+    #[test]
+    fn test_inspect_subcommand_output() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "inspect"])
+            .unwrap();
+        let (_, inspect_matches) = matches.subcommand().unwrap();
+        assert_eq!(inspect_matches.get_one::<String>("output").unwrap(), "json");
+
+        let result = cli_command().try_get_matches_from(["geni", "inspect", "-o", "table"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_diff_migration_subcommand_requires_name() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "diff-migration", "add users"])
+            .unwrap();
+        let (name, diff_matches) = matches.subcommand().unwrap();
+        assert_eq!(name, "diff-migration");
+        assert_eq!(diff_matches.get_one::<String>("name").unwrap(), "add users");
+
+        let result = cli_command().try_get_matches_from(["geni", "diff-migration"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_load_subcommand_args() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "load", "--version", "1700000000"])
+            .unwrap();
+        let (_, load_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            load_matches.get_one::<i64>("version").copied(),
+            Some(1700000000)
+        );
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "load"])
+            .unwrap();
+        let (_, load_matches) = matches.subcommand().unwrap();
+        assert_eq!(load_matches.get_one::<i64>("version"), None);
+    }
+
+    #[test]
+    fn test_mark_subcommands_parse_id() {
+        for subcommand in ["mark-applied", "mark-unapplied"] {
+            let matches = cli_command()
+                .try_get_matches_from(["geni", subcommand, "1700000000"])
+                .unwrap();
+            let (_, mark_matches) = matches.subcommand().unwrap();
+
+            assert_eq!(mark_matches.get_one::<i64>("id").copied(), Some(1700000000));
+        }
+
+        let result = cli_command().try_get_matches_from(["geni", "mark-applied", "latest"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_logging_args() {
+        let matches = cli_command().try_get_matches_from(["geni", "up"]).unwrap();
+        assert_eq!(log_format(&matches), "text");
+        assert_eq!(log_level(&matches), LevelFilter::Info);
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "up", "--log-format", "json", "--log-level", "warn"])
+            .unwrap();
+        assert_eq!(log_format(&matches), "json");
+        assert_eq!(log_level(&matches), LevelFilter::Warn);
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "status", "-q", "--log-level", "debug"])
+            .unwrap();
+        assert_eq!(log_level(&matches), LevelFilter::Error);
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "--verbose", "status"])
+            .unwrap();
+        assert_eq!(log_level(&matches), LevelFilter::Debug);
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "up", "--verbose"])
+            .unwrap();
+        assert_eq!(log_level(&matches), LevelFilter::Debug);
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "status", "-v"])
+            .unwrap();
+        let (_, status_matches) = matches.subcommand().unwrap();
+        assert!(status_matches.get_flag("log-verbose"));
+        assert_eq!(log_level(&matches), LevelFilter::Debug);
+
+        let result = cli_command().try_get_matches_from(["geni", "up", "--verbose", "--quiet"]);
+        assert!(result.is_err());
+
+        let result = cli_command().try_get_matches_from(["geni", "--verbose", "--quiet", "up"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_status_subcommand_output() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "status", "--output", "json"])
+            .unwrap();
+        let (_, status_matches) = matches.subcommand().unwrap();
+
+        assert_eq!(
+            status_matches
+                .get_one::<String>("output")
+                .map(String::as_str),
+            Some("json")
+        );
+
+        let result = cli_command().try_get_matches_from(["geni", "status", "--output", "xml"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_status_subcommand_check() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "status", "--check"])
+            .unwrap();
+        let (_, status_matches) = matches.subcommand().unwrap();
+
+        assert!(status_matches.get_flag("check"));
+        assert!(status_matches.get_one::<String>("output").is_none());
+    }
+
+    #[test]
+    fn test_history_subcommand_args() {
+        let matches = cli_command()
+            .try_get_matches_from([
+                "geni",
+                "history",
+                "--since",
+                "2024-01-02",
+                "--until",
+                "2024-02-01T10:00:00+02:00",
+                "--limit",
+                "5",
+            ])
+            .unwrap();
+        let (_, history_matches) = matches.subcommand().unwrap();
+
+        assert_eq!(
+            history_matches
+                .get_one::<DateTime<Utc>>("since")
+                .map(|d| d.to_rfc3339()),
+            Some("2024-01-02T00:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            history_matches
+                .get_one::<DateTime<Utc>>("until")
+                .map(|d| d.to_rfc3339()),
+            Some("2024-02-01T08:00:00+00:00".to_string())
+        );
+        assert_eq!(history_matches.get_one::<usize>("limit").copied(), Some(5));
+        assert_eq!(
+            history_matches
+                .get_one::<String>("output")
+                .map(String::as_str),
+            Some("table")
+        );
+
+        let result =
+            cli_command().try_get_matches_from(["geni", "history", "--since", "yesterday"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_renumber_subcommand_keeps_order() {
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "renumber", "1700000002", "1700000001_add_users"])
+            .unwrap();
+        let (_, renumber_matches) = matches.subcommand().unwrap();
+
+        let migrations = renumber_matches
+            .get_many::<String>("migrations")
+            .unwrap()
+            .cloned()
+            .collect::<Vec<String>>();
+        assert_eq!(migrations, vec!["1700000002", "1700000001_add_users"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_version_scheme_from_env() {
+        let _scheme = EnvGuard::unset("DATABASE_MIGRATIONS_VERSION_SCHEME");
+        assert!(matches!(
+            version_scheme().unwrap(),
+            VersionScheme::Timestamp
+        ));
+
+        let _scheme = EnvGuard::set("DATABASE_MIGRATIONS_VERSION_SCHEME", "sequential");
+        assert!(matches!(
+            version_scheme().unwrap(),
+            VersionScheme::Sequential
+        ));
+
+        let _scheme = EnvGuard::set("DATABASE_MIGRATIONS_VERSION_SCHEME", "uuid");
+        assert!(version_scheme().is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_dump_options_from_env() {
+        let _user_version = EnvGuard::set("DATABASE_DUMP_USER_VERSION", "true");
+        let _migrations = EnvGuard::set("DATABASE_DUMP_MIGRATIONS", "1");
+        let _split = EnvGuard::unset("DATABASE_DUMP_SPLIT");
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "dump"])
+            .unwrap();
+        assert_eq!(
+            dump_options(&matches),
+            DumpOptions {
+                user_version: true,
+                migrations: false,
+                split: false,
+            }
+        );
+
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "dump", "--split"])
+            .unwrap();
+        assert!(dump_options(&matches).split);
+
+        let _split = EnvGuard::set("DATABASE_DUMP_SPLIT", "true");
+        let matches = cli_command()
+            .try_get_matches_from(["geni", "drift"])
+            .unwrap();
+        assert!(dump_options(&matches).split);
+    }
+
+    #[test]
+    #[serial]
+    fn test_hooks_from_env() {
+        let _before = EnvGuard::set("DATABASE_HOOK_BEFORE_UP", "./pause-consumer.sh");
+        let _after = EnvGuard::set("DATABASE_HOOK_AFTER_EACH_DOWN", "");
+        let _each = EnvGuard::unset("DATABASE_HOOK_BEFORE_EACH_UP");
+
+        let hooks = hooks();
+        assert_eq!(hooks.before_up.as_deref(), Some("./pause-consumer.sh"));
+        assert!(hooks.after_each_down.is_none());
+        assert!(hooks.before_each_up.is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_audit_log_from_env() {
+        let _path = EnvGuard::set("DATABASE_AUDIT_LOG", "/var/log/geni/audit.jsonl");
+        let _max = EnvGuard::unset("DATABASE_AUDIT_LOG_MAX_BYTES");
+        assert_eq!(audit_log().as_deref(), Some("/var/log/geni/audit.jsonl"));
+        assert_eq!(audit_log_max_bytes().unwrap(), audit::DEFAULT_MAX_BYTES);
+
+        let _max = EnvGuard::set("DATABASE_AUDIT_LOG_MAX_BYTES", "1048576");
+        assert_eq!(audit_log_max_bytes().unwrap(), 1048576);
+
+        let _max = EnvGuard::set("DATABASE_AUDIT_LOG_MAX_BYTES", "1MB");
+        assert!(audit_log_max_bytes().is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_otlp_config_from_env() {
+        let _traces = EnvGuard::unset("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT");
+        let _endpoint = EnvGuard::unset("OTEL_EXPORTER_OTLP_ENDPOINT");
+        let _headers = EnvGuard::set(
+            "OTEL_EXPORTER_OTLP_HEADERS",
+            "x-api-key=secret, x-team = db",
+        );
+        assert!(otlp_traces_endpoint().is_none());
+        assert_eq!(
+            otlp_headers(),
+            vec![
+                ("x-api-key".to_string(), "secret".to_string()),
+                ("x-team".to_string(), "db".to_string())
+            ]
+        );
+
+        let _endpoint = EnvGuard::set("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318/");
+        assert_eq!(
+            otlp_traces_endpoint().as_deref(),
+            Some("http://localhost:4318/v1/traces")
+        );
+
+        let _traces = EnvGuard::set(
+            "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+            "http://collector:4318/custom",
+        );
+        assert_eq!(
+            otlp_traces_endpoint().as_deref(),
+            Some("http://collector:4318/custom")
+        );
+    }
+
     #[test]
     #[serial]
     fn test_database_url_cli_overrides_env() {
@@ -336,6 +983,25 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_resolve_keeps_configured_database_url() {
+        let connection = DatabaseOptions {
+            database_url: "postgres://app@db.internal:5432/app".to_string(),
+            database_token: None,
+            wait_timeout: 30,
+            tunnel: None,
+        }
+        .resolve()
+        .await
+        .unwrap();
+
+        assert_eq!(connection.database_url, connection.configured_database_url);
+        assert_eq!(
+            connection.configured_database_url,
+            "postgres://app@db.internal:5432/app"
+        );
+    }
+
     #[test]
     #[serial]
     fn test_database_options_requires_database_url() {
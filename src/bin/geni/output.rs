@@ -0,0 +1,246 @@
+use anyhow::{bail, Result};
+use geni::database_drivers::SchemaMigration;
+use geni::schema::Schema;
+use geni::status::{MigrationState, MigrationStatus};
+
+pub const EXIT_PENDING_MIGRATIONS: i32 = 2;
+pub const EXIT_MISSING_MIGRATIONS: i32 = 3;
+
+// Missing files win over pending migrations as they need a human to look at the database
+pub fn check_exit_code(statuses: &[MigrationStatus]) -> i32 {
+    if statuses
+        .iter()
+        .any(|s| s.state == MigrationState::MissingFile)
+    {
+        return EXIT_MISSING_MIGRATIONS;
+    }
+
+    if statuses.iter().any(|s| s.state != MigrationState::Applied) {
+        return EXIT_PENDING_MIGRATIONS;
+    }
+
+    0
+}
+
+pub fn format_status(statuses: &[MigrationStatus], output: &str) -> Result<String> {
+    match output {
+        "json" => Ok(serde_json::to_string_pretty(statuses)?),
+        "yaml" => Ok(serde_yaml::to_string(statuses)?),
+        "table" => Ok(status_table(statuses)),
+        _ => bail!("Unknown output format {}", output),
+    }
+}
+
+pub fn format_history(migrations: &[SchemaMigration], output: &str) -> Result<String> {
+    match output {
+        "json" => Ok(serde_json::to_string_pretty(migrations)?),
+        "yaml" => Ok(serde_yaml::to_string(migrations)?),
+        "table" => Ok(history_table(migrations)),
+        _ => bail!("Unknown output format {}", output),
+    }
+}
+
+pub fn format_schema(schema: &Schema, output: &str) -> Result<String> {
+    match output {
+        "json" => Ok(serde_json::to_string_pretty(schema)?),
+        "yaml" => Ok(serde_yaml::to_string(schema)?),
+        _ => bail!("Unknown output format {}", output),
+    }
+}
+
+fn status_table(statuses: &[MigrationStatus]) -> String {
+    let mut rows = vec![["ID", "STATE", "NAME", "PATH"].map(String::from)];
+
+    for s in statuses {
+        rows.push([
+            s.id.to_string(),
+            s.state.as_str().to_string(),
+            s.name.clone().unwrap_or_default(),
+            s.path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+        ]);
+    }
+
+    table(&rows)
+}
+
+fn history_table(migrations: &[SchemaMigration]) -> String {
+    let mut rows = vec![[
+        "ID",
+        "NAME",
+        "APPLIED AT",
+        "DURATION",
+        "APPLIED BY",
+        "BATCH",
+    ]
+    .map(String::from)];
+
+    for m in migrations {
+        rows.push([
+            m.id.clone(),
+            m.name.clone().unwrap_or_default(),
+            m.applied_at
+                .map(|a| a.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_default(),
+            m.duration_ms
+                .map(|d| format!("{}ms", d))
+                .unwrap_or_default(),
+            m.applied_by.clone().unwrap_or_default(),
+            m.batch.map(|b| b.to_string()).unwrap_or_default(),
+        ]);
+    }
+
+    table(&rows)
+}
+
+fn table<const N: usize>(rows: &[[String; N]]) -> String {
+    let mut widths = [0; N];
+    for row in rows {
+        for (i, column) in row.iter().enumerate() {
+            widths[i] = widths[i].max(column.len());
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(i, column)| format!("{:width$}", column, width = widths[i]))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use std::path::PathBuf;
+
+    fn statuses() -> Vec<MigrationStatus> {
+        vec![
+            MigrationStatus {
+                id: 1700000000,
+                name: Some("create_users".to_string()),
+                state: MigrationState::Applied,
+                path: Some(PathBuf::from("./migrations/1700000000_create_users.up.sql")),
+                applied: Some(SchemaMigration {
+                    id: "1700000000".to_string(),
+                    ..Default::default()
+                }),
+            },
+            MigrationStatus {
+                id: 1700000001,
+                name: None,
+                state: MigrationState::MissingFile,
+                path: None,
+                applied: Some(SchemaMigration {
+                    id: "1700000001".to_string(),
+                    ..Default::default()
+                }),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_format_status_json() {
+        let output = format_status(&statuses(), "json").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(value[0]["id"], 1700000000);
+        assert_eq!(value[0]["state"], "applied");
+        assert_eq!(value[0]["applied"]["id"], "1700000000");
+        assert_eq!(value[1]["state"], "missing-file");
+        assert!(value[1]["path"].is_null());
+    }
+
+    #[test]
+    fn test_format_status_yaml() {
+        let output = format_status(&statuses(), "yaml").unwrap();
+
+        assert!(output.contains("state: applied"));
+        assert!(output.contains("state: missing-file"));
+        assert!(output.contains("name: create_users"));
+    }
+
+    #[test]
+    fn test_format_status_table() {
+        let output = format_status(&statuses(), "table").unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ID          STATE"));
+        assert!(lines[1].contains("applied       create_users"));
+        assert_eq!(lines[2], "1700000001  missing-file");
+    }
+
+    #[test]
+    fn test_format_status_unknown() {
+        assert!(format_status(&statuses(), "xml").is_err());
+    }
+
+    #[test]
+    fn test_check_exit_code() {
+        let mut s = statuses();
+        assert_eq!(check_exit_code(&s), EXIT_MISSING_MIGRATIONS);
+
+        s.pop();
+        assert_eq!(check_exit_code(&s), 0);
+        assert_eq!(check_exit_code(&[]), 0);
+
+        s[0].state = MigrationState::OutOfOrder;
+        assert_eq!(check_exit_code(&s), EXIT_PENDING_MIGRATIONS);
+
+        s[0].state = MigrationState::Pending;
+        assert_eq!(check_exit_code(&s), EXIT_PENDING_MIGRATIONS);
+    }
+
+    fn history() -> Vec<SchemaMigration> {
+        vec![
+            SchemaMigration {
+                id: "1700000000".to_string(),
+                ..Default::default()
+            },
+            SchemaMigration {
+                id: "1700000001".to_string(),
+                name: Some("add_index".to_string()),
+                applied_at: Some(Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()),
+                duration_ms: Some(42),
+                applied_by: Some("deploy".to_string()),
+                batch: Some(2),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_format_history_json() {
+        let output = format_history(&history(), "json").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert!(value[0]["applied_at"].is_null());
+        assert_eq!(value[1]["applied_at"], "2024-01-02T03:04:05Z");
+        assert_eq!(value[1]["duration_ms"], 42);
+        assert_eq!(value[1]["applied_by"], "deploy");
+        assert_eq!(value[1]["batch"], 2);
+    }
+
+    #[test]
+    fn test_format_history_table() {
+        let output = format_history(&history(), "table").unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ID          NAME"));
+        assert_eq!(lines[1], "1700000000");
+        assert_eq!(
+            lines[2],
+            "1700000001  add_index  2024-01-02 03:04:05 UTC  42ms      deploy      2"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use log::warn;
+use opentelemetry::context::Context;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::config;
+
+// Set by init, shutdown flushes the spans that are still queued
+static PROVIDER: Mutex<Option<SdkTracerProvider>> = Mutex::new(None);
+
+// Exports spans over OTLP/HTTP when an OTLP endpoint is configured, otherwise tracing stays off.
+// Returns the context to run the command in, so geni's spans join the TRACEPARENT trace.
+pub fn init() -> Context {
+    let Some(endpoint) = config::otlp_traces_endpoint() else {
+        return Context::new();
+    };
+
+    let provider = match tracer_provider(
+        endpoint,
+        config::otlp_headers(),
+        config::otel_service_name(),
+    ) {
+        Ok(provider) => provider,
+        Err(err) => {
+            warn!("Couldn't enable tracing: {:?}", err);
+            return Context::new();
+        }
+    };
+
+    let subscriber = tracing_subscriber::registry().with(layer(&provider));
+    if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
+        warn!("Couldn't enable tracing: {}", err);
+        return Context::new();
+    }
+    *PROVIDER.lock().unwrap() = Some(provider);
+
+    config::traceparent()
+        .and_then(|traceparent| parse_traceparent(&traceparent))
+        .unwrap_or_default()
+}
+
+// Exports the remaining spans, has to run before the process exits
+pub fn shutdown() {
+    let Some(provider) = PROVIDER.lock().unwrap().take() else {
+        return;
+    };
+
+    if let Err(err) = provider.shutdown() {
+        warn!("Couldn't export traces: {}", err);
+    }
+}
+
+fn tracer_provider(
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    service_name: String,
+) -> Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(endpoint)
+        .with_headers(headers.into_iter().collect::<HashMap<String, String>>())
+        .with_timeout(Duration::from_secs(5))
+        .build()?;
+
+    let resource = Resource::builder()
+        .with_service_name(service_name)
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .build();
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build())
+}
+
+fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let scope = InstrumentationScope::builder("geni")
+        .with_version(env!("CARGO_PKG_VERSION"))
+        .build();
+
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer_with_scope(scope))
+        .with_location(false)
+        .with_tracked_inactivity(false)
+        .with_threads(false)
+        .with_target(false)
+}
+
+// W3C traceparent, e.g. from the deploy pipeline, None when it isn't valid
+pub fn parse_traceparent(traceparent: &str) -> Option<Context> {
+    let carrier = HashMap::from([("traceparent".to_string(), traceparent.trim().to_string())]);
+    let context = TraceContextPropagator::new().extract(&carrier);
+
+    context.span().span_context().is_valid().then_some(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::context::FutureExt;
+    use serde_json::Value as JsonValue;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use tracing::{field, info_span, Instrument};
+
+    // Accepts a single OTLP request and hands its body to the test
+    fn local_collector() -> (String, mpsc::Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse::<usize>().unwrap();
+                }
+                headers.push_str(&line);
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+                .unwrap();
+            sender
+                .send((headers, String::from_utf8(body).unwrap()))
+                .unwrap();
+        });
+
+        (endpoint, receiver)
+    }
+
+    fn attribute<'a>(span: &'a JsonValue, key: &str) -> &'a JsonValue {
+        &span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["key"] == key)
+            .unwrap()["value"]
+    }
+
+    #[tokio::test]
+    async fn test_spans_are_exported_on_shutdown() {
+        let (endpoint, receiver) = local_collector();
+        let provider = tracer_provider(
+            endpoint,
+            vec![("x-api-key".to_string(), "secret".to_string())],
+            "geni".to_string(),
+        )
+        .unwrap();
+        let parent =
+            parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _default = tracing::subscriber::set_default(subscriber);
+        async {
+            let root = info_span!(
+                "geni.migrate",
+                direction = "up",
+                db.system = "sqlite",
+                outcome = field::Empty
+            );
+            async {
+                let migration = info_span!(
+                    "geni.migration",
+                    migration.id = 1700000000i64,
+                    db.system = "sqlite",
+                    outcome = field::Empty
+                );
+                migration.record("outcome", "failure");
+            }
+            .instrument(root.clone())
+            .await;
+            root.record("outcome", "success");
+        }
+        .with_context(parent)
+        .await;
+
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (headers, body) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(headers.to_lowercase().contains("x-api-key: secret"));
+
+        let body: JsonValue = serde_json::from_str(&body).unwrap();
+        let resource = &body["resourceSpans"][0];
+        assert_eq!(
+            attribute(&resource["resource"], "service.name")["stringValue"],
+            "geni"
+        );
+
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 2);
+        let (migration, root) = (&spans[0], &spans[1]);
+
+        assert_eq!(migration["name"], "geni.migration");
+        assert_eq!(
+            attribute(migration, "migration.id")["intValue"],
+            "1700000000"
+        );
+        assert_eq!(attribute(migration, "outcome")["stringValue"], "failure");
+        assert_eq!(migration["parentSpanId"], root["spanId"]);
+
+        assert_eq!(root["name"], "geni.migrate");
+        assert_eq!(attribute(root, "db.system")["stringValue"], "sqlite");
+        assert_eq!(attribute(root, "outcome")["stringValue"], "success");
+        assert_eq!(root["traceId"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(root["parentSpanId"], "b7ad6b7169203331");
+        assert_eq!(migration["traceId"], root["traceId"]);
+    }
+
+    #[test]
+    fn test_parse_traceparent() {
+        let context =
+            parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+        let span = context.span();
+        assert_eq!(
+            span.span_context().trace_id().to_string(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+        assert_eq!(
+            span.span_context().span_id().to_string(),
+            "b7ad6b7169203331"
+        );
+
+        assert!(
+            parse_traceparent("00-00000000000000000000000000000000-b7ad6b7169203331-01").is_none()
+        );
+        assert!(parse_traceparent("not-a-traceparent").is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{bail, Context, Result};
+use geni::database_drivers::utils::redact_database_url;
 use log::info;
 use std::ffi::OsString;
 use std::fs::{self, OpenOptions};
@@ -429,18 +430,6 @@ fn ssh_log_state(ssh_log_path: &Path, local_port: u16) -> Result<SshLogState> {
     Ok(SshLogState::None)
 }
 
-pub fn redact_database_url(database_url: &str) -> String {
-    let Ok(mut parsed) = Url::parse(database_url) else {
-        return database_url.to_string();
-    };
-
-    if parsed.password().is_some() {
-        let _ = parsed.set_password(Some("******"));
-    }
-
-    parsed.to_string()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -631,7 +620,7 @@ mod tests {
         drop(listener);
 
         let script = format!(
-            "#!/bin/sh\nprintf '%s\n' \"$@\" > \"{}\"\nlog_file=\"\"\nlocal_forward=\"\"\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-E\" ]; then\n    log_file=\"$2\"\n    shift 2\n    continue\n  fi\n  if [ \"$1\" = \"-L\" ]; then\n    local_forward=\"$2\"\n    shift 2\n    continue\n  fi\n  shift\n done\nlocal_port=$(printf '%s' \"$local_forward\" | cut -d: -f2)\npython3 - <<'PY' \"$local_port\" \"$log_file\"\nimport socket, sys\nport = int(sys.argv[1])\nlog_file = sys.argv[2]\nserver = socket.socket()\nserver.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)\nserver.bind(('127.0.0.1', port))\nserver.listen(1)\nwith open(log_file, 'a', encoding='utf-8') as fh:\n    fh.write(f'debug1: Local forwarding listening on 127.0.0.1 port {{port}}.\\n')\nwhile True:\n    conn, _ = server.accept()\n    conn.close()\nPY\n",
+            "#!/bin/sh\nprintf '%s\n' \"$@\" > \"{}\"\nlog_file=\"\"\nlocal_forward=\"\"\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-E\" ]; then\n    log_file=\"$2\"\n    shift 2\n    continue\n  fi\n  if [ \"$1\" = \"-L\" ]; then\n    local_forward=\"$2\"\n    shift 2\n    continue\n  fi\n  shift\n done\nlocal_port=$(printf '%s' \"$local_forward\" | cut -d: -f2)\nexec python3 - <<'PY' \"$local_port\" \"$log_file\"\nimport socket, sys\nport = int(sys.argv[1])\nlog_file = sys.argv[2]\nserver = socket.socket()\nserver.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)\nserver.bind(('127.0.0.1', port))\nserver.listen(1)\nwith open(log_file, 'a', encoding='utf-8') as fh:\n    fh.write(f'debug1: Local forwarding listening on 127.0.0.1 port {{port}}.\\n')\nwhile True:\n    conn, _ = server.accept()\n    conn.close()\nPY\n",
             args_path.display()
         );
 
@@ -701,7 +690,7 @@ mod tests {
         let attempted_ports_path = temp_dir.path().join("attempted-ports.txt");
 
         let script = format!(
-            "#!/bin/sh\ncount_file=\"{}\"\nattempts_file=\"{}\"\nlog_file=\"\"\nlocal_forward=\"\"\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-E\" ]; then\n    log_file=\"$2\"\n    shift 2\n    continue\n  fi\n  if [ \"$1\" = \"-L\" ]; then\n    local_forward=\"$2\"\n    shift 2\n    continue\n  fi\n  shift\n done\nlocal_port=$(printf '%s' \"$local_forward\" | cut -d: -f2)\ncount=1\nif [ -f \"$count_file\" ]; then\n  count=$(($(cat \"$count_file\") + 1))\nfi\nprintf '%s' \"$count\" > \"$count_file\"\nprintf '%s\n' \"$local_port\" >> \"$attempts_file\"\nif [ \"$count\" -eq 1 ]; then\n  printf 'bind [127.0.0.1]:%s: Address already in use\\n' \"$local_port\" >> \"$log_file\"\n  exit 1\nfi\npython3 - <<'PY' \"$local_port\" \"$log_file\"\nimport socket, sys\nport = int(sys.argv[1])\nlog_file = sys.argv[2]\nserver = socket.socket()\nserver.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)\nserver.bind(('127.0.0.1', port))\nserver.listen(1)\nwith open(log_file, 'a', encoding='utf-8') as fh:\n    fh.write(f'debug1: Local forwarding listening on 127.0.0.1 port {{port}}.\\n')\nwhile True:\n    conn, _ = server.accept()\n    conn.close()\nPY\n",
+            "#!/bin/sh\ncount_file=\"{}\"\nattempts_file=\"{}\"\nlog_file=\"\"\nlocal_forward=\"\"\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-E\" ]; then\n    log_file=\"$2\"\n    shift 2\n    continue\n  fi\n  if [ \"$1\" = \"-L\" ]; then\n    local_forward=\"$2\"\n    shift 2\n    continue\n  fi\n  shift\n done\nlocal_port=$(printf '%s' \"$local_forward\" | cut -d: -f2)\ncount=1\nif [ -f \"$count_file\" ]; then\n  count=$(($(cat \"$count_file\") + 1))\nfi\nprintf '%s' \"$count\" > \"$count_file\"\nprintf '%s\n' \"$local_port\" >> \"$attempts_file\"\nif [ \"$count\" -eq 1 ]; then\n  printf 'bind [127.0.0.1]:%s: Address already in use\\n' \"$local_port\" >> \"$log_file\"\n  exit 1\nfi\nexec python3 - <<'PY' \"$local_port\" \"$log_file\"\nimport socket, sys\nport = int(sys.argv[1])\nlog_file = sys.argv[2]\nserver = socket.socket()\nserver.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)\nserver.bind(('127.0.0.1', port))\nserver.listen(1)\nwith open(log_file, 'a', encoding='utf-8') as fh:\n    fh.write(f'debug1: Local forwarding listening on 127.0.0.1 port {{port}}.\\n')\nwhile True:\n    conn, _ = server.accept()\n    conn.close()\nPY\n",
             attempt_count_path.display(),
             attempted_ports_path.display()
         );
@@ -921,7 +910,7 @@ mod tests {
         drop(listener);
 
         let script = format!(
-            "#!/bin/sh\necho $$ > \"{}\"\nlog_file=\"\"\nlocal_forward=\"\"\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-E\" ]; then\n    log_file=\"$2\"\n    shift 2\n    continue\n  fi\n  if [ \"$1\" = \"-L\" ]; then\n    local_forward=\"$2\"\n    shift 2\n    continue\n  fi\n  shift\n done\nlocal_port=$(printf '%s' \"$local_forward\" | cut -d: -f2)\npython3 - <<'PY' \"$local_port\" \"$log_file\"\nimport socket, sys\nport = int(sys.argv[1])\nlog_file = sys.argv[2]\nserver = socket.socket()\nserver.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)\nserver.bind(('127.0.0.1', port))\nserver.listen(1)\nwith open(log_file, 'a', encoding='utf-8') as fh:\n    fh.write(f'debug1: Local forwarding listening on 127.0.0.1 port {{port}}.\\n')\nwhile True:\n    conn, _ = server.accept()\n    conn.close()\nPY\n",
+            "#!/bin/sh\necho $$ > \"{}\"\nlog_file=\"\"\nlocal_forward=\"\"\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = \"-E\" ]; then\n    log_file=\"$2\"\n    shift 2\n    continue\n  fi\n  if [ \"$1\" = \"-L\" ]; then\n    local_forward=\"$2\"\n    shift 2\n    continue\n  fi\n  shift\n done\nlocal_port=$(printf '%s' \"$local_forward\" | cut -d: -f2)\nexec python3 - <<'PY' \"$local_port\" \"$log_file\"\nimport socket, sys\nport = int(sys.argv[1])\nlog_file = sys.argv[2]\nserver = socket.socket()\nserver.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)\nserver.bind(('127.0.0.1', port))\nserver.listen(1)\nwith open(log_file, 'a', encoding='utf-8') as fh:\n    fh.write(f'debug1: Local forwarding listening on 127.0.0.1 port {{port}}.\\n')\nwhile True:\n    conn, _ = server.accept()\n    conn.close()\nPY\n",
             pid_path.display()
         );
         fs::write(&ssh_path, script)?;
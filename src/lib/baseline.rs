@@ -0,0 +1,115 @@
+use crate::database_drivers;
+use crate::utils::{applied_migration, applied_migration_ids, get_local_migrations, next_batch};
+use anyhow::{bail, Result};
+use log::info;
+use std::path::PathBuf;
+
+pub async fn baseline<F>(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    version: i64,
+    confirm: F,
+) -> Result<()>
+where
+    F: FnOnce(&[String]) -> bool,
+{
+    let path = PathBuf::from(&migration_folder);
+    let files = match get_local_migrations(&path, "up") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+
+    let ids = migrations_to_baseline(&files, version);
+    if ids.is_empty() {
+        bail!(
+            "Didn't find any migrations up to {} at {}",
+            version,
+            migration_folder
+        );
+    }
+
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    let migrations = database.get_or_create_schema_migrations().await?;
+
+    if !migrations.is_empty() && !confirm(&migrations) {
+        bail!(
+            "Aborted baseline, the migrations table already contains {} migrations",
+            migrations.len()
+        );
+    }
+
+    let applied = applied_migration_ids(&migrations);
+    let batch = next_batch(&mut database).await?;
+
+    for (id, file) in files
+        .iter()
+        .filter(|(id, _)| ids.contains(id) && !applied.contains(id))
+    {
+        database
+            .insert_schema_migration(&applied_migration(*id, file, None, batch))
+            .await?;
+        info!("Marked {} as applied", id);
+    }
+
+    Ok(())
+}
+
+fn migrations_to_baseline(files: &[(i64, PathBuf)], version: i64) -> Vec<i64> {
+    files
+        .iter()
+        .filter(|(id, _)| *id <= version)
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_to_baseline() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+            (1234567892, PathBuf::from("1234567892_add_posts.up.sql")),
+        ];
+
+        let result = migrations_to_baseline(&files, 1234567891);
+        assert_eq!(result, vec![1234567890, 1234567891]);
+    }
+
+    #[test]
+    fn test_migrations_to_baseline_version_between_migrations() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567895, PathBuf::from("1234567895_add_index.up.sql")),
+        ];
+
+        let result = migrations_to_baseline(&files, 1234567893);
+        assert_eq!(result, vec![1234567890]);
+    }
+
+    #[test]
+    fn test_migrations_to_baseline_none() {
+        let files = vec![(1234567890, PathBuf::from("1234567890_create_users.up.sql"))];
+
+        let result = migrations_to_baseline(&files, 1234567889);
+        assert!(result.is_empty());
+    }
+}
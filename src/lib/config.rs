@@ -35,6 +35,32 @@ impl Database {
     }
 }
 
+pub enum VersionScheme {
+    Timestamp,
+    DateTime,
+    Sequential,
+}
+
+#[allow(dead_code)]
+impl VersionScheme {
+    pub fn new(s: &str) -> Result<VersionScheme> {
+        match s {
+            "timestamp" => Ok(VersionScheme::Timestamp),
+            "datetime" => Ok(VersionScheme::DateTime),
+            "sequential" => Ok(VersionScheme::Sequential),
+            _ => bail!("Unknown migration version scheme"),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str> {
+        match self {
+            VersionScheme::Timestamp => Ok("timestamp"),
+            VersionScheme::DateTime => Ok("datetime"),
+            VersionScheme::Sequential => Ok("sequential"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +141,28 @@ mod tests {
             assert_eq!(db.as_str().unwrap(), expected);
         }
     }
+
+    #[test]
+    fn test_version_scheme_new() {
+        assert!(matches!(
+            VersionScheme::new("timestamp").unwrap(),
+            VersionScheme::Timestamp
+        ));
+        assert!(matches!(
+            VersionScheme::new("datetime").unwrap(),
+            VersionScheme::DateTime
+        ));
+        assert!(matches!(
+            VersionScheme::new("sequential").unwrap(),
+            VersionScheme::Sequential
+        ));
+        assert!(VersionScheme::new("uuid").is_err());
+    }
+
+    #[test]
+    fn test_version_scheme_as_str() {
+        assert_eq!(VersionScheme::Timestamp.as_str().unwrap(), "timestamp");
+        assert_eq!(VersionScheme::DateTime.as_str().unwrap(), "datetime");
+        assert_eq!(VersionScheme::Sequential.as_str().unwrap(), "sequential");
+    }
 }
@@ -1,11 +1,12 @@
-use crate::database_drivers::DatabaseDriver;
+use crate::database_drivers::{DatabaseDriver, DumpOptions, SchemaMigration};
+use crate::schema::Schema;
 use anyhow::{bail, Result};
 use libsql::{params, Builder, Connection};
 use log::info;
 use std::future::Future;
 use std::pin::Pin;
 
-use super::utils;
+use super::{sqlite_schema, utils};
 
 pub struct LibSQLDriver {
     db: Connection,
@@ -47,18 +48,51 @@ impl LibSQLDriver {
             schema_file,
         })
     }
+
+    // Empty when the migrations table doesn't exist
+    async fn migrations_table_columns(&mut self) -> Result<Vec<String>> {
+        let (_, name) = utils::split_table_name(&self.migrations_table);
+        let mut result = self
+            .db
+            .query(
+                format!(
+                    "PRAGMA table_info({});",
+                    utils::quote_identifier(name, "\"")
+                )
+                .as_str(),
+                params![],
+            )
+            .await?;
+
+        let mut columns: Vec<String> = vec![];
+        while let Some(row) = result.next().await? {
+            columns.push(row.get::<String>(1)?);
+        }
+
+        Ok(columns)
+    }
 }
 
 impl DatabaseDriver for LibSQLDriver {
-    fn execute<'a>(
+    fn execute_all<'a>(
         &'a mut self,
-        query: &'a str,
+        queries: &'a [&'a str],
         run_in_transaction: bool,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             if run_in_transaction {
-                self.db.execute_transactional_batch(query).await?;
-            } else {
+                let tx = self.db.transaction().await?;
+                for query in queries {
+                    if let Err(e) = tx.execute_batch(query).await {
+                        tx.rollback().await?;
+                        bail!(e)
+                    }
+                }
+                tx.commit().await?;
+                return Ok(());
+            }
+
+            for query in queries {
                 self.db.execute_batch(query).await?;
             }
 
@@ -85,24 +119,46 @@ impl DatabaseDriver for LibSQLDriver {
                 )
                 .await?;
 
-            let mut result = self
-                .db
-                .query(
-                    format!(" SELECT id FROM {} ORDER BY id DESC;", table).as_str(),
-                    params![],
-                )
-                .await?;
+            let columns = self.migrations_table_columns().await?;
+            for query in utils::add_tracking_columns_queries(&table, &columns) {
+                self.db.execute(query.as_str(), params![]).await?;
+            }
 
-            let mut schema_migrations: Vec<String> = vec![];
-            while let Some(row) = result.next().await.unwrap() {
-                if let Ok(r) = row.get_str(0) {
-                    schema_migrations.push(r.to_string());
-                    continue;
-                }
-                break;
+            let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+
+            Ok(migrations.into_iter().map(|m| m.id).collect())
+        };
+
+        Box::pin(fut)
+    }
+
+    fn get_schema_migrations(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<SchemaMigration>>, anyhow::Error>> + '_>>
+    {
+        let fut = async move {
+            let columns = self.migrations_table_columns().await?;
+            if columns.is_empty() {
+                return Ok(None);
             }
 
-            Ok(schema_migrations)
+            let table = utils::quote_identifier(&self.migrations_table, "\"");
+            let query = utils::select_schema_migrations_query(&table, &columns);
+            let mut result = self.db.query(query.as_str(), params![]).await?;
+
+            let mut schema_migrations: Vec<SchemaMigration> = vec![];
+            while let Some(row) = result.next().await? {
+                schema_migrations.push(SchemaMigration {
+                    id: row.get::<String>(0)?,
+                    name: row.get::<Option<String>>(1)?,
+                    applied_at: utils::parse_applied_at(row.get::<Option<String>>(2)?),
+                    duration_ms: row.get::<Option<i64>>(3)?,
+                    applied_by: row.get::<Option<String>>(4)?,
+                    batch: row.get::<Option<i64>>(5)?,
+                });
+            }
+
+            Ok(Some(utils::newest_first(schema_migrations)))
         };
 
         Box::pin(fut)
@@ -110,16 +166,28 @@ impl DatabaseDriver for LibSQLDriver {
 
     fn insert_schema_migration<'a>(
         &'a mut self,
-        id: &'a str,
+        migration: &'a SchemaMigration,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             let table = utils::quote_identifier(&self.migrations_table, "\"");
             self.db
                 .execute(
-                    format!("INSERT INTO {} (id) VALUES ('{}')", table, id).as_str(),
-                    params![],
+                    format!(
+                        "INSERT INTO {} (id, name, applied_at, duration_ms, applied_by, batch) VALUES (?, ?, ?, ?, ?, ?);",
+                        table
+                    )
+                    .as_str(),
+                    params![
+                        migration.id.as_str(),
+                        migration.name.clone(),
+                        utils::format_applied_at(&migration.applied_at),
+                        migration.duration_ms,
+                        migration.applied_by.clone(),
+                        migration.batch
+                    ],
                 )
                 .await?;
+
             Ok(())
         };
 
@@ -175,54 +243,54 @@ impl DatabaseDriver for LibSQLDriver {
         Box::pin(fut)
     }
 
-    fn dump_database_schema(
-        &mut self,
+    fn dump_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
-            let schema = r#"
-                --
-                -- LibSQL SQL Schema dump automatic generated by geni
-                --
-
-            "#;
-            let mut schema = schema
-                .lines()
-                .map(str::trim_start)
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            let mut result = self
-                .db
-                .query("SELECT sql FROM sqlite_master", params![])
-                .await?;
-
-            let mut schemas: Vec<String> = vec![];
-            while let Some(row) = result.next().await.unwrap_or(None) {
-                if let Ok(r) = row.get_str(0) {
-                    let text = r
-                        .to_string()
-                        .trim_start_matches('"')
-                        .trim_end_matches('"')
-                        .to_string()
-                        .replace("\\n", "\n");
-                    schemas.push(format!("{};", text));
-                }
-            }
-
-            schema.push_str(schemas.join("\n").as_str());
-
-            utils::write_to_schema_file(
+            let schema = self.render_database_schema(options).await?;
+            utils::write_schema(
                 schema,
                 self.migrations_folder.clone(),
                 self.schema_file.clone(),
+                options,
             )
-            .await?;
+            .await
+        };
 
-            Ok(())
+        Box::pin(fut)
+    }
+
+    fn render_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + '_>> {
+        let fut = async move {
+            let mut schema = sqlite_schema::dump(&self.db, "LibSQL", options).await?;
+
+            if options.migrations {
+                let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+                let table = utils::quote_identifier(&self.migrations_table, "\"");
+                utils::push_section(
+                    &mut schema,
+                    "MIGRATIONS",
+                    utils::schema_migration_inserts(&table, &migrations),
+                );
+            }
+
+            Ok(schema)
         };
 
         Box::pin(fut)
     }
+
+    fn introspect_database_schema(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Schema, anyhow::Error>> + '_>> {
+        let fut = async move { sqlite_schema::introspect(&self.db).await };
+
+        Box::pin(fut)
+    }
 }
 
 #[cfg(test)]
@@ -1,4 +1,5 @@
-use crate::database_drivers::DatabaseDriver;
+use crate::database_drivers::{DatabaseDriver, DumpOptions, SchemaMigration};
+use crate::schema::Schema;
 use anyhow::{bail, Result};
 use log::info;
 use sqlx::mysql::MySqlRow;
@@ -7,7 +8,7 @@ use sqlx::{Connection, MySqlConnection, Row};
 use std::future::Future;
 use std::pin::Pin;
 
-use super::utils;
+use super::{mysql_schema, utils};
 
 pub struct MariaDBDriver {
     db: MySqlConnection,
@@ -69,30 +70,46 @@ impl MariaDBDriver {
 
         Ok(m)
     }
+
+    // Empty when the migrations table doesn't exist
+    async fn migrations_table_columns(&mut self) -> Result<Vec<String>> {
+        let (schema, name) = utils::split_table_name(&self.migrations_table);
+
+        let columns: Vec<String> = sqlx::query(
+            "SELECT CAST(COLUMN_NAME AS CHAR) FROM information_schema.columns WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ?",
+        )
+        .bind(schema)
+        .bind(name)
+        .map(|row: MySqlRow| row.get(0))
+        .fetch_all(&mut self.db)
+        .await?;
+
+        Ok(columns)
+    }
 }
 
 impl DatabaseDriver for MariaDBDriver {
-    fn execute<'a>(
+    fn execute_all<'a>(
         &'a mut self,
-        query: &'a str,
+        queries: &'a [&'a str],
         run_in_transaction: bool,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             if run_in_transaction {
                 let mut tx = self.db.begin().await?;
-                match tx.execute(query).await {
-                    Ok(_) => {
-                        tx.commit().await?;
-                    }
-                    Err(e) => {
+                for query in queries {
+                    if let Err(e) = tx.execute(*query).await {
                         tx.rollback().await?;
                         bail!(e)
                     }
                 }
+                tx.commit().await?;
                 return Ok(());
             }
 
-            self.db.execute(query).await?;
+            for query in queries {
+                self.db.execute(*query).await?;
+            }
 
             Ok(())
         };
@@ -111,13 +128,46 @@ impl DatabaseDriver for MariaDBDriver {
                 table,
             );
             sqlx::query(query.as_str()).execute(&mut self.db).await?;
-            let query = format!("SELECT id FROM {} ORDER BY id DESC", table);
-            let result: Vec<String> = sqlx::query(query.as_str())
-                .map(|row: MySqlRow| row.get("id"))
+
+            let columns = self.migrations_table_columns().await?;
+            for query in utils::add_tracking_columns_queries(&table, &columns) {
+                sqlx::query(query.as_str()).execute(&mut self.db).await?;
+            }
+
+            let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+
+            Ok(migrations.into_iter().map(|m| m.id).collect())
+        };
+
+        Box::pin(fut)
+    }
+
+    fn get_schema_migrations(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<SchemaMigration>>, anyhow::Error>> + '_>>
+    {
+        let fut = async move {
+            let columns = self.migrations_table_columns().await?;
+            if columns.is_empty() {
+                return Ok(None);
+            }
+
+            let table = utils::quote_identifier(&self.migrations_table, "`");
+            let query = utils::select_schema_migrations_query(&table, &columns);
+
+            let result: Vec<SchemaMigration> = sqlx::query(query.as_str())
+                .map(|row: MySqlRow| SchemaMigration {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    applied_at: utils::parse_applied_at(row.get("applied_at")),
+                    duration_ms: row.get("duration_ms"),
+                    applied_by: row.get("applied_by"),
+                    batch: row.get("batch"),
+                })
                 .fetch_all(&mut self.db)
                 .await?;
 
-            Ok(result)
+            Ok(Some(utils::newest_first(result)))
         };
 
         Box::pin(fut)
@@ -125,13 +175,21 @@ impl DatabaseDriver for MariaDBDriver {
 
     fn insert_schema_migration<'a>(
         &'a mut self,
-        id: &'a str,
+        migration: &'a SchemaMigration,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             let table = utils::quote_identifier(&self.migrations_table, "`");
-            let query = format!("INSERT INTO {} (id) VALUES (?)", table);
+            let query = format!(
+                "INSERT INTO {} (id, name, applied_at, duration_ms, applied_by, batch) VALUES (?, ?, ?, ?, ?, ?)",
+                table
+            );
             sqlx::query(query.as_str())
-                .bind(id)
+                .bind(&migration.id)
+                .bind(&migration.name)
+                .bind(utils::format_applied_at(&migration.applied_at))
+                .bind(migration.duration_ms)
+                .bind(&migration.applied_by)
+                .bind(migration.batch)
                 .execute(&mut self.db)
                 .await?;
             Ok(())
@@ -191,283 +249,51 @@ impl DatabaseDriver for MariaDBDriver {
         Box::pin(fut)
     }
 
-    fn dump_database_schema(
-        &mut self,
+    fn dump_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
-            let schema = r#"
-                --
-                -- MySQL SQL Schema dump automatic generated by geni
-                --
-
-
-            "#;
-
-            let mut schema = schema
-                .lines()
-                .map(str::trim_start)
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            let tables: Vec<String> = sqlx::query(
-                r#"
-                SELECT 
-                    CONCAT(
-                        'CREATE TABLE ', 
-                        TABLE_NAME, 
-                        ' (\n',
-                        GROUP_CONCAT(
-                            CONCAT(
-                                '  ', COLUMN_NAME, ' ', COLUMN_TYPE,
-                                IF(IS_NULLABLE = 'NO', ' NOT NULL', ''),
-                                IF(COLUMN_DEFAULT IS NOT NULL, CONCAT(' DEFAULT ', COLUMN_DEFAULT), '')
-                            ) 
-                            ORDER BY COLUMN_NAME ASC
-                            SEPARATOR', \n'
-                        ),
-                        '\n);'
-                    ) AS create_table_stmt
-                FROM 
-                    INFORMATION_SCHEMA.COLUMNS
-                WHERE 
-                    TABLE_SCHEMA = ? AND TABLE_NAME NOT IN (SELECT TABLE_NAME FROM INFORMATION_SCHEMA.VIEWS WHERE TABLE_SCHEMA = ?)
-                GROUP BY 
-                    TABLE_NAME
-                ORDER BY 
-                    TABLE_NAME;
-                "#,
-            )
-            .bind(&self.db_name)
-            .bind(&self.db_name)
-            .map(|row: MySqlRow| row.get("create_table_stmt"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !tables.is_empty() {
-                schema.push_str("-- TABLES \n\n");
-                for ele in tables.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
-
-            let views: Vec<String> = sqlx::query(
-                r#"
-                SELECT 
-                    CONCAT(
-                        'CREATE VIEW ', 
-                        TABLE_NAME, 
-                        ' AS ', 
-                        VIEW_DEFINITION, 
-                        ';'
-                    ) AS create_view_stmt
-                FROM 
-                    INFORMATION_SCHEMA.VIEWS
-                WHERE 
-                    TABLE_SCHEMA = ?
-                ORDER BY TABLE_SCHEMA asc
-                "#,
+            let schema = self.render_database_schema(options).await?;
+            utils::write_schema(
+                schema,
+                self.migrations_folder.clone(),
+                self.schema_file.clone(),
+                options,
             )
-            .bind(&self.db_name)
-            .map(|row: MySqlRow| row.get("create_view_stmt"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !views.is_empty() {
-                schema.push_str("-- VIEWS \n\n");
-                for ele in views.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
-
-            let constraints: Vec<String> = sqlx::query(
-                r#"
-                    SELECT
-                        CONCAT(
-                            'ALTER TABLE ',
-                            TABLE_NAME,
-                            ' ADD CONSTRAINT ',
-                            CASE
-                                WHEN CONSTRAINT_NAME = 'PRIMARY' THEN 'PRIMARY KEY'
-                                WHEN INDEX_NAME != 'PRIMARY' AND INDEX_NAME IS NOT NULL THEN 'UNIQUE'
-                                ELSE 'FOREIGN KEY'
-                            END,
-                            ' (',
-                            GROUP_CONCAT(COLUMN_NAME ORDER BY ORDINAL_POSITION SEPARATOR ', '),
-                            CASE
-                                WHEN MAX(REFERENCED_TABLE_NAME) IS NOT NULL THEN
-                                    CONCAT(') REFERENCES ', MAX(REFERENCED_TABLE_NAME), ' (', GROUP_CONCAT(REFERENCED_COLUMN_NAME ORDER BY ORDINAL_POSITION SEPARATOR ', '), ')',
-                                    IF(MAX(DELETE_RULE) != 'RESTRICT' AND MAX(DELETE_RULE) != 'NO ACTION', CONCAT(' ON DELETE ', MAX(DELETE_RULE)), ''),
-                                    IF(MAX(UPDATE_RULE) != 'RESTRICT' AND MAX(UPDATE_RULE) != 'NO ACTION', CONCAT(' ON UPDATE ', MAX(UPDATE_RULE)), ''))
-                                ELSE ')'
-                            END,
-                            ';'
-                        ) AS create_constraint_stmt,
-                        TABLE_NAME
-                    FROM
-                        (
-                        SELECT
-                            TABLE_NAME,
-                            COLUMN_NAME,
-                            CONSTRAINT_NAME,
-                            NULL AS INDEX_NAME,
-                            NULL AS REFERENCED_TABLE_NAME,
-                            NULL AS REFERENCED_COLUMN_NAME,
-                            ORDINAL_POSITION,
-                            NULL AS DELETE_RULE,
-                            NULL AS UPDATE_RULE
-                        FROM
-                            INFORMATION_SCHEMA.KEY_COLUMN_USAGE
-                        WHERE
-                            TABLE_SCHEMA = ?
-                            AND CONSTRAINT_NAME = 'PRIMARY'
-                        UNION ALL
-                        SELECT
-                            TABLE_NAME,
-                            COLUMN_NAME,
-                            NULL AS CONSTRAINT_NAME,
-                            INDEX_NAME,
-                            NULL AS REFERENCED_TABLE_NAME,
-                            NULL AS REFERENCED_COLUMN_NAME,
-                            SEQ_IN_INDEX AS ORDINAL_POSITION,
-                            NULL AS DELETE_RULE,
-                            NULL AS UPDATE_RULE
-                        FROM
-                            INFORMATION_SCHEMA.STATISTICS
-                        WHERE
-                            TABLE_SCHEMA = ?
-                            AND NON_UNIQUE = 0
-                            AND INDEX_NAME != 'PRIMARY'
-                        UNION ALL
-                        SELECT
-                            kcu.TABLE_NAME,
-                            kcu.COLUMN_NAME,
-                            kcu.CONSTRAINT_NAME,
-                            NULL AS INDEX_NAME,
-                            kcu.REFERENCED_TABLE_NAME,
-                            kcu.REFERENCED_COLUMN_NAME,
-                            kcu.ORDINAL_POSITION,
-                            rc.DELETE_RULE,
-                            rc.UPDATE_RULE
-                        FROM
-                            INFORMATION_SCHEMA.KEY_COLUMN_USAGE kcu
-                        JOIN
-                            INFORMATION_SCHEMA.REFERENTIAL_CONSTRAINTS rc
-                            ON kcu.CONSTRAINT_NAME = rc.CONSTRAINT_NAME
-                            AND kcu.TABLE_SCHEMA = rc.CONSTRAINT_SCHEMA
-                        WHERE
-                            kcu.TABLE_SCHEMA = ?
-                            AND kcu.REFERENCED_TABLE_NAME IS NOT NULL
-                        ) AS constraints
-                    GROUP BY
-                        TABLE_NAME,
-                        CONSTRAINT_NAME,
-                        INDEX_NAME
-                    ORDER BY
-                        TABLE_NAME,
-                        CONSTRAINT_NAME,
-                        INDEX_NAME
-                "#,
-                )
-                .bind(&self.db_name)
-                .bind(&self.db_name)
-                .bind(&self.db_name)
-                .map(|row: MySqlRow| row.get("create_constraint_stmt"))
-                .fetch_all(&mut self.db)
-                .await?;
+            .await
+        };
 
-            if !constraints.is_empty() {
-                schema.push_str("-- CONSTRAINTS \n\n");
-                for ele in constraints.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
+        Box::pin(fut)
+    }
 
-            let indexes: Vec<String> = sqlx::query(
-                r#"
-                    SELECT
-                        CONCAT(
-                            'CREATE INDEX ',
-                            INDEX_NAME,
-                            ' ON ',
-                            TABLE_NAME,
-                            ' (',
-                            GROUP_CONCAT(COLUMN_NAME ORDER BY SEQ_IN_INDEX SEPARATOR ', '),
-                            ');'
-                        ) AS create_index_stmt
-                    FROM
-                        INFORMATION_SCHEMA.STATISTICS
-                    WHERE
-                        TABLE_SCHEMA = ?
-                        AND INDEX_NAME != 'PRIMARY'
-                    GROUP BY
-                        TABLE_NAME, INDEX_NAME
-                    ORDER BY
-                        TABLE_NAME, INDEX_NAME
-                "#,
-            )
-            .bind(&self.db_name)
-            .map(|row: MySqlRow| row.get("create_index_stmt"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !indexes.is_empty() {
-                schema.push_str("-- INDEXES \n\n");
-                for ele in indexes.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
+    fn render_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + '_>> {
+        let fut = async move {
+            let mut schema = mysql_schema::dump(&mut self.db, &self.db_name).await?;
+
+            if options.migrations {
+                let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+                let table = utils::quote_identifier(&self.migrations_table, "`");
+                utils::push_section(
+                    &mut schema,
+                    "MIGRATIONS",
+                    utils::schema_migration_inserts(&table, &migrations),
+                );
             }
 
-            let comments: Vec<String> = sqlx::query(
-                r#"
-                SELECT 
-                    CONCAT(
-                        CASE 
-                            WHEN TABLE_COMMENT IS NOT NULL THEN 
-                                CONCAT('ALTER TABLE ', TABLE_NAME, ' COMMENT = ''', TABLE_COMMENT, ''';')
-                            ELSE 
-                                CONCAT('ALTER TABLE ', TABLE_NAME, ' MODIFY COLUMN ', COLUMN_NAME, ' COMMENT ''', COLUMN_COMMENT, ''';')
-                        END
-                    ) AS comment_stmt
-                FROM 
-                    (
-                        SELECT TABLE_NAME, TABLE_COMMENT, NULL AS COLUMN_NAME, NULL AS COLUMN_COMMENT
-                        FROM INFORMATION_SCHEMA.TABLES
-                        WHERE TABLE_SCHEMA = ? AND (TABLE_COMMENT IS NOT NULL OR TABLE_COMMENT != '')
-                        UNION ALL
-                        SELECT TABLE_NAME, NULL, COLUMN_NAME, COLUMN_COMMENT
-                        FROM INFORMATION_SCHEMA.COLUMNS
-                        WHERE TABLE_SCHEMA = ? AND (COLUMN_COMMENT IS NOT NULL OR COLUMN_COMMENT != '')
-                    ) AS comments
-                ORDER BY TABLE_NAME, COLUMN_NAME
-                "#,
-            )
-            .bind(&self.db_name)
-            .bind(&self.db_name)
-            .map(|row: MySqlRow| row.get("comment_stmt"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !comments.is_empty() {
-                schema.push_str("-- COMMENTS \n\n");
-                for ele in comments.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
+            Ok(schema)
+        };
 
-            utils::write_to_schema_file(
-                schema.to_string(),
-                self.migrations_folder.clone(),
-                self.schema_file.clone(),
-            )
-            .await?;
+        Box::pin(fut)
+    }
 
-            Ok(())
-        };
+    fn introspect_database_schema(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Schema, anyhow::Error>> + '_>> {
+        let fut = async move { mysql_schema::introspect(&mut self.db, &self.db_name).await };
 
         Box::pin(fut)
     }
@@ -1,20 +1,43 @@
 use crate::config;
+use crate::schema::Schema;
 use anyhow::bail;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::future::Future;
 use std::pin::Pin;
+use tracing::{field, info_span, Instrument};
 
 pub mod libsql;
 pub mod maria;
 pub mod mysql;
+mod mysql_schema;
+mod pg_catalog;
 pub mod postgres;
 pub mod sqlite;
+mod sqlite_schema;
 pub mod turso;
 pub mod utils;
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
 pub struct SchemaMigration {
     pub id: String,
+    pub name: Option<String>,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<i64>,
+    pub applied_by: Option<String>,
+    // Migrations applied by the same run share a batch
+    pub batch: Option<i64>,
+}
+
+// What goes into the schema dump next to the schema itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DumpOptions {
+    // PRAGMA user_version, only SQLite, libSQL and Turso have it
+    pub user_version: bool,
+    // The rows of the migrations table
+    pub migrations: bool,
+    // One file per object in a directory named after the schema file instead of the schema file
+    pub split: bool,
 }
 
 // DatabaseDriver is a trait that all database drivers must implement
@@ -24,6 +47,15 @@ pub trait DatabaseDriver {
         &'a mut self,
         query: &'a str,
         run_in_transaction: bool,
+    ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
+        Box::pin(async move { self.execute_all(&[query], run_in_transaction).await })
+    }
+
+    // execute the queries one after another on the same connection, in one transaction when asked
+    fn execute_all<'a>(
+        &'a mut self,
+        queries: &'a [&'a str],
+        run_in_transaction: bool,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>>;
 
     // create database with the specific driver
@@ -37,10 +69,16 @@ pub trait DatabaseDriver {
         &mut self,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<String>, anyhow::Error>> + '_>>;
 
+    // get the full schema migration rows without creating anything, None when the table doesn't exist
+    #[allow(clippy::type_complexity)]
+    fn get_schema_migrations(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<SchemaMigration>>, anyhow::Error>> + '_>>;
+
     // insert new schema migration
     fn insert_schema_migration<'a>(
         &'a mut self,
-        id: &'a str,
+        migration: &'a SchemaMigration,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>>;
 
     // remove schema migration from the schema migrations table
@@ -52,10 +90,22 @@ pub trait DatabaseDriver {
     // create database with the specific driver
     fn ready(&mut self) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>>;
 
-    // dump the database
-    fn dump_database_schema(
-        &mut self,
+    // dump the database to the schema file
+    fn dump_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>>;
+
+    // render the schema dump without writing it
+    fn render_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + '_>>;
+
+    // read the tables, indexes, constraints, views, sequences and functions into the schema model
+    fn introspect_database_schema(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Schema, anyhow::Error>> + '_>>;
 }
 
 // Creates a new database driver based on the database_url
@@ -67,6 +117,36 @@ pub async fn new(
     schema_file: String,
     wait_timeout: Option<usize>,
     with_selected_database: bool,
+) -> Result<Box<dyn DatabaseDriver>, anyhow::Error> {
+    let span = info_span!(
+        "geni.connect",
+        db.system = crate::utils::database_kind(&db_url),
+        outcome = field::Empty,
+    );
+    let result = connect(
+        db_url,
+        db_token,
+        migrations_table,
+        migrations_folder,
+        schema_file,
+        wait_timeout,
+        with_selected_database,
+    )
+    .instrument(span.clone())
+    .await;
+    span.record("outcome", crate::utils::outcome(&result));
+
+    result
+}
+
+async fn connect(
+    db_url: String,
+    db_token: Option<String>,
+    migrations_table: String,
+    migrations_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    with_selected_database: bool,
 ) -> Result<Box<dyn DatabaseDriver>, anyhow::Error> {
     let mut parsed_db_url = url::Url::parse(&db_url)?;
 
@@ -170,6 +250,7 @@ mod tests {
         // Test that SchemaMigration can be created and has the expected fields
         let migration = SchemaMigration {
             id: "1234567890".to_string(),
+            ..Default::default()
         };
 
         assert_eq!(migration.id, "1234567890");
@@ -0,0 +1,740 @@
+// Builds the MySQL/MariaDB schema dump from the SHOW CREATE statements of every object, normalised
+// so the output doesn't depend on who created the objects or how many rows have been inserted.
+use super::utils::push_section;
+use crate::schema::{
+    Column, Constraint, ConstraintKind, Function, Identity, Index, Reference, Schema, Sequence,
+    Table, View,
+};
+use anyhow::{bail, Result};
+use regex::Regex;
+use sqlx::mysql::MySqlRow;
+use sqlx::{ColumnIndex, MySqlConnection, Row};
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+static AUTO_INCREMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r" AUTO_INCREMENT=\d+").unwrap());
+
+static DEFINER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\s*DEFINER\s*=\s*(`[^`]*`|'[^']*'|[^\s@]+)@(`[^`]*`|'[^']*'|\S+)").unwrap()
+});
+
+static EVENT_START: LazyLock<Regex> = LazyLock::new(|| Regex::new(r" STARTS '[^']*'").unwrap());
+
+pub async fn dump(conn: &mut MySqlConnection, database: &str) -> Result<String> {
+    let mut schema = [
+        "--",
+        "-- MySQL SQL Schema dump automatic generated by geni",
+        "--",
+        "",
+        "SET FOREIGN_KEY_CHECKS = 0;",
+        "",
+        "",
+    ]
+    .join("\n");
+
+    push_section(&mut schema, "TABLES", tables(conn, database).await?);
+    push_section(&mut schema, "ROUTINES", routines(conn, database).await?);
+    push_section(&mut schema, "VIEWS", views(conn, database).await?);
+    push_section(&mut schema, "TRIGGERS", triggers(conn, database).await?);
+    push_section(&mut schema, "EVENTS", events(conn, database).await?);
+
+    schema.push_str("SET FOREIGN_KEY_CHECKS = 1;\n\n");
+
+    Ok(schema)
+}
+
+// Information schema and SHOW columns are flagged as binary on some server versions
+fn optional_text<I: ColumnIndex<MySqlRow>>(row: &MySqlRow, index: I) -> Result<Option<String>> {
+    let bytes: Option<Vec<u8>> = row.try_get(index)?;
+    Ok(bytes.map(|b| String::from_utf8_lossy(&b).to_string()))
+}
+
+fn text<I: ColumnIndex<MySqlRow>>(row: &MySqlRow, index: I) -> Result<String> {
+    Ok(optional_text(row, index)?.unwrap_or_default())
+}
+
+async fn rows(
+    conn: &mut MySqlConnection,
+    query: &str,
+    database: &str,
+) -> Result<Vec<Vec<Option<String>>>> {
+    let rows = sqlx::query(query).bind(database).fetch_all(conn).await?;
+
+    rows.iter()
+        .map(|row| (0..row.len()).map(|i| optional_text(row, i)).collect())
+        .collect()
+}
+
+async fn names(
+    conn: &mut MySqlConnection,
+    query: &str,
+    database: &str,
+) -> Result<Vec<Vec<String>>> {
+    Ok(rows(conn, query, database)
+        .await?
+        .into_iter()
+        .map(|row| row.into_iter().map(Option::unwrap_or_default).collect())
+        .collect())
+}
+
+async fn show_create(
+    conn: &mut MySqlConnection,
+    kind: &str,
+    name: &str,
+    column: &str,
+) -> Result<String> {
+    let query = format!("SHOW CREATE {} {}", kind, quote_ident(name));
+    let row = sqlx::query(&query).fetch_one(conn).await?;
+    let statement = text(&row, column)?;
+    if statement.is_empty() {
+        bail!(
+            "SHOW CREATE {} {} returned no definition, the user needs privileges to read it",
+            kind,
+            name
+        );
+    }
+
+    Ok(statement)
+}
+
+async fn tables(conn: &mut MySqlConnection, database: &str) -> Result<Vec<String>> {
+    let tables = names(
+        conn,
+        r#"
+        SELECT TABLE_NAME, TABLE_TYPE
+        FROM INFORMATION_SCHEMA.TABLES
+        WHERE TABLE_SCHEMA = ? AND TABLE_TYPE IN ('BASE TABLE', 'SEQUENCE')
+        ORDER BY TABLE_NAME
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut statements = vec![];
+    for table in tables {
+        // MariaDB sequences are tables too but only SHOW CREATE SEQUENCE gives a usable definition
+        let statement = if table[1] == "SEQUENCE" {
+            show_create(conn, "SEQUENCE", &table[0], "Create Table").await?
+        } else {
+            show_create(conn, "TABLE", &table[0], "Create Table").await?
+        };
+        statements.push(format!("{};", strip_auto_increment(&statement)));
+    }
+
+    Ok(statements)
+}
+
+async fn routines(conn: &mut MySqlConnection, database: &str) -> Result<Vec<String>> {
+    Ok(functions(conn, database)
+        .await?
+        .into_iter()
+        .map(|f| f.definition)
+        .collect())
+}
+
+async fn functions(conn: &mut MySqlConnection, database: &str) -> Result<Vec<Function>> {
+    let routines = names(
+        conn,
+        r#"
+        SELECT
+            r.ROUTINE_NAME,
+            r.ROUTINE_TYPE,
+            (
+                SELECT GROUP_CONCAT(
+                    CONCAT_WS(' ', p.PARAMETER_MODE, p.PARAMETER_NAME, p.DTD_IDENTIFIER)
+                    ORDER BY p.ORDINAL_POSITION SEPARATOR ', '
+                )
+                FROM INFORMATION_SCHEMA.PARAMETERS p
+                WHERE p.SPECIFIC_SCHEMA = r.ROUTINE_SCHEMA AND p.SPECIFIC_NAME = r.SPECIFIC_NAME
+                    AND p.ROUTINE_TYPE = r.ROUTINE_TYPE AND p.ORDINAL_POSITION > 0
+            )
+        FROM INFORMATION_SCHEMA.ROUTINES r
+        WHERE r.ROUTINE_SCHEMA = ? AND r.ROUTINE_TYPE IN ('FUNCTION', 'PROCEDURE')
+        ORDER BY r.ROUTINE_TYPE, r.ROUTINE_NAME
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut functions = vec![];
+    for routine in routines {
+        let column = if routine[1] == "FUNCTION" {
+            "Create Function"
+        } else {
+            "Create Procedure"
+        };
+        let statement = show_create(conn, &routine[1], &routine[0], column).await?;
+        functions.push(Function {
+            schema: None,
+            name: routine[0].clone(),
+            kind: routine[1].clone(),
+            arguments: routine[2].clone(),
+            definition: format!("{};", strip_definer(&statement)),
+        });
+    }
+
+    Ok(functions)
+}
+
+async fn views(conn: &mut MySqlConnection, database: &str) -> Result<Vec<String>> {
+    Ok(sort_views(view_definitions(conn, database).await?))
+}
+
+// (name, statement) of every view in name order
+async fn view_definitions(
+    conn: &mut MySqlConnection,
+    database: &str,
+) -> Result<Vec<(String, String)>> {
+    let names = names(
+        conn,
+        r#"
+        SELECT TABLE_NAME
+        FROM INFORMATION_SCHEMA.VIEWS
+        WHERE TABLE_SCHEMA = ?
+        ORDER BY TABLE_NAME
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut views = vec![];
+    for name in names {
+        let statement = show_create(conn, "VIEW", &name[0], "Create View").await?;
+        let statement = strip_database(&strip_definer(&statement), database);
+        views.push((name[0].clone(), format!("{};", statement)));
+    }
+
+    Ok(views)
+}
+
+async fn triggers(conn: &mut MySqlConnection, database: &str) -> Result<Vec<String>> {
+    let names = names(
+        conn,
+        r#"
+        SELECT TRIGGER_NAME
+        FROM INFORMATION_SCHEMA.TRIGGERS
+        WHERE TRIGGER_SCHEMA = ?
+        ORDER BY EVENT_OBJECT_TABLE, ACTION_TIMING, EVENT_MANIPULATION, ACTION_ORDER, TRIGGER_NAME
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut statements = vec![];
+    for name in names {
+        let statement = show_create(conn, "TRIGGER", &name[0], "SQL Original Statement").await?;
+        statements.push(format!("{};", strip_definer(&statement)));
+    }
+
+    Ok(statements)
+}
+
+async fn events(conn: &mut MySqlConnection, database: &str) -> Result<Vec<String>> {
+    let names = names(
+        conn,
+        r#"
+        SELECT EVENT_NAME
+        FROM INFORMATION_SCHEMA.EVENTS
+        WHERE EVENT_SCHEMA = ?
+        ORDER BY EVENT_NAME
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut statements = vec![];
+    for name in names {
+        let statement = show_create(conn, "EVENT", &name[0], "Create Event").await?;
+        statements.push(format!(
+            "{};",
+            strip_event_start(&strip_definer(&statement))
+        ));
+    }
+
+    Ok(statements)
+}
+
+// Reads the schema model from the information schema, views and routines are the statements the
+// dump contains
+pub async fn introspect(conn: &mut MySqlConnection, database: &str) -> Result<Schema> {
+    let mut schema = Schema {
+        tables: tables_and_columns(conn, database).await?,
+        constraints: constraints(conn, database).await?,
+        ..Default::default()
+    };
+    schema.indexes = indexes(conn, database, &schema.constraints).await?;
+    schema.views = view_definitions(conn, database)
+        .await?
+        .into_iter()
+        .map(|(name, definition)| View {
+            schema: None,
+            name,
+            materialized: false,
+            definition,
+        })
+        .collect();
+    schema.sequences = sequences(conn, database).await?;
+    schema.functions = functions(conn, database).await?;
+
+    Ok(schema)
+}
+
+async fn tables_and_columns(conn: &mut MySqlConnection, database: &str) -> Result<Vec<Table>> {
+    let rows = rows(
+        conn,
+        r#"
+        SELECT
+            c.TABLE_NAME,
+            c.COLUMN_NAME,
+            c.COLUMN_TYPE,
+            c.IS_NULLABLE,
+            c.COLUMN_DEFAULT,
+            c.EXTRA,
+            c.GENERATION_EXPRESSION,
+            CASE WHEN c.COLLATION_NAME <> t.TABLE_COLLATION THEN c.COLLATION_NAME END
+        FROM INFORMATION_SCHEMA.COLUMNS c
+        JOIN INFORMATION_SCHEMA.TABLES t
+            ON t.TABLE_SCHEMA = c.TABLE_SCHEMA AND t.TABLE_NAME = c.TABLE_NAME
+        WHERE c.TABLE_SCHEMA = ? AND t.TABLE_TYPE = 'BASE TABLE'
+        ORDER BY c.TABLE_NAME, c.ORDINAL_POSITION
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut tables: Vec<Table> = vec![];
+    for row in rows {
+        let value = |i: usize| row[i].clone().unwrap_or_default();
+        let generated = row[6].clone().filter(|expression| !expression.is_empty());
+        let column = Column {
+            name: value(1),
+            data_type: value(2),
+            nullable: value(3) == "YES",
+            // MariaDB reports a missing default as the text NULL
+            default: row[4]
+                .clone()
+                .filter(|default| generated.is_none() && default != "NULL"),
+            identity: value(5)
+                .to_lowercase()
+                .contains("auto_increment")
+                .then_some(Identity::AutoIncrement),
+            generated,
+            collation: row[7].clone(),
+        };
+
+        match tables.last_mut() {
+            Some(table) if table.name == value(0) => table.columns.push(column),
+            _ => tables.push(Table {
+                schema: None,
+                name: value(0),
+                columns: vec![column],
+            }),
+        }
+    }
+
+    Ok(tables)
+}
+
+async fn constraints(conn: &mut MySqlConnection, database: &str) -> Result<Vec<Constraint>> {
+    let keys = rows(
+        conn,
+        r#"
+        SELECT
+            tc.TABLE_NAME,
+            tc.CONSTRAINT_NAME,
+            tc.CONSTRAINT_TYPE,
+            k.COLUMN_NAME,
+            k.REFERENCED_TABLE_NAME,
+            k.REFERENCED_COLUMN_NAME,
+            r.UPDATE_RULE,
+            r.DELETE_RULE
+        FROM INFORMATION_SCHEMA.TABLE_CONSTRAINTS tc
+        JOIN INFORMATION_SCHEMA.KEY_COLUMN_USAGE k
+            ON k.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA AND k.TABLE_NAME = tc.TABLE_NAME
+            AND k.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+        LEFT JOIN INFORMATION_SCHEMA.REFERENTIAL_CONSTRAINTS r
+            ON r.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA AND r.TABLE_NAME = tc.TABLE_NAME
+            AND r.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+        WHERE tc.CONSTRAINT_SCHEMA = ?
+            AND tc.CONSTRAINT_TYPE IN ('PRIMARY KEY', 'UNIQUE', 'FOREIGN KEY')
+        ORDER BY
+            tc.TABLE_NAME,
+            FIELD(tc.CONSTRAINT_TYPE, 'PRIMARY KEY', 'UNIQUE', 'FOREIGN KEY'),
+            tc.CONSTRAINT_NAME,
+            k.ORDINAL_POSITION
+        "#,
+        database,
+    )
+    .await?;
+
+    // (constraint, update rule, delete rule)
+    let mut keys_with_rules: Vec<(Constraint, String, String)> = vec![];
+    for row in keys {
+        let value = |i: usize| row[i].clone().unwrap_or_default();
+        let kind = match value(2).as_str() {
+            "PRIMARY KEY" => ConstraintKind::PrimaryKey,
+            "UNIQUE" => ConstraintKind::Unique,
+            _ => ConstraintKind::ForeignKey,
+        };
+
+        match keys_with_rules.last_mut() {
+            Some((last, _, _))
+                if last.table == value(0)
+                    && last.name.as_deref() == Some(&value(1))
+                    && last.kind == kind =>
+            {
+                last.columns.push(value(3));
+                if let Some(reference) = last.references.as_mut() {
+                    reference.columns.push(value(5));
+                }
+            }
+            _ => keys_with_rules.push((
+                Constraint {
+                    schema: None,
+                    table: value(0),
+                    name: Some(value(1)),
+                    kind,
+                    columns: vec![value(3)],
+                    references: row[4].clone().map(|table| Reference {
+                        schema: None,
+                        table,
+                        columns: vec![value(5)],
+                    }),
+                    definition: String::new(),
+                },
+                value(6),
+                value(7),
+            )),
+        }
+    }
+
+    let mut constraints = keys_with_rules
+        .into_iter()
+        .map(|(mut constraint, update, delete)| {
+            let columns = quote_list(&constraint.columns);
+            constraint.definition = match (&constraint.kind, &constraint.references) {
+                (ConstraintKind::PrimaryKey, _) => format!("PRIMARY KEY ({})", columns),
+                (ConstraintKind::ForeignKey, Some(reference)) => format!(
+                    "FOREIGN KEY ({}) REFERENCES {} ({}){}{}",
+                    columns,
+                    quote_ident(&reference.table),
+                    quote_list(&reference.columns),
+                    referential_action("DELETE", &delete),
+                    referential_action("UPDATE", &update)
+                ),
+                _ => format!("UNIQUE ({})", columns),
+            };
+            constraint
+        })
+        .collect::<Vec<Constraint>>();
+
+    let checks = names(
+        conn,
+        r#"
+        SELECT tc.TABLE_NAME, tc.CONSTRAINT_NAME, cc.CHECK_CLAUSE
+        FROM INFORMATION_SCHEMA.TABLE_CONSTRAINTS tc
+        JOIN INFORMATION_SCHEMA.CHECK_CONSTRAINTS cc
+            ON cc.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA
+            AND cc.CONSTRAINT_NAME = tc.CONSTRAINT_NAME
+        WHERE tc.CONSTRAINT_SCHEMA = ? AND tc.CONSTRAINT_TYPE = 'CHECK'
+        ORDER BY tc.TABLE_NAME, tc.CONSTRAINT_NAME
+        "#,
+        database,
+    )
+    .await?;
+    constraints.extend(checks.into_iter().map(|check| Constraint {
+        schema: None,
+        table: check[0].clone(),
+        name: Some(check[1].clone()),
+        kind: ConstraintKind::Check,
+        columns: vec![],
+        references: None,
+        definition: format!("CHECK ({})", check[2]),
+    }));
+
+    Ok(constraints)
+}
+
+// RESTRICT and NO ACTION are the same in InnoDB and the default when no action is given
+fn referential_action(event: &str, action: &str) -> String {
+    match action {
+        "" | "RESTRICT" | "NO ACTION" => String::new(),
+        action => format!(" ON {} {}", event, action),
+    }
+}
+
+// Keys backing a constraint are part of the constraint. Foreign keys create an index named after
+// themselves when no index covers their columns.
+async fn indexes(
+    conn: &mut MySqlConnection,
+    database: &str,
+    constraints: &[Constraint],
+) -> Result<Vec<Index>> {
+    let rows = rows(
+        conn,
+        r#"
+        SELECT
+            TABLE_NAME,
+            INDEX_NAME,
+            CAST(NON_UNIQUE AS CHAR),
+            COLUMN_NAME,
+            CAST(SUB_PART AS CHAR),
+            INDEX_TYPE
+        FROM INFORMATION_SCHEMA.STATISTICS
+        WHERE TABLE_SCHEMA = ? AND INDEX_NAME <> 'PRIMARY'
+        ORDER BY TABLE_NAME, INDEX_NAME, SEQ_IN_INDEX
+        "#,
+        database,
+    )
+    .await?;
+
+    // (index, type, key parts)
+    let mut indexes: Vec<(Index, String, Vec<String>)> = vec![];
+    for row in rows {
+        let value = |i: usize| row[i].clone().unwrap_or_default();
+        if constraints
+            .iter()
+            .any(|c| c.table == value(0) && c.name.as_deref() == Some(&value(1)))
+        {
+            continue;
+        }
+
+        // Functional indexes have no column name
+        let Some(column) = row[3].clone() else {
+            continue;
+        };
+        let part = match &row[4] {
+            Some(length) => format!("{}({})", quote_ident(&column), length),
+            None => quote_ident(&column),
+        };
+
+        match indexes.last_mut() {
+            Some((index, _, parts)) if index.table == value(0) && index.name == value(1) => {
+                index.columns.push(column);
+                parts.push(part);
+            }
+            _ => indexes.push((
+                Index {
+                    schema: None,
+                    table: value(0),
+                    name: value(1),
+                    columns: vec![column],
+                    unique: value(2) == "0",
+                    definition: String::new(),
+                },
+                value(5),
+                vec![part],
+            )),
+        }
+    }
+
+    Ok(indexes
+        .into_iter()
+        .map(|(mut index, index_type, parts)| {
+            let kind = match (index.unique, index_type.as_str()) {
+                (true, _) => "UNIQUE ",
+                (false, "FULLTEXT") => "FULLTEXT ",
+                (false, "SPATIAL") => "SPATIAL ",
+                _ => "",
+            };
+            index.definition = format!(
+                "CREATE {}INDEX {} ON {} ({});",
+                kind,
+                quote_ident(&index.name),
+                quote_ident(&index.table),
+                parts.join(", ")
+            );
+            index
+        })
+        .collect())
+}
+
+// Only MariaDB has sequences, their options are read from the sequence itself
+async fn sequences(conn: &mut MySqlConnection, database: &str) -> Result<Vec<Sequence>> {
+    let names = names(
+        conn,
+        r#"
+        SELECT TABLE_NAME
+        FROM INFORMATION_SCHEMA.TABLES
+        WHERE TABLE_SCHEMA = ? AND TABLE_TYPE = 'SEQUENCE'
+        ORDER BY TABLE_NAME
+        "#,
+        database,
+    )
+    .await?;
+
+    let mut sequences = vec![];
+    for name in names {
+        let query = format!(
+            "SELECT CAST(start_value AS CHAR), CAST(increment AS CHAR), CAST(minimum_value AS CHAR), CAST(maximum_value AS CHAR), CAST(cycle_option AS CHAR) FROM {}",
+            quote_ident(&name[0])
+        );
+        let row = sqlx::query(&query).fetch_one(&mut *conn).await?;
+        let number = |i: usize| -> Result<i64> { Ok(text(&row, i)?.parse()?) };
+
+        sequences.push(Sequence {
+            schema: None,
+            name: name[0].clone(),
+            data_type: "bigint".to_string(),
+            start: number(0)?,
+            increment: number(1)?,
+            min: number(2)?,
+            max: number(3)?,
+            cycle: number(4)? == 1,
+            owned_by: None,
+        });
+    }
+
+    Ok(sequences)
+}
+
+fn quote_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| quote_ident(name))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+// The counter only reflects how many rows were inserted, not the schema
+fn strip_auto_increment(statement: &str) -> String {
+    AUTO_INCREMENT.replace_all(statement, "").to_string()
+}
+
+// The definer is the account that ran the migration, which differs between environments
+fn strip_definer(statement: &str) -> String {
+    DEFINER.replace(statement, "").to_string()
+}
+
+// Recurring events get the creation time as start unless one was given
+fn strip_event_start(statement: &str) -> String {
+    EVENT_START.replace(statement, "").to_string()
+}
+
+// Views reference their own database explicitly, which would tie the dump to its name
+fn strip_database(statement: &str, database: &str) -> String {
+    statement.replace(&format!("{}.", quote_ident(database)), "")
+}
+
+// Views are ordered by name, a view referencing another one comes after it
+fn sort_views(views: Vec<(String, String)>) -> Vec<String> {
+    fn visit(
+        index: usize,
+        views: &[(String, String)],
+        done: &mut HashSet<usize>,
+        sorted: &mut Vec<String>,
+    ) {
+        if !done.insert(index) {
+            return;
+        }
+
+        let (name, statement) = &views[index];
+        for (dependency, (other, _)) in views.iter().enumerate() {
+            let body = statement.split_once(" AS ").map_or("", |(_, body)| body);
+            if other != name && body.contains(&quote_ident(other)) {
+                visit(dependency, views, done, sorted);
+            }
+        }
+
+        sorted.push(statement.clone());
+    }
+
+    let mut done = HashSet::new();
+    let mut sorted = vec![];
+    for index in 0..views.len() {
+        visit(index, &views, &mut done, &mut sorted);
+    }
+
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_auto_increment() {
+        let statement = "CREATE TABLE `users` (\n  `id` int NOT NULL AUTO_INCREMENT,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB AUTO_INCREMENT=42 DEFAULT CHARSET=utf8mb4";
+        assert_eq!(
+            strip_auto_increment(statement),
+            "CREATE TABLE `users` (\n  `id` int NOT NULL AUTO_INCREMENT,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4"
+        );
+    }
+
+    #[test]
+    fn test_strip_definer() {
+        assert_eq!(
+            strip_definer("CREATE DEFINER=`root`@`%` TRIGGER `t` BEFORE INSERT ON `users` FOR EACH ROW SET NEW.id = 1"),
+            "CREATE TRIGGER `t` BEFORE INSERT ON `users` FOR EACH ROW SET NEW.id = 1"
+        );
+        assert_eq!(
+            strip_definer("CREATE ALGORITHM=UNDEFINED DEFINER=`app`@`localhost` SQL SECURITY DEFINER VIEW `v` AS select 1 AS `1`"),
+            "CREATE ALGORITHM=UNDEFINED SQL SECURITY DEFINER VIEW `v` AS select 1 AS `1`"
+        );
+        assert_eq!(
+            strip_definer("CREATE DEFINER=root@localhost PROCEDURE `p`()\nBEGIN\nEND"),
+            "CREATE PROCEDURE `p`()\nBEGIN\nEND"
+        );
+    }
+
+    #[test]
+    fn test_strip_event_start() {
+        assert_eq!(
+            strip_event_start("CREATE EVENT `cleanup` ON SCHEDULE EVERY 1 DAY STARTS '2024-01-01 10:00:00' ON COMPLETION NOT PRESERVE ENABLE DO DELETE FROM `sessions`"),
+            "CREATE EVENT `cleanup` ON SCHEDULE EVERY 1 DAY ON COMPLETION NOT PRESERVE ENABLE DO DELETE FROM `sessions`"
+        );
+    }
+
+    #[test]
+    fn test_strip_database() {
+        assert_eq!(
+            strip_database(
+                "CREATE VIEW `v` AS select `app`.`users`.`id` AS `id` from `app`.`users`",
+                "app"
+            ),
+            "CREATE VIEW `v` AS select `users`.`id` AS `id` from `users`"
+        );
+    }
+
+    #[test]
+    fn test_referential_action() {
+        assert_eq!(
+            referential_action("DELETE", "CASCADE"),
+            " ON DELETE CASCADE"
+        );
+        assert_eq!(
+            referential_action("UPDATE", "SET NULL"),
+            " ON UPDATE SET NULL"
+        );
+        assert_eq!(referential_action("DELETE", "RESTRICT"), "");
+        assert_eq!(referential_action("UPDATE", "NO ACTION"), "");
+    }
+
+    #[test]
+    fn test_sort_views() {
+        let views = vec![
+            (
+                "a_active".to_string(),
+                "CREATE VIEW `a_active` AS select `id` from `z_users`;".to_string(),
+            ),
+            (
+                "b_counts".to_string(),
+                "CREATE VIEW `b_counts` AS select count(0) from `users`;".to_string(),
+            ),
+            (
+                "z_users".to_string(),
+                "CREATE VIEW `z_users` AS select `id` from `users`;".to_string(),
+            ),
+        ];
+
+        let sorted = sort_views(views);
+        assert!(sorted[0].contains("VIEW `z_users`"));
+        assert!(sorted[1].contains("VIEW `a_active`"));
+        assert!(sorted[2].contains("VIEW `b_counts`"));
+    }
+}
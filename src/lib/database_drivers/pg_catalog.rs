@@ -0,0 +1,2062 @@
+// Introspects a Postgres database through pg_catalog and renders a schema dump that can be
+// loaded into an empty database. Queries return raw catalog values, all SQL is rendered here.
+use super::utils::push_section;
+use crate::schema as model;
+use anyhow::Result;
+use sqlx::postgres::PgRow;
+use sqlx::{Connection, PgConnection, Row};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Every query filters out system schemas and objects owned by extensions
+const USER_SCHEMA: &str = "n.nspname !~ '^pg_' AND n.nspname <> 'information_schema'";
+
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    pub schemas: Vec<String>,
+    pub extensions: Vec<Extension>,
+    pub enums: Vec<EnumType>,
+    pub domains: Vec<Domain>,
+    pub composite_types: Vec<CompositeType>,
+    pub functions: Vec<Function>,
+    pub sequences: Vec<Sequence>,
+    pub tables: Vec<Table>,
+    pub constraints: Vec<Constraint>,
+    pub indexes: Vec<Index>,
+    pub views: Vec<View>,
+    pub triggers: Vec<Trigger>,
+    pub policies: Vec<Policy>,
+    pub grants: Vec<Grant>,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extension {
+    pub name: String,
+    pub schema: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumType {
+    pub schema: String,
+    pub name: String,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Domain {
+    pub schema: String,
+    pub name: String,
+    pub data_type: String,
+    pub collation: Option<String>,
+    pub default: Option<String>,
+    pub not_null: bool,
+    // (name, definition)
+    pub checks: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositeType {
+    pub schema: String,
+    pub name: String,
+    // (name, type)
+    pub attributes: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub schema: String,
+    pub name: String,
+    pub kind: String,
+    pub arguments: String,
+    pub definition: String,
+    // Functions taking or returning a table's row type can only be created after the table
+    pub depends_on_relations: bool,
+    pub revoked_from_public: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceOptions {
+    pub start: i64,
+    pub increment: i64,
+    pub min: i64,
+    pub max: i64,
+    pub cache: i64,
+    pub cycle: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    pub schema: String,
+    pub name: String,
+    pub data_type: String,
+    pub options: SequenceOptions,
+    // (schema, table, column) of a serial column
+    pub owned_by: Option<(String, String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Identity {
+    Always(SequenceOptions),
+    ByDefault(SequenceOptions),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub collation: Option<String>,
+    pub not_null: bool,
+    pub default: Option<String>,
+    pub identity: Option<Identity>,
+    // (expression, stored)
+    pub generated: Option<(String, bool)>,
+    // False for columns only inherited from a parent table
+    pub is_local: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub oid: i64,
+    pub schema: String,
+    pub name: String,
+    pub unlogged: bool,
+    pub columns: Vec<Column>,
+    pub partition_key: Option<String>,
+    pub partition_bound: Option<String>,
+    pub parents: Vec<i64>,
+    pub row_security: bool,
+    pub force_row_security: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub schema: String,
+    pub table: String,
+    pub name: String,
+    pub kind: String,
+    pub definition: String,
+    pub columns: Vec<String>,
+    // (schema, table, columns) a foreign key references
+    pub references: Option<(String, String, Vec<String>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Index {
+    pub schema: String,
+    pub table: String,
+    pub name: String,
+    pub unique: bool,
+    // Key columns and expressions
+    pub columns: Vec<String>,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub oid: i64,
+    pub schema: String,
+    pub name: String,
+    pub materialized: bool,
+    pub options: Vec<String>,
+    pub definition: String,
+    pub depends_on: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub schema: String,
+    pub table: String,
+    pub name: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub schema: String,
+    pub table: String,
+    pub name: String,
+    pub permissive: bool,
+    pub command: String,
+    pub roles: Vec<String>,
+    pub using: Option<String>,
+    pub with_check: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grant {
+    // TABLE, SEQUENCE, SCHEMA, FUNCTION or PROCEDURE
+    pub object_kind: String,
+    pub object: String,
+    pub grantee: String,
+    pub privileges: Vec<String>,
+    pub grantable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub object_kind: String,
+    pub object: String,
+    pub text: String,
+}
+
+// Reads the whole catalog in one read only snapshot. The search_path is emptied so every
+// expression and type Postgres prints is schema qualified.
+pub async fn introspect(conn: &mut PgConnection) -> Result<Catalog> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("SET LOCAL search_path = ''")
+        .execute(&mut *tx)
+        .await?;
+
+    let mut tables = tables(&mut tx).await?;
+    let mut columns = columns(&mut tx).await?;
+    for table in tables.iter_mut() {
+        table.columns = columns.remove(&table.oid).unwrap_or_default();
+    }
+
+    let catalog = Catalog {
+        schemas: schemas(&mut tx).await?,
+        extensions: extensions(&mut tx).await?,
+        enums: enums(&mut tx).await?,
+        domains: domains(&mut tx).await?,
+        composite_types: composite_types(&mut tx).await?,
+        functions: functions(&mut tx).await?,
+        sequences: sequences(&mut tx).await?,
+        tables,
+        constraints: constraints(&mut tx).await?,
+        indexes: indexes(&mut tx).await?,
+        views: views(&mut tx).await?,
+        triggers: triggers(&mut tx).await?,
+        policies: policies(&mut tx).await?,
+        grants: grants(&mut tx).await?,
+        comments: comments(&mut tx).await?,
+    };
+
+    tx.rollback().await?;
+
+    Ok(catalog)
+}
+
+async fn schemas(conn: &mut PgConnection) -> Result<Vec<String>> {
+    let query = format!(
+        r#"
+        SELECT n.nspname::text AS name
+        FROM pg_namespace n
+        WHERE {}
+            AND NOT EXISTS (
+                SELECT 1 FROM pg_depend d
+                WHERE d.classid = 'pg_namespace'::regclass AND d.objid = n.oid AND d.deptype = 'e'
+            )
+        ORDER BY 1
+        "#,
+        USER_SCHEMA
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| row.get("name"))
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn extensions(conn: &mut PgConnection) -> Result<Vec<Extension>> {
+    let query = format!(
+        r#"
+        SELECT e.extname::text AS name, n.nspname::text AS schema
+        FROM pg_extension e
+        JOIN pg_namespace n ON n.oid = e.extnamespace
+        WHERE {}
+        ORDER BY 1
+        "#,
+        USER_SCHEMA
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| Extension {
+            name: row.get("name"),
+            schema: row.get("schema"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+fn not_extension_member(class: &str, oid: &str) -> String {
+    format!(
+        "NOT EXISTS (SELECT 1 FROM pg_depend e WHERE e.classid = '{}'::regclass AND e.objid = {} AND e.deptype = 'e')",
+        class, oid
+    )
+}
+
+async fn enums(conn: &mut PgConnection) -> Result<Vec<EnumType>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            t.typname::text AS name,
+            ARRAY(
+                SELECT e.enumlabel::text FROM pg_enum e
+                WHERE e.enumtypid = t.oid
+                ORDER BY e.enumsortorder
+            ) AS labels
+        FROM pg_type t
+        JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE t.typtype = 'e' AND {} AND {}
+        ORDER BY 1, 2
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_type", "t.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| EnumType {
+            schema: row.get("schema"),
+            name: row.get("name"),
+            labels: row.get("labels"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn domains(conn: &mut PgConnection) -> Result<Vec<Domain>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            t.typname::text AS name,
+            format_type(t.typbasetype, t.typtypmod) AS data_type,
+            cn.nspname::text AS collation_schema,
+            co.collname::text AS collation_name,
+            t.typdefault AS default_value,
+            t.typnotnull AS not_null,
+            ARRAY(
+                SELECT c.conname::text FROM pg_constraint c
+                WHERE c.contypid = t.oid AND c.contype = 'c'
+                ORDER BY c.conname
+            ) AS check_names,
+            ARRAY(
+                SELECT pg_get_constraintdef(c.oid) FROM pg_constraint c
+                WHERE c.contypid = t.oid AND c.contype = 'c'
+                ORDER BY c.conname
+            ) AS check_definitions
+        FROM pg_type t
+        JOIN pg_namespace n ON n.oid = t.typnamespace
+        JOIN pg_type bt ON bt.oid = t.typbasetype
+        LEFT JOIN pg_collation co ON co.oid = t.typcollation AND t.typcollation <> bt.typcollation
+        LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+        WHERE t.typtype = 'd' AND {} AND {}
+        ORDER BY 1, 2
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_type", "t.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| {
+            let names: Vec<String> = row.get("check_names");
+            let definitions: Vec<String> = row.get("check_definitions");
+
+            Domain {
+                schema: row.get("schema"),
+                name: row.get("name"),
+                data_type: row.get("data_type"),
+                collation: collation(&row),
+                default: row.get("default_value"),
+                not_null: row.get("not_null"),
+                checks: names.into_iter().zip(definitions).collect(),
+            }
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+fn collation(row: &PgRow) -> Option<String> {
+    let schema: Option<String> = row.get("collation_schema");
+    let name: Option<String> = row.get("collation_name");
+
+    Some(format!(
+        "{}.{}",
+        quote_ident(&schema?),
+        quote_always(&name?)
+    ))
+}
+
+async fn composite_types(conn: &mut PgConnection) -> Result<Vec<CompositeType>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            t.typname::text AS name,
+            ARRAY(
+                SELECT a.attname::text FROM pg_attribute a
+                WHERE a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped
+                ORDER BY a.attnum
+            ) AS attribute_names,
+            ARRAY(
+                SELECT format_type(a.atttypid, a.atttypmod) FROM pg_attribute a
+                WHERE a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped
+                ORDER BY a.attnum
+            ) AS attribute_types
+        FROM pg_type t
+        JOIN pg_namespace n ON n.oid = t.typnamespace
+        JOIN pg_class c ON c.oid = t.typrelid
+        WHERE t.typtype = 'c' AND c.relkind = 'c' AND {} AND {}
+        ORDER BY 1, 2
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_type", "t.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| {
+            let names: Vec<String> = row.get("attribute_names");
+            let types: Vec<String> = row.get("attribute_types");
+
+            CompositeType {
+                schema: row.get("schema"),
+                name: row.get("name"),
+                attributes: names.into_iter().zip(types).collect(),
+            }
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn functions(conn: &mut PgConnection) -> Result<Vec<Function>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            p.proname::text AS name,
+            CASE p.prokind WHEN 'p' THEN 'PROCEDURE' ELSE 'FUNCTION' END AS kind,
+            pg_get_function_identity_arguments(p.oid) AS arguments,
+            pg_get_functiondef(p.oid) AS definition,
+            EXISTS (
+                SELECT 1 FROM pg_depend d
+                LEFT JOIN pg_type dt ON d.refclassid = 'pg_type'::regclass AND dt.oid = d.refobjid
+                LEFT JOIN pg_class dc ON dc.oid = dt.typrelid
+                WHERE d.classid = 'pg_proc'::regclass AND d.objid = p.oid
+                    AND (d.refclassid = 'pg_class'::regclass OR dc.relkind IN ('r', 'p', 'v', 'm', 'f'))
+            ) AS depends_on_relations,
+            p.proacl IS NOT NULL AND NOT EXISTS (
+                SELECT 1 FROM aclexplode(p.proacl) acl WHERE acl.grantee = 0
+            ) AS revoked_from_public
+        FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE p.prokind IN ('f', 'p') AND {} AND {}
+        ORDER BY 1, 2, 4
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_proc", "p.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| Function {
+            schema: row.get("schema"),
+            name: row.get("name"),
+            kind: row.get("kind"),
+            arguments: row.get("arguments"),
+            definition: row.get("definition"),
+            depends_on_relations: row.get("depends_on_relations"),
+            revoked_from_public: row.get("revoked_from_public"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn sequences(conn: &mut PgConnection) -> Result<Vec<Sequence>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            c.relname::text AS name,
+            format_type(s.seqtypid, NULL) AS data_type,
+            s.seqstart AS start_value,
+            s.seqincrement AS increment,
+            s.seqmin AS min_value,
+            s.seqmax AS max_value,
+            s.seqcache AS cache,
+            s.seqcycle AS cycle,
+            tn.nspname::text AS owner_schema,
+            tc.relname::text AS owner_table,
+            ta.attname::text AS owner_column
+        FROM pg_sequence s
+        JOIN pg_class c ON c.oid = s.seqrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        LEFT JOIN pg_depend d ON d.classid = 'pg_class'::regclass AND d.objid = c.oid
+            AND d.refclassid = 'pg_class'::regclass AND d.deptype = 'a'
+        LEFT JOIN pg_class tc ON tc.oid = d.refobjid
+        LEFT JOIN pg_namespace tn ON tn.oid = tc.relnamespace
+        LEFT JOIN pg_attribute ta ON ta.attrelid = d.refobjid AND ta.attnum = d.refobjsubid
+        WHERE {} AND {}
+            AND NOT EXISTS (
+                SELECT 1 FROM pg_depend i
+                WHERE i.classid = 'pg_class'::regclass AND i.objid = c.oid AND i.deptype = 'i'
+            )
+        ORDER BY 1, 2
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_class", "c.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| {
+            let owner_schema: Option<String> = row.get("owner_schema");
+            let owner_table: Option<String> = row.get("owner_table");
+            let owner_column: Option<String> = row.get("owner_column");
+
+            Sequence {
+                schema: row.get("schema"),
+                name: row.get("name"),
+                data_type: row.get("data_type"),
+                options: SequenceOptions {
+                    start: row.get("start_value"),
+                    increment: row.get("increment"),
+                    min: row.get("min_value"),
+                    max: row.get("max_value"),
+                    cache: row.get("cache"),
+                    cycle: row.get("cycle"),
+                },
+                owned_by: match (owner_schema, owner_table, owner_column) {
+                    (Some(schema), Some(table), Some(column)) => Some((schema, table, column)),
+                    _ => None,
+                },
+            }
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn tables(conn: &mut PgConnection) -> Result<Vec<Table>> {
+    let query = format!(
+        r#"
+        SELECT
+            c.oid::bigint AS oid,
+            n.nspname::text AS schema,
+            c.relname::text AS name,
+            c.relpersistence = 'u' AS unlogged,
+            CASE WHEN c.relkind = 'p' THEN pg_get_partkeydef(c.oid) END AS partition_key,
+            CASE WHEN c.relispartition THEN pg_get_expr(c.relpartbound, c.oid) END AS partition_bound,
+            ARRAY(
+                SELECT i.inhparent::bigint FROM pg_inherits i
+                WHERE i.inhrelid = c.oid
+                ORDER BY i.inhseqno
+            ) AS parents,
+            c.relrowsecurity AS row_security,
+            c.relforcerowsecurity AS force_row_security
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.relkind IN ('r', 'p') AND {} AND {}
+        ORDER BY 2, 3
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_class", "c.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| Table {
+            oid: row.get("oid"),
+            schema: row.get("schema"),
+            name: row.get("name"),
+            unlogged: row.get("unlogged"),
+            columns: vec![],
+            partition_key: row.get("partition_key"),
+            partition_bound: row.get("partition_bound"),
+            parents: row.get("parents"),
+            row_security: row.get("row_security"),
+            force_row_security: row.get("force_row_security"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+// Columns in ordinal position, grouped by the oid of their table
+async fn columns(conn: &mut PgConnection) -> Result<HashMap<i64, Vec<Column>>> {
+    let query = format!(
+        r#"
+        SELECT
+            a.attrelid::bigint AS table_oid,
+            a.attname::text AS name,
+            format_type(a.atttypid, a.atttypmod) AS data_type,
+            cn.nspname::text AS collation_schema,
+            co.collname::text AS collation_name,
+            a.attnotnull AS not_null,
+            pg_get_expr(ad.adbin, ad.adrelid) AS default_value,
+            a.attidentity::text AS identity,
+            a.attgenerated::text AS generated,
+            a.attislocal AS is_local,
+            s.seqstart AS start_value,
+            s.seqincrement AS increment,
+            s.seqmin AS min_value,
+            s.seqmax AS max_value,
+            s.seqcache AS cache,
+            s.seqcycle AS cycle
+        FROM pg_attribute a
+        JOIN pg_class c ON c.oid = a.attrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        JOIN pg_type t ON t.oid = a.atttypid
+        LEFT JOIN pg_attrdef ad ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum
+        LEFT JOIN pg_collation co ON co.oid = a.attcollation AND a.attcollation <> t.typcollation
+        LEFT JOIN pg_namespace cn ON cn.oid = co.collnamespace
+        LEFT JOIN pg_depend d ON a.attidentity <> '' AND d.classid = 'pg_class'::regclass
+            AND d.refclassid = 'pg_class'::regclass AND d.refobjid = a.attrelid
+            AND d.refobjsubid = a.attnum AND d.deptype = 'i'
+        LEFT JOIN pg_sequence s ON s.seqrelid = d.objid
+        WHERE c.relkind IN ('r', 'p') AND a.attnum > 0 AND NOT a.attisdropped AND {}
+        ORDER BY a.attrelid, a.attnum
+        "#,
+        USER_SCHEMA
+    );
+
+    let rows = sqlx::query(&query).fetch_all(conn).await?;
+    let mut columns: HashMap<i64, Vec<Column>> = HashMap::new();
+
+    for row in rows {
+        let default: Option<String> = row.get("default_value");
+        let identity: String = row.get("identity");
+        let generated: String = row.get("generated");
+        let options = || SequenceOptions {
+            start: row.get("start_value"),
+            increment: row.get("increment"),
+            min: row.get("min_value"),
+            max: row.get("max_value"),
+            cache: row.get("cache"),
+            cycle: row.get("cycle"),
+        };
+
+        let column = Column {
+            name: row.get("name"),
+            data_type: row.get("data_type"),
+            collation: collation(&row),
+            not_null: row.get("not_null"),
+            identity: match identity.as_str() {
+                "a" => Some(Identity::Always(options())),
+                "d" => Some(Identity::ByDefault(options())),
+                _ => None,
+            },
+            generated: match generated.as_str() {
+                "" => None,
+                kind => default.clone().map(|expr| (expr, kind == "s")),
+            },
+            default: if generated.is_empty() { default } else { None },
+            is_local: row.get("is_local"),
+        };
+
+        columns
+            .entry(row.get("table_oid"))
+            .or_default()
+            .push(column);
+    }
+
+    Ok(columns)
+}
+
+async fn constraints(conn: &mut PgConnection) -> Result<Vec<Constraint>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            c.relname::text AS table_name,
+            con.conname::text AS name,
+            con.contype::text AS kind,
+            pg_get_constraintdef(con.oid) AS definition,
+            ARRAY(
+                SELECT a.attname::text FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, position)
+                JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                ORDER BY k.position
+            ) AS columns,
+            fn.nspname::text AS foreign_schema,
+            fc.relname::text AS foreign_table,
+            ARRAY(
+                SELECT a.attname::text FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, position)
+                JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+                ORDER BY k.position
+            ) AS foreign_columns
+        FROM pg_constraint con
+        JOIN pg_class c ON c.oid = con.conrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        LEFT JOIN pg_class fc ON fc.oid = con.confrelid
+        LEFT JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+        WHERE con.contype IN ('p', 'u', 'x', 'c', 'f')
+            AND con.conislocal
+            AND con.conparentid = 0
+            AND c.relkind IN ('r', 'p')
+            AND {} AND {}
+        ORDER BY
+            CASE con.contype
+                WHEN 'p' THEN 1
+                WHEN 'u' THEN 2
+                WHEN 'x' THEN 3
+                WHEN 'c' THEN 4
+                ELSE 5
+            END,
+            1, 2, 3
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_class", "c.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| {
+            let foreign_schema: Option<String> = row.get("foreign_schema");
+            let foreign_table: Option<String> = row.get("foreign_table");
+
+            Constraint {
+                schema: row.get("schema"),
+                table: row.get("table_name"),
+                name: row.get("name"),
+                kind: row.get("kind"),
+                definition: row.get("definition"),
+                columns: row.get("columns"),
+                references: match (foreign_schema, foreign_table) {
+                    (Some(schema), Some(table)) => {
+                        Some((schema, table, row.get("foreign_columns")))
+                    }
+                    _ => None,
+                },
+            }
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+// Indexes backing constraints are created by the constraint, indexes of partitions by the
+// index on the partitioned table
+async fn indexes(conn: &mut PgConnection) -> Result<Vec<Index>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            c.relname::text AS table_name,
+            ic.relname::text AS name,
+            i.indisunique AS is_unique,
+            ARRAY(
+                SELECT pg_get_indexdef(i.indexrelid, k, true)
+                FROM generate_series(1, i.indnkeyatts) AS k
+                ORDER BY k
+            ) AS columns,
+            c.relkind = 'p' AS partitioned,
+            pg_get_indexdef(i.indexrelid) AS definition
+        FROM pg_index i
+        JOIN pg_class ic ON ic.oid = i.indexrelid
+        JOIN pg_class c ON c.oid = i.indrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.relkind IN ('r', 'p', 'm')
+            AND {} AND {}
+            AND NOT EXISTS (
+                SELECT 1 FROM pg_constraint con
+                WHERE con.conindid = i.indexrelid AND con.contype IN ('p', 'u', 'x')
+            )
+            AND NOT EXISTS (SELECT 1 FROM pg_inherits inh WHERE inh.inhrelid = i.indexrelid)
+        ORDER BY 1, 2
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_class", "c.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| {
+            let definition: String = row.get("definition");
+            let partitioned: bool = row.get("partitioned");
+
+            Index {
+                schema: row.get("schema"),
+                table: row.get("table_name"),
+                name: row.get("name"),
+                unique: row.get("is_unique"),
+                columns: row.get("columns"),
+                // ON ONLY would leave the partitions without the index
+                definition: if partitioned {
+                    definition.replacen(" ON ONLY ", " ON ", 1)
+                } else {
+                    definition
+                },
+            }
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn views(conn: &mut PgConnection) -> Result<Vec<View>> {
+    let query = format!(
+        r#"
+        SELECT
+            c.oid::bigint AS oid,
+            n.nspname::text AS schema,
+            c.relname::text AS name,
+            c.relkind = 'm' AS materialized,
+            COALESCE(c.reloptions::text[], ARRAY[]::text[]) AS options,
+            pg_get_viewdef(c.oid, true) AS definition,
+            ARRAY(
+                SELECT DISTINCT d.refobjid::bigint FROM pg_rewrite r
+                JOIN pg_depend d ON d.classid = 'pg_rewrite'::regclass AND d.objid = r.oid
+                WHERE r.ev_class = c.oid AND d.refclassid = 'pg_class'::regclass
+                    AND d.refobjid <> c.oid
+            ) AS depends_on
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.relkind IN ('v', 'm') AND {} AND {}
+        ORDER BY 2, 3
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_class", "c.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| View {
+            oid: row.get("oid"),
+            schema: row.get("schema"),
+            name: row.get("name"),
+            materialized: row.get("materialized"),
+            options: row.get("options"),
+            definition: row.get("definition"),
+            depends_on: row.get("depends_on"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+// Triggers cloned onto partitions have a parent trigger
+async fn triggers(conn: &mut PgConnection) -> Result<Vec<Trigger>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            c.relname::text AS table_name,
+            t.tgname::text AS name,
+            pg_get_triggerdef(t.oid, true) AS definition
+        FROM pg_trigger t
+        JOIN pg_class c ON c.oid = t.tgrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE NOT t.tgisinternal AND t.tgparentid = 0 AND {} AND {}
+        ORDER BY 1, 2, 3
+        "#,
+        USER_SCHEMA,
+        not_extension_member("pg_class", "c.oid")
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| Trigger {
+            schema: row.get("schema"),
+            table: row.get("table_name"),
+            name: row.get("name"),
+            definition: row.get("definition"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+async fn policies(conn: &mut PgConnection) -> Result<Vec<Policy>> {
+    let query = format!(
+        r#"
+        SELECT
+            n.nspname::text AS schema,
+            c.relname::text AS table_name,
+            p.polname::text AS name,
+            p.polpermissive AS permissive,
+            CASE p.polcmd
+                WHEN 'r' THEN 'SELECT'
+                WHEN 'a' THEN 'INSERT'
+                WHEN 'w' THEN 'UPDATE'
+                WHEN 'd' THEN 'DELETE'
+                ELSE 'ALL'
+            END AS command,
+            ARRAY(
+                SELECT CASE WHEN r.oid = 0 THEN 'PUBLIC' ELSE pg_get_userbyid(r.oid)::text END
+                FROM unnest(p.polroles) AS r(oid)
+                ORDER BY 1
+            ) AS roles,
+            pg_get_expr(p.polqual, p.polrelid) AS using_expression,
+            pg_get_expr(p.polwithcheck, p.polrelid) AS with_check
+        FROM pg_policy p
+        JOIN pg_class c ON c.oid = p.polrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE {}
+        ORDER BY 1, 2, 3
+        "#,
+        USER_SCHEMA
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| Policy {
+            schema: row.get("schema"),
+            table: row.get("table_name"),
+            name: row.get("name"),
+            permissive: row.get("permissive"),
+            command: row.get("command"),
+            roles: row.get("roles"),
+            using: row.get("using_expression"),
+            with_check: row.get("with_check"),
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+// Privileges the owner holds implicitly aren't dumped
+async fn grants(conn: &mut PgConnection) -> Result<Vec<Grant>> {
+    let query = format!(
+        r#"
+        SELECT
+            object_kind, schema, name, arguments,
+            CASE WHEN grantee = 0 THEN 'PUBLIC' ELSE pg_get_userbyid(grantee)::text END AS grantee,
+            privilege, grantable
+        FROM (
+            SELECT
+                CASE WHEN c.relkind = 'S' THEN 'SEQUENCE' ELSE 'TABLE' END AS object_kind,
+                n.nspname::text AS schema,
+                c.relname::text AS name,
+                NULL::text AS arguments,
+                acl.grantee,
+                acl.privilege_type AS privilege,
+                acl.is_grantable AS grantable
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            CROSS JOIN LATERAL aclexplode(c.relacl) acl
+            WHERE c.relkind IN ('r', 'p', 'v', 'm', 'S') AND acl.grantee <> c.relowner
+                AND {user_schema} AND {class_member}
+            UNION ALL
+            SELECT
+                'SCHEMA', n.nspname::text, n.nspname::text, NULL,
+                acl.grantee, acl.privilege_type, acl.is_grantable
+            FROM pg_namespace n
+            CROSS JOIN LATERAL aclexplode(n.nspacl) acl
+            WHERE acl.grantee <> n.nspowner AND {user_schema}
+            UNION ALL
+            SELECT
+                CASE p.prokind WHEN 'p' THEN 'PROCEDURE' ELSE 'FUNCTION' END,
+                n.nspname::text, p.proname::text, pg_get_function_identity_arguments(p.oid),
+                acl.grantee, acl.privilege_type, acl.is_grantable
+            FROM pg_proc p
+            JOIN pg_namespace n ON n.oid = p.pronamespace
+            CROSS JOIN LATERAL aclexplode(p.proacl) acl
+            WHERE p.prokind IN ('f', 'p') AND acl.grantee <> p.proowner
+                AND {user_schema} AND {proc_member}
+        ) AS grants
+        ORDER BY object_kind, schema, name, arguments, grantee, grantable, privilege
+        "#,
+        user_schema = USER_SCHEMA,
+        class_member = not_extension_member("pg_class", "c.oid"),
+        proc_member = not_extension_member("pg_proc", "p.oid"),
+    );
+
+    let rows = sqlx::query(&query)
+        .map(|row: PgRow| {
+            let kind: String = row.get("object_kind");
+            let schema: String = row.get("schema");
+            let name: String = row.get("name");
+            let arguments: Option<String> = row.get("arguments");
+            let grantee: String = row.get("grantee");
+
+            let object = match (kind.as_str(), arguments) {
+                ("SCHEMA", _) => quote_ident(&name),
+                (_, Some(arguments)) => format!("{}({})", qualified(&schema, &name), arguments),
+                (_, None) => qualified(&schema, &name),
+            };
+
+            (
+                kind,
+                object,
+                match grantee.as_str() {
+                    "PUBLIC" => grantee,
+                    role => quote_ident(role),
+                },
+                row.get::<String, _>("privilege"),
+                row.get::<bool, _>("grantable"),
+            )
+        })
+        .fetch_all(conn)
+        .await?;
+
+    let mut grants: Vec<Grant> = vec![];
+    for (object_kind, object, grantee, privilege, grantable) in rows {
+        match grants.last_mut() {
+            Some(last)
+                if last.object_kind == object_kind
+                    && last.object == object
+                    && last.grantee == grantee
+                    && last.grantable == grantable =>
+            {
+                last.privileges.push(privilege)
+            }
+            _ => grants.push(Grant {
+                object_kind,
+                object,
+                grantee,
+                privileges: vec![privilege],
+                grantable,
+            }),
+        }
+    }
+
+    Ok(grants)
+}
+
+async fn comments(conn: &mut PgConnection) -> Result<Vec<Comment>> {
+    let query = format!(
+        r#"
+        SELECT object_kind, schema, name, sub_name, description
+        FROM (
+            SELECT
+                CASE c.relkind
+                    WHEN 'v' THEN 'VIEW'
+                    WHEN 'm' THEN 'MATERIALIZED VIEW'
+                    WHEN 'S' THEN 'SEQUENCE'
+                    WHEN 'i' THEN 'INDEX'
+                    WHEN 'I' THEN 'INDEX'
+                    ELSE 'TABLE'
+                END AS object_kind,
+                n.nspname::text AS schema,
+                c.relname::text AS name,
+                NULL::text AS sub_name,
+                d.description
+            FROM pg_description d
+            JOIN pg_class c ON d.classoid = 'pg_class'::regclass AND c.oid = d.objoid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE d.objsubid = 0 AND c.relkind IN ('r', 'p', 'v', 'm', 'S', 'i', 'I')
+                AND {user_schema} AND {class_member}
+            UNION ALL
+            SELECT 'COLUMN', n.nspname::text, c.relname::text, a.attname::text, d.description
+            FROM pg_description d
+            JOIN pg_class c ON d.classoid = 'pg_class'::regclass AND c.oid = d.objoid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = d.objsubid
+            WHERE d.objsubid > 0 AND {user_schema} AND {class_member}
+            UNION ALL
+            SELECT
+                CASE t.typtype WHEN 'd' THEN 'DOMAIN' ELSE 'TYPE' END,
+                n.nspname::text, t.typname::text, NULL, d.description
+            FROM pg_description d
+            JOIN pg_type t ON d.classoid = 'pg_type'::regclass AND t.oid = d.objoid
+            JOIN pg_namespace n ON n.oid = t.typnamespace
+            WHERE {user_schema} AND {type_member}
+            UNION ALL
+            SELECT
+                CASE p.prokind WHEN 'p' THEN 'PROCEDURE' ELSE 'FUNCTION' END,
+                n.nspname::text, p.proname::text, pg_get_function_identity_arguments(p.oid),
+                d.description
+            FROM pg_description d
+            JOIN pg_proc p ON d.classoid = 'pg_proc'::regclass AND p.oid = d.objoid
+            JOIN pg_namespace n ON n.oid = p.pronamespace
+            WHERE {user_schema} AND {proc_member}
+            UNION ALL
+            SELECT 'SCHEMA', n.nspname::text, n.nspname::text, NULL, d.description
+            FROM pg_description d
+            JOIN pg_namespace n ON d.classoid = 'pg_namespace'::regclass AND n.oid = d.objoid
+            WHERE n.nspname <> 'public' AND {user_schema}
+        ) AS comments
+        ORDER BY schema, name, object_kind, sub_name
+        "#,
+        user_schema = USER_SCHEMA,
+        class_member = not_extension_member("pg_class", "c.oid"),
+        type_member = not_extension_member("pg_type", "t.oid"),
+        proc_member = not_extension_member("pg_proc", "p.oid"),
+    );
+
+    Ok(sqlx::query(&query)
+        .map(|row: PgRow| {
+            let object_kind: String = row.get("object_kind");
+            let schema: String = row.get("schema");
+            let name: String = row.get("name");
+            let sub_name: Option<String> = row.get("sub_name");
+
+            let object = match (object_kind.as_str(), sub_name) {
+                ("SCHEMA", _) => quote_ident(&name),
+                ("COLUMN", Some(column)) => {
+                    format!("{}.{}", qualified(&schema, &name), quote_ident(&column))
+                }
+                (_, Some(arguments)) => format!("{}({})", qualified(&schema, &name), arguments),
+                (_, None) => qualified(&schema, &name),
+            };
+
+            Comment {
+                object_kind,
+                object,
+                text: row.get("description"),
+            }
+        })
+        .fetch_all(conn)
+        .await?)
+}
+
+// Renders the catalog in an order that can be executed against an empty database
+pub fn render(catalog: &Catalog) -> String {
+    let mut schema = [
+        "--",
+        "-- Postgres SQL Schema dump automatic generated by geni",
+        "--",
+        "",
+        "SET check_function_bodies = false;",
+        "",
+        "",
+    ]
+    .join("\n");
+
+    push_section(
+        &mut schema,
+        "SCHEMAS",
+        catalog
+            .schemas
+            .iter()
+            .map(|name| format!("CREATE SCHEMA IF NOT EXISTS {};", quote_ident(name))),
+    );
+
+    push_section(
+        &mut schema,
+        "EXTENSIONS",
+        catalog.extensions.iter().map(|e| {
+            format!(
+                "CREATE EXTENSION IF NOT EXISTS {} WITH SCHEMA {};",
+                quote_always(&e.name),
+                quote_ident(&e.schema)
+            )
+        }),
+    );
+
+    push_section(
+        &mut schema,
+        "TYPES",
+        catalog
+            .enums
+            .iter()
+            .map(render_enum)
+            .chain(catalog.domains.iter().map(render_domain))
+            .chain(catalog.composite_types.iter().map(render_composite_type)),
+    );
+
+    push_section(
+        &mut schema,
+        "FUNCTIONS",
+        catalog
+            .functions
+            .iter()
+            .filter(|f| !f.depends_on_relations)
+            .map(render_function),
+    );
+
+    push_section(
+        &mut schema,
+        "SEQUENCES",
+        catalog.sequences.iter().map(|s| {
+            format!(
+                "CREATE SEQUENCE {} AS {} {};",
+                qualified(&s.schema, &s.name),
+                s.data_type,
+                render_sequence_options(&s.options)
+            )
+        }),
+    );
+
+    push_section(
+        &mut schema,
+        "TABLES",
+        sort_tables(&catalog.tables)
+            .into_iter()
+            .map(|table| render_table(table, &catalog.tables)),
+    );
+
+    push_section(
+        &mut schema,
+        "SEQUENCE OWNERSHIP",
+        catalog.sequences.iter().filter_map(|s| {
+            let (schema, table, column) = s.owned_by.as_ref()?;
+            Some(format!(
+                "ALTER SEQUENCE {} OWNED BY {}.{};",
+                qualified(&s.schema, &s.name),
+                qualified(schema, table),
+                quote_ident(column)
+            ))
+        }),
+    );
+
+    push_section(
+        &mut schema,
+        "CONSTRAINTS",
+        catalog.constraints.iter().map(|c| {
+            format!(
+                "ALTER TABLE {} ADD CONSTRAINT {} {};",
+                qualified(&c.schema, &c.table),
+                quote_ident(&c.name),
+                c.definition
+            )
+        }),
+    );
+
+    push_section(
+        &mut schema,
+        "FUNCTIONS",
+        catalog
+            .functions
+            .iter()
+            .filter(|f| f.depends_on_relations)
+            .map(render_function),
+    );
+
+    push_section(
+        &mut schema,
+        "VIEWS",
+        sort_views(&catalog.views).into_iter().map(render_view),
+    );
+
+    push_section(
+        &mut schema,
+        "INDEXES",
+        catalog.indexes.iter().map(|i| format!("{};", i.definition)),
+    );
+
+    push_section(
+        &mut schema,
+        "TRIGGERS",
+        catalog
+            .triggers
+            .iter()
+            .map(|t| format!("{};", t.definition)),
+    );
+
+    push_section(
+        &mut schema,
+        "ROW LEVEL SECURITY",
+        catalog
+            .tables
+            .iter()
+            .filter(|t| t.row_security || t.force_row_security)
+            .flat_map(|t| {
+                let table = qualified(&t.schema, &t.name);
+                let mut statements = vec![];
+                if t.row_security {
+                    statements.push(format!("ALTER TABLE {} ENABLE ROW LEVEL SECURITY;", table));
+                }
+                if t.force_row_security {
+                    statements.push(format!("ALTER TABLE {} FORCE ROW LEVEL SECURITY;", table));
+                }
+                statements
+            })
+            .chain(catalog.policies.iter().map(render_policy)),
+    );
+
+    push_section(
+        &mut schema,
+        "GRANTS",
+        catalog
+            .functions
+            .iter()
+            .filter(|f| f.revoked_from_public)
+            .map(|f| {
+                format!(
+                    "REVOKE ALL ON {} {}({}) FROM PUBLIC;",
+                    f.kind,
+                    qualified(&f.schema, &f.name),
+                    f.arguments
+                )
+            })
+            .chain(catalog.grants.iter().map(render_grant)),
+    );
+
+    push_section(
+        &mut schema,
+        "COMMENTS",
+        catalog.comments.iter().map(|c| {
+            format!(
+                "COMMENT ON {} {} IS {};",
+                c.object_kind,
+                c.object,
+                quote_literal(&c.text)
+            )
+        }),
+    );
+
+    schema
+}
+
+fn render_enum(e: &EnumType) -> String {
+    format!(
+        "CREATE TYPE {} AS ENUM ({});",
+        qualified(&e.schema, &e.name),
+        e.labels
+            .iter()
+            .map(|label| quote_literal(label))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+fn render_domain(d: &Domain) -> String {
+    let mut sql = format!(
+        "CREATE DOMAIN {} AS {}",
+        qualified(&d.schema, &d.name),
+        d.data_type
+    );
+    if let Some(collation) = &d.collation {
+        sql.push_str(&format!(" COLLATE {}", collation));
+    }
+    if let Some(default) = &d.default {
+        sql.push_str(&format!(" DEFAULT {}", default));
+    }
+    if d.not_null {
+        sql.push_str(" NOT NULL");
+    }
+    for (name, definition) in &d.checks {
+        sql.push_str(&format!(" CONSTRAINT {} {}", quote_ident(name), definition));
+    }
+    sql.push(';');
+
+    sql
+}
+
+fn render_composite_type(t: &CompositeType) -> String {
+    format!(
+        "CREATE TYPE {} AS (\n{}\n);",
+        qualified(&t.schema, &t.name),
+        t.attributes
+            .iter()
+            .map(|(name, data_type)| format!("    {} {}", quote_ident(name), data_type))
+            .collect::<Vec<String>>()
+            .join(",\n")
+    )
+}
+
+fn render_function(f: &Function) -> String {
+    format!("{};", f.definition.trim_end())
+}
+
+fn render_sequence_options(options: &SequenceOptions) -> String {
+    format!(
+        "START WITH {} INCREMENT BY {} MINVALUE {} MAXVALUE {} CACHE {} {}",
+        options.start,
+        options.increment,
+        options.min,
+        options.max,
+        options.cache,
+        if options.cycle { "CYCLE" } else { "NO CYCLE" }
+    )
+}
+
+fn render_column(column: &Column) -> String {
+    let mut sql = format!("{} {}", quote_ident(&column.name), column.data_type);
+
+    if let Some(collation) = &column.collation {
+        sql.push_str(&format!(" COLLATE {}", collation));
+    }
+
+    match (&column.generated, &column.identity, &column.default) {
+        (Some((expression, stored)), _, _) => sql.push_str(&format!(
+            " GENERATED ALWAYS AS ({}) {}",
+            expression,
+            if *stored { "STORED" } else { "VIRTUAL" }
+        )),
+        (None, Some(Identity::Always(options)), _) => sql.push_str(&format!(
+            " GENERATED ALWAYS AS IDENTITY ({})",
+            render_sequence_options(options)
+        )),
+        (None, Some(Identity::ByDefault(options)), _) => sql.push_str(&format!(
+            " GENERATED BY DEFAULT AS IDENTITY ({})",
+            render_sequence_options(options)
+        )),
+        (None, None, Some(default)) => sql.push_str(&format!(" DEFAULT {}", default)),
+        (None, None, None) => {}
+    }
+
+    if column.not_null {
+        sql.push_str(" NOT NULL");
+    }
+
+    sql
+}
+
+fn render_table(table: &Table, tables: &[Table]) -> String {
+    let name_of = |oid: &i64| {
+        tables
+            .iter()
+            .find(|t| t.oid == *oid)
+            .map(|t| qualified(&t.schema, &t.name))
+            .unwrap_or_else(|| oid.to_string())
+    };
+    let unlogged = if table.unlogged { "UNLOGGED " } else { "" };
+    let partition_by = table
+        .partition_key
+        .as_ref()
+        .map(|key| format!(" PARTITION BY {}", key))
+        .unwrap_or_default();
+
+    if let (Some(bound), Some(parent)) = (&table.partition_bound, table.parents.first()) {
+        return format!(
+            "CREATE {}TABLE {} PARTITION OF {} {}{};",
+            unlogged,
+            qualified(&table.schema, &table.name),
+            name_of(parent),
+            bound,
+            partition_by
+        );
+    }
+
+    let columns = table
+        .columns
+        .iter()
+        .filter(|c| c.is_local)
+        .map(|c| format!("    {}", render_column(c)))
+        .collect::<Vec<String>>()
+        .join(",\n");
+    let inherits = if table.parents.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " INHERITS ({})",
+            table
+                .parents
+                .iter()
+                .map(name_of)
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    };
+
+    format!(
+        "CREATE {}TABLE {} (\n{}\n){}{};",
+        unlogged,
+        qualified(&table.schema, &table.name),
+        columns,
+        inherits,
+        partition_by
+    )
+}
+
+// Parents and partitioned tables go before the tables inheriting from them
+fn sort_tables(tables: &[Table]) -> Vec<&Table> {
+    let by_oid: HashMap<i64, &Table> = tables.iter().map(|t| (t.oid, t)).collect();
+
+    fn depth(table: &Table, by_oid: &HashMap<i64, &Table>, seen: &mut HashSet<i64>) -> usize {
+        if !seen.insert(table.oid) {
+            return 0;
+        }
+        table
+            .parents
+            .iter()
+            .filter_map(|oid| by_oid.get(oid))
+            .map(|parent| depth(parent, by_oid, seen) + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    let mut sorted = tables
+        .iter()
+        .map(|t| (depth(t, &by_oid, &mut HashSet::new()), t))
+        .collect::<Vec<(usize, &Table)>>();
+    sorted.sort_by(|(a_depth, a), (b_depth, b)| {
+        (a_depth, &a.schema, &a.name).cmp(&(b_depth, &b.schema, &b.name))
+    });
+
+    sorted.into_iter().map(|(_, t)| t).collect()
+}
+
+// Views in name order, each one after the views it selects from
+fn sort_views(views: &[View]) -> Vec<&View> {
+    let by_oid: BTreeMap<i64, &View> = views.iter().map(|v| (v.oid, v)).collect();
+    let mut named = views.iter().collect::<Vec<&View>>();
+    named.sort_by(|a, b| (&a.schema, &a.name).cmp(&(&b.schema, &b.name)));
+
+    fn visit<'a>(
+        view: &'a View,
+        by_oid: &BTreeMap<i64, &'a View>,
+        done: &mut HashSet<i64>,
+        sorted: &mut Vec<&'a View>,
+    ) {
+        if !done.insert(view.oid) {
+            return;
+        }
+
+        let mut dependencies = view
+            .depends_on
+            .iter()
+            .filter_map(|oid| by_oid.get(oid).copied())
+            .collect::<Vec<&View>>();
+        dependencies.sort_by(|a, b| (&a.schema, &a.name).cmp(&(&b.schema, &b.name)));
+        for dependency in dependencies {
+            visit(dependency, by_oid, done, sorted);
+        }
+
+        sorted.push(view);
+    }
+
+    let mut done = HashSet::new();
+    let mut sorted = vec![];
+    for view in named {
+        visit(view, &by_oid, &mut done, &mut sorted);
+    }
+
+    sorted
+}
+
+fn render_view(view: &View) -> String {
+    let (check_options, options): (Vec<&String>, Vec<&String>) = view
+        .options
+        .iter()
+        .partition(|option| option.starts_with("check_option="));
+    let with = if options.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " WITH ({})",
+            options
+                .iter()
+                .map(|o| o.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )
+    };
+    let definition = view.definition.trim_end().trim_end_matches(';');
+
+    if view.materialized {
+        return format!(
+            "CREATE MATERIALIZED VIEW {}{} AS\n{}\nWITH NO DATA;",
+            qualified(&view.schema, &view.name),
+            with,
+            definition
+        );
+    }
+
+    let check_option = check_options
+        .first()
+        .and_then(|o| o.split_once('='))
+        .map(|(_, level)| format!("\nWITH {} CHECK OPTION", level.to_uppercase()))
+        .unwrap_or_default();
+
+    format!(
+        "CREATE VIEW {}{} AS\n{}{};",
+        qualified(&view.schema, &view.name),
+        with,
+        definition,
+        check_option
+    )
+}
+
+fn render_policy(policy: &Policy) -> String {
+    let mut sql = format!(
+        "CREATE POLICY {} ON {} AS {} FOR {} TO {}",
+        quote_ident(&policy.name),
+        qualified(&policy.schema, &policy.table),
+        if policy.permissive {
+            "PERMISSIVE"
+        } else {
+            "RESTRICTIVE"
+        },
+        policy.command,
+        policy
+            .roles
+            .iter()
+            .map(|role| match role.as_str() {
+                "PUBLIC" => role.to_string(),
+                role => quote_ident(role),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+    if let Some(using) = &policy.using {
+        sql.push_str(&format!(" USING ({})", using));
+    }
+    if let Some(with_check) = &policy.with_check {
+        sql.push_str(&format!(" WITH CHECK ({})", with_check));
+    }
+    sql.push(';');
+
+    sql
+}
+
+fn render_grant(grant: &Grant) -> String {
+    format!(
+        "GRANT {} ON {} {} TO {}{};",
+        grant.privileges.join(", "),
+        grant.object_kind,
+        grant.object,
+        grant.grantee,
+        if grant.grantable {
+            " WITH GRANT OPTION"
+        } else {
+            ""
+        }
+    )
+}
+
+// The catalog in the database independent schema model
+pub fn to_schema(catalog: &Catalog) -> model::Schema {
+    let column = |c: &Column| model::Column {
+        name: c.name.clone(),
+        data_type: c.data_type.clone(),
+        nullable: !c.not_null,
+        default: c.default.clone(),
+        identity: match c.identity {
+            Some(Identity::Always(_)) => Some(model::Identity::Always),
+            Some(Identity::ByDefault(_)) => Some(model::Identity::ByDefault),
+            None => None,
+        },
+        generated: c
+            .generated
+            .as_ref()
+            .map(|(expression, _)| expression.clone()),
+        collation: c.collation.clone(),
+    };
+
+    model::Schema {
+        tables: catalog
+            .tables
+            .iter()
+            .map(|t| model::Table {
+                schema: Some(t.schema.clone()),
+                name: t.name.clone(),
+                columns: t.columns.iter().map(column).collect(),
+            })
+            .collect(),
+        indexes: catalog
+            .indexes
+            .iter()
+            .map(|i| model::Index {
+                schema: Some(i.schema.clone()),
+                table: i.table.clone(),
+                name: i.name.clone(),
+                columns: i.columns.clone(),
+                unique: i.unique,
+                definition: format!("{};", i.definition),
+            })
+            .collect(),
+        constraints: catalog
+            .constraints
+            .iter()
+            .map(|c| model::Constraint {
+                schema: Some(c.schema.clone()),
+                table: c.table.clone(),
+                name: Some(c.name.clone()),
+                kind: match c.kind.as_str() {
+                    "p" => model::ConstraintKind::PrimaryKey,
+                    "u" => model::ConstraintKind::Unique,
+                    "f" => model::ConstraintKind::ForeignKey,
+                    "x" => model::ConstraintKind::Exclusion,
+                    _ => model::ConstraintKind::Check,
+                },
+                columns: c.columns.clone(),
+                references: c.references.as_ref().map(|(schema, table, columns)| {
+                    model::Reference {
+                        schema: Some(schema.clone()),
+                        table: table.clone(),
+                        columns: columns.clone(),
+                    }
+                }),
+                definition: c.definition.clone(),
+            })
+            .collect(),
+        views: catalog
+            .views
+            .iter()
+            .map(|v| model::View {
+                schema: Some(v.schema.clone()),
+                name: v.name.clone(),
+                materialized: v.materialized,
+                definition: render_view(v),
+            })
+            .collect(),
+        sequences: catalog
+            .sequences
+            .iter()
+            .map(|s| model::Sequence {
+                schema: Some(s.schema.clone()),
+                name: s.name.clone(),
+                data_type: s.data_type.clone(),
+                start: s.options.start,
+                increment: s.options.increment,
+                min: s.options.min,
+                max: s.options.max,
+                cycle: s.options.cycle,
+                owned_by: s
+                    .owned_by
+                    .as_ref()
+                    .map(|(schema, table, column)| format!("{}.{}.{}", schema, table, column)),
+            })
+            .collect(),
+        functions: catalog
+            .functions
+            .iter()
+            .map(|f| model::Function {
+                schema: Some(f.schema.clone()),
+                name: f.name.clone(),
+                kind: f.kind.clone(),
+                arguments: f.arguments.clone(),
+                definition: render_function(f),
+            })
+            .collect(),
+    }
+}
+
+// Keeps the schema."name" format geni has always written for relations
+fn qualified(schema: &str, name: &str) -> String {
+    format!("{}.{}", quote_ident(schema), quote_always(name))
+}
+
+fn quote_always(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Quotes the identifier when Postgres would otherwise fold or reject it
+pub fn quote_ident(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+
+    if plain && !RESERVED_KEYWORDS.contains(&name) {
+        name.to_string()
+    } else {
+        quote_always(name)
+    }
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Reserved keywords and keywords that can't be used as a column or table name unquoted
+const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "binary",
+    "both",
+    "case",
+    "cast",
+    "check",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "false",
+    "fetch",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "intersect",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "natural",
+    "not",
+    "notnull",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "overlaps",
+    "placing",
+    "primary",
+    "references",
+    "returning",
+    "right",
+    "select",
+    "session_user",
+    "similar",
+    "some",
+    "symmetric",
+    "system_user",
+    "table",
+    "tablesample",
+    "then",
+    "to",
+    "trailing",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> SequenceOptions {
+        SequenceOptions {
+            start: 1,
+            increment: 1,
+            min: 1,
+            max: 2147483647,
+            cache: 1,
+            cycle: false,
+        }
+    }
+
+    fn column(name: &str, data_type: &str) -> Column {
+        Column {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            collation: None,
+            not_null: false,
+            default: None,
+            identity: None,
+            generated: None,
+            is_local: true,
+        }
+    }
+
+    fn table(oid: i64, name: &str, columns: Vec<Column>) -> Table {
+        Table {
+            oid,
+            schema: "public".to_string(),
+            name: name.to_string(),
+            unlogged: false,
+            columns,
+            partition_key: None,
+            partition_bound: None,
+            parents: vec![],
+            row_security: false,
+            force_row_security: false,
+        }
+    }
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("users"), "users");
+        assert_eq!(quote_ident("user_id2"), "user_id2");
+        assert_eq!(quote_ident("group"), "\"group\"");
+        assert_eq!(quote_ident("CamelCase"), "\"CamelCase\"");
+        assert_eq!(quote_ident("1st"), "\"1st\"");
+        assert_eq!(quote_ident("with\"quote"), "\"with\"\"quote\"");
+    }
+
+    #[test]
+    fn test_render_table_keeps_column_order_and_details() {
+        let mut id = column("id", "integer");
+        id.not_null = true;
+        id.identity = Some(Identity::Always(options()));
+        let mut tags = column("tags", "text[]");
+        tags.default = Some("'{}'::text[]".to_string());
+        let mut name = column("name", "text");
+        name.collation = Some("pg_catalog.\"C\"".to_string());
+        let mut search = column("search", "tsvector");
+        search.generated = Some(("to_tsvector('english'::regconfig, name)".to_string(), true));
+        let mut status = column("status", "public.mood");
+        status.not_null = true;
+
+        let users = table(1, "users", vec![id, tags, name, search, status]);
+
+        assert_eq!(
+            render_table(&users, &[]),
+            "CREATE TABLE public.\"users\" (\n    id integer GENERATED ALWAYS AS IDENTITY (START WITH 1 INCREMENT BY 1 MINVALUE 1 MAXVALUE 2147483647 CACHE 1 NO CYCLE) NOT NULL,\n    tags text[] DEFAULT '{}'::text[],\n    name text COLLATE pg_catalog.\"C\",\n    search tsvector GENERATED ALWAYS AS (to_tsvector('english'::regconfig, name)) STORED,\n    status public.mood NOT NULL\n);"
+        );
+    }
+
+    #[test]
+    fn test_render_partitions_after_their_parent() {
+        let mut events = table(2, "events", vec![column("created_at", "date")]);
+        events.partition_key = Some("RANGE (created_at)".to_string());
+        let mut events_2024 = table(1, "events_2024", vec![column("created_at", "date")]);
+        events_2024.parents = vec![2];
+        events_2024.partition_bound =
+            Some("FOR VALUES FROM ('2024-01-01') TO ('2025-01-01')".to_string());
+        let tables = vec![events_2024, events];
+
+        let sorted = sort_tables(&tables);
+        assert_eq!(sorted[0].name, "events");
+        assert_eq!(
+            render_table(sorted[0], &tables),
+            "CREATE TABLE public.\"events\" (\n    created_at date\n) PARTITION BY RANGE (created_at);"
+        );
+        assert_eq!(
+            render_table(sorted[1], &tables),
+            "CREATE TABLE public.\"events_2024\" PARTITION OF public.\"events\" FOR VALUES FROM ('2024-01-01') TO ('2025-01-01');"
+        );
+    }
+
+    #[test]
+    fn test_sort_views_puts_dependencies_first() {
+        let view = |oid: i64, name: &str, depends_on: Vec<i64>| View {
+            oid,
+            schema: "public".to_string(),
+            name: name.to_string(),
+            materialized: false,
+            options: vec![],
+            definition: " SELECT 1;".to_string(),
+            depends_on,
+        };
+        let views = vec![
+            view(1, "a_report", vec![3]),
+            view(2, "b_active", vec![100]),
+            view(3, "c_users", vec![]),
+        ];
+
+        let names = sort_views(&views)
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["c_users", "a_report", "b_active"]);
+        assert_eq!(
+            render_view(&views[0]),
+            "CREATE VIEW public.\"a_report\" AS\n SELECT 1;"
+        );
+    }
+
+    #[test]
+    fn test_render_domain_policy_and_grant() {
+        let domain = Domain {
+            schema: "public".to_string(),
+            name: "email".to_string(),
+            data_type: "text".to_string(),
+            collation: None,
+            default: None,
+            not_null: true,
+            checks: vec![(
+                "email_check".to_string(),
+                "CHECK (VALUE ~~ '%@%'::text)".to_string(),
+            )],
+        };
+        assert_eq!(
+            render_domain(&domain),
+            "CREATE DOMAIN public.\"email\" AS text NOT NULL CONSTRAINT email_check CHECK (VALUE ~~ '%@%'::text);"
+        );
+
+        let policy = Policy {
+            schema: "public".to_string(),
+            table: "documents".to_string(),
+            name: "owner_only".to_string(),
+            permissive: true,
+            command: "SELECT".to_string(),
+            roles: vec!["PUBLIC".to_string()],
+            using: Some("(owner = CURRENT_USER)".to_string()),
+            with_check: None,
+        };
+        assert_eq!(
+            render_policy(&policy),
+            "CREATE POLICY owner_only ON public.\"documents\" AS PERMISSIVE FOR SELECT TO PUBLIC USING ((owner = CURRENT_USER));"
+        );
+
+        let grant = Grant {
+            object_kind: "TABLE".to_string(),
+            object: "public.\"documents\"".to_string(),
+            grantee: "reporting".to_string(),
+            privileges: vec!["INSERT".to_string(), "SELECT".to_string()],
+            grantable: false,
+        };
+        assert_eq!(
+            render_grant(&grant),
+            "GRANT INSERT, SELECT ON TABLE public.\"documents\" TO reporting;"
+        );
+    }
+
+    #[test]
+    fn test_to_schema() {
+        let mut id = column("id", "integer");
+        id.not_null = true;
+        id.identity = Some(Identity::ByDefault(options()));
+        let catalog = Catalog {
+            tables: vec![table(1, "orders", vec![id, column("user_id", "bigint")])],
+            constraints: vec![Constraint {
+                schema: "public".to_string(),
+                table: "orders".to_string(),
+                name: "orders_user_id_fkey".to_string(),
+                kind: "f".to_string(),
+                definition: "FOREIGN KEY (user_id) REFERENCES public.users(id)".to_string(),
+                columns: vec!["user_id".to_string()],
+                references: Some((
+                    "public".to_string(),
+                    "users".to_string(),
+                    vec!["id".to_string()],
+                )),
+            }],
+            ..Default::default()
+        };
+
+        let schema = to_schema(&catalog);
+        assert_eq!(schema.tables[0].schema.as_deref(), Some("public"));
+        assert_eq!(
+            schema.tables[0].columns[0],
+            model::Column {
+                name: "id".to_string(),
+                data_type: "integer".to_string(),
+                nullable: false,
+                default: None,
+                identity: Some(model::Identity::ByDefault),
+                generated: None,
+                collation: None,
+            }
+        );
+        assert!(schema.tables[0].columns[1].nullable);
+        assert_eq!(
+            schema.constraints[0].kind,
+            model::ConstraintKind::ForeignKey
+        );
+        assert_eq!(
+            schema.constraints[0].references,
+            Some(model::Reference {
+                schema: Some("public".to_string()),
+                table: "users".to_string(),
+                columns: vec!["id".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_skips_empty_sections() {
+        let catalog = Catalog {
+            schemas: vec!["public".to_string()],
+            enums: vec![EnumType {
+                schema: "public".to_string(),
+                name: "mood".to_string(),
+                labels: vec!["happy".to_string(), "it's fine".to_string()],
+            }],
+            ..Default::default()
+        };
+
+        let schema = render(&catalog);
+        assert!(schema.contains("-- SCHEMAS \n\nCREATE SCHEMA IF NOT EXISTS public;\n\n"));
+        assert!(schema.contains("CREATE TYPE public.\"mood\" AS ENUM ('happy', 'it''s fine');"));
+        assert!(!schema.contains("-- TABLES"));
+    }
+}
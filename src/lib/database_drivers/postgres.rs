@@ -1,4 +1,5 @@
-use crate::database_drivers::DatabaseDriver;
+use crate::database_drivers::{DatabaseDriver, DumpOptions, SchemaMigration};
+use crate::schema::Schema;
 use anyhow::{bail, Result};
 use log::info;
 use sqlx::postgres::PgRow;
@@ -7,7 +8,7 @@ use sqlx::{Connection, PgConnection, Row};
 use std::future::Future;
 use std::pin::Pin;
 
-use super::utils;
+use super::{pg_catalog, utils};
 
 pub struct PostgresDriver {
     db: PgConnection,
@@ -64,29 +65,45 @@ impl PostgresDriver {
 
         Ok(p)
     }
+
+    // Empty when the migrations table doesn't exist
+    async fn migrations_table_columns(&mut self) -> Result<Vec<String>> {
+        let (schema, name) = utils::split_table_name(&self.migrations_table);
+
+        let columns: Vec<String> = sqlx::query(
+            "SELECT column_name::text FROM information_schema.columns WHERE table_schema = COALESCE($1, current_schema()) AND table_name = $2",
+        )
+        .bind(schema)
+        .bind(name)
+        .map(|row: PgRow| row.get(0))
+        .fetch_all(&mut self.db)
+        .await?;
+
+        Ok(columns)
+    }
 }
 
 impl DatabaseDriver for PostgresDriver {
-    fn execute<'a>(
+    fn execute_all<'a>(
         &'a mut self,
-        query: &'a str,
+        queries: &'a [&'a str],
         run_in_transaction: bool,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             if run_in_transaction {
                 let mut tx = self.db.begin().await?;
-                match tx.execute(query).await {
-                    Ok(_) => {
-                        tx.commit().await?;
-                    }
-                    Err(e) => {
+                for query in queries {
+                    if let Err(e) = tx.execute(*query).await {
                         tx.rollback().await?;
                         bail!(e)
                     }
                 }
+                tx.commit().await?;
                 return Ok(());
-            } else {
-                self.db.execute(query).await?;
+            }
+
+            for query in queries {
+                self.db.execute(*query).await?;
             }
 
             Ok(())
@@ -116,14 +133,45 @@ impl DatabaseDriver for PostgresDriver {
             );
             sqlx::query(query.as_str()).execute(&mut self.db).await?;
 
-            let query = format!("SELECT id FROM {} ORDER BY id DESC", table);
+            let columns = self.migrations_table_columns().await?;
+            for query in utils::add_tracking_columns_queries(&table, &columns) {
+                sqlx::query(query.as_str()).execute(&mut self.db).await?;
+            }
+
+            let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+
+            Ok(migrations.into_iter().map(|m| m.id).collect())
+        };
 
-            let result: Vec<String> = sqlx::query(query.as_str())
-                .map(|row: PgRow| row.get("id"))
+        Box::pin(fut)
+    }
+
+    fn get_schema_migrations(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<SchemaMigration>>, anyhow::Error>> + '_>>
+    {
+        let fut = async move {
+            let columns = self.migrations_table_columns().await?;
+            if columns.is_empty() {
+                return Ok(None);
+            }
+
+            let table = utils::quote_identifier(&self.migrations_table, "\"");
+            let query = utils::select_schema_migrations_query(&table, &columns);
+
+            let result: Vec<SchemaMigration> = sqlx::query(query.as_str())
+                .map(|row: PgRow| SchemaMigration {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    applied_at: utils::parse_applied_at(row.get("applied_at")),
+                    duration_ms: row.get("duration_ms"),
+                    applied_by: row.get("applied_by"),
+                    batch: row.get("batch"),
+                })
                 .fetch_all(&mut self.db)
                 .await?;
 
-            Ok(result)
+            Ok(Some(utils::newest_first(result)))
         };
 
         Box::pin(fut)
@@ -131,13 +179,21 @@ impl DatabaseDriver for PostgresDriver {
 
     fn insert_schema_migration<'a>(
         &'a mut self,
-        id: &'a str,
+        migration: &'a SchemaMigration,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             let table = utils::quote_identifier(&self.migrations_table, "\"");
-            let query = format!("INSERT INTO {} (id) VALUES ($1)", table);
+            let query = format!(
+                "INSERT INTO {} (id, name, applied_at, duration_ms, applied_by, batch) VALUES ($1, $2, $3, $4, $5, $6)",
+                table
+            );
             sqlx::query(query.as_str())
-                .bind(id)
+                .bind(&migration.id)
+                .bind(&migration.name)
+                .bind(utils::format_applied_at(&migration.applied_at))
+                .bind(migration.duration_ms)
+                .bind(&migration.applied_by)
+                .bind(migration.batch)
                 .execute(&mut self.db)
                 .await?;
             Ok(())
@@ -197,309 +253,54 @@ impl DatabaseDriver for PostgresDriver {
         Box::pin(fut)
     }
 
-    fn dump_database_schema(
-        &mut self,
+    fn dump_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
-            let schema = r#"
-                --
-                -- Postgres SQL Schema dump automatic generated by geni
-                --
-
-
-            "#;
-
-            let mut schema = schema
-                .lines()
-                .map(str::trim_start)
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            let extensions: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    'CREATE EXTENSION IF NOT EXISTS "' || extname || '" WITH SCHEMA ' || nspname || ';' AS sql
-                FROM
-                    pg_extension
-                JOIN pg_namespace ON pg_namespace.oid = pg_extension.extnamespace
-                WHERE
-                    nspname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                ORDER BY extname ASC
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !extensions.is_empty() {
-                schema.push_str("-- EXTENSIONS \n\n");
-                for ele in extensions.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
-
-            let schemas: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    'CREATE SCHEMA IF NOT EXISTS ' || s || ';' AS sql
-                FROM (
-                    SELECT DISTINCT table_schema AS s
-                    FROM information_schema.tables
-                    WHERE table_schema NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                ) AS schemas
-                ORDER BY s ASC
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !schemas.is_empty() {
-                schema.push_str("-- SCHEMAS \n\n");
-                for ele in schemas.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
-
-            let tables: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    'CREATE TABLE ' || t.table_schema || '."' || t.table_name || E'" (\n ' ||
-                    string_agg(c.column_name || ' ' || c.data_type || ' ' ||
-                                (CASE WHEN c.character_maximum_length IS NOT NULL
-                                    THEN '(' || c.character_maximum_length || ')'
-                                    ELSE '' END) ||
-                                (CASE WHEN c.is_nullable = 'NO' THEN ' NOT NULL' ELSE '' END),
-                                E',\n ' ORDER BY c.column_name ASC) ||
-                    E'\n);' AS sql
-                FROM
-                    information_schema.columns c
-                JOIN
-                    information_schema.tables t ON c.table_name = t.table_name AND c.table_schema = t.table_schema
-                WHERE
-                    t.table_schema NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                    AND t.table_type = 'BASE TABLE'
-                GROUP BY
-                    t.table_schema, t.table_name
-                ORDER BY
-                    t.table_schema, t.table_name;
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !tables.is_empty() {
-                schema.push_str("-- TABLES \n\n");
-                for ele in tables.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
-
-            let views: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    'CREATE VIEW ' || table_schema || '."' || table_name || '" AS\n' || view_definition || ';' AS sql
-                FROM
-                    information_schema.views
-                WHERE
-                    table_schema NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                ORDER BY
-                    table_schema, table_name ASC
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !views.is_empty() {
-                schema.push_str("-- VIEWS \n\n");
-                for ele in views.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
-
-            let constraints: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    CASE
-                        WHEN tc.constraint_type = 'PRIMARY KEY' THEN
-                            'ALTER TABLE ' || tc.table_schema || '."' || tc.table_name ||
-                            '" ADD CONSTRAINT ' || tc.constraint_name ||
-                            ' PRIMARY KEY (' || (SELECT string_agg(kcu2.column_name, ', ' ORDER BY kcu2.ordinal_position) FROM information_schema.key_column_usage kcu2 WHERE kcu2.constraint_name = tc.constraint_name AND kcu2.table_schema = tc.table_schema) || ');'
-                        WHEN tc.constraint_type = 'FOREIGN KEY' THEN
-                            'ALTER TABLE ' || tc.table_schema || '."' || tc.table_name ||
-                            '" ADD CONSTRAINT ' || tc.constraint_name ||
-                            ' FOREIGN KEY (' || (SELECT string_agg(kcu_fk.column_name, ', ' ORDER BY kcu_fk.ordinal_position) FROM information_schema.key_column_usage kcu_fk WHERE kcu_fk.constraint_name = tc.constraint_name AND kcu_fk.table_schema = tc.table_schema) || ') REFERENCES ' ||
-                            (SELECT kcu_ref.table_schema || '.' || kcu_ref.table_name FROM information_schema.referential_constraints rc JOIN information_schema.key_column_usage kcu_ref ON rc.unique_constraint_name = kcu_ref.constraint_name AND rc.constraint_schema = kcu_ref.table_schema WHERE rc.constraint_name = tc.constraint_name LIMIT 1) || '(' || (SELECT string_agg(kcu_ref.column_name, ', ' ORDER BY kcu_ref.ordinal_position) FROM information_schema.referential_constraints rc JOIN information_schema.key_column_usage kcu_ref ON rc.unique_constraint_name = kcu_ref.constraint_name AND rc.constraint_schema = kcu_ref.table_schema WHERE rc.constraint_name = tc.constraint_name) || ')' ||
-                            COALESCE((SELECT CASE WHEN delete_rule != 'NO ACTION' THEN ' ON DELETE ' || delete_rule ELSE '' END FROM information_schema.referential_constraints WHERE constraint_name = tc.constraint_name AND constraint_schema = tc.table_schema LIMIT 1), '') ||
-                            COALESCE((SELECT CASE WHEN update_rule != 'NO ACTION' THEN ' ON UPDATE ' || update_rule ELSE '' END FROM information_schema.referential_constraints WHERE constraint_name = tc.constraint_name AND constraint_schema = tc.table_schema LIMIT 1), '') ||
-                            ';'
-                        WHEN tc.constraint_type = 'UNIQUE' THEN
-                            'ALTER TABLE ' || tc.table_schema || '."' || tc.table_name ||
-                            '" ADD CONSTRAINT ' || tc.constraint_name ||
-                            ' UNIQUE (' || (SELECT string_agg(kcu2.column_name, ', ' ORDER BY kcu2.ordinal_position) FROM information_schema.key_column_usage kcu2 WHERE kcu2.constraint_name = tc.constraint_name AND kcu2.table_schema = tc.table_schema) || ');'
-                        WHEN tc.constraint_type = 'CHECK' THEN
-                            'ALTER TABLE ' || tc.table_schema || '."' || tc.table_name ||
-                            '" ADD CONSTRAINT ' || tc.constraint_name ||
-                            ' CHECK (' || cc.check_clause || ');'
-                    END AS sql,
-                    tc.table_schema,
-                    tc.table_name,
-                    tc.constraint_name
-                FROM
-                    information_schema.table_constraints tc
-                LEFT JOIN
-                    information_schema.check_constraints cc ON tc.constraint_name = cc.constraint_name
-                WHERE
-                    tc.table_schema NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                    AND NOT (tc.constraint_type = 'CHECK' AND cc.check_clause LIKE '%IS NOT NULL%')
-                GROUP BY
-                    tc.table_schema,
-                    tc.table_name,
-                    tc.constraint_name,
-                    tc.constraint_type,
-                    cc.check_clause
-                ORDER BY
-                    CASE tc.constraint_type
-                        WHEN 'PRIMARY KEY' THEN 1
-                        WHEN 'UNIQUE' THEN 2
-                        WHEN 'FOREIGN KEY' THEN 3
-                        WHEN 'CHECK' THEN 4
-                        ELSE 5
-                    END,
-                    tc.table_schema,
-                    tc.table_name,
-                    tc.constraint_name
-                "#,
+            let schema = self.render_database_schema(options).await?;
+            utils::write_schema(
+                schema,
+                self.migrations_folder.clone(),
+                self.schema_file.clone(),
+                options,
             )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !constraints.is_empty() {
-                schema.push_str("-- CONSTRAINTS \n\n");
-                for ele in constraints.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
+            .await
+        };
 
-            let indexes: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    pi.indexdef AS sql
-                FROM
-                    pg_indexes pi
-                JOIN
-                    pg_index i ON i.indexrelid = (
-                        SELECT c.oid FROM pg_class c
-                        WHERE c.relname = pi.indexname
-                        AND c.relnamespace = (SELECT oid FROM pg_namespace WHERE nspname = pi.schemaname)
-                    )
-                WHERE
-                    pi.schemaname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                    AND i.indisprimary = false
-                    AND NOT EXISTS (
-                        SELECT 1 FROM pg_constraint c
-                        WHERE c.conindid = i.indexrelid AND c.contype = 'u'
-                    )
-                ORDER BY
-                    pi.schemaname, pi.indexname ASC;
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !indexes.is_empty() {
-                schema.push_str("-- INDEXES \n\n");
-                for ele in indexes.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push(';');
-                    schema.push_str("\n\n")
-                }
-            }
+        Box::pin(fut)
+    }
 
-            let sequences: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    'CREATE SEQUENCE ' || sequence_schema || '."' || sequence_name ||
-                    '" AS ' || data_type ||
-                    ' START WITH ' || start_value ||
-                    ' MINVALUE ' || minimum_value ||
-                    ' MAXVALUE ' || maximum_value ||
-                    ' INCREMENT BY ' || increment ||
-                    ' CYCLE;' AS sql
-                FROM
-                    information_schema.sequences
-                WHERE
-                    sequence_schema NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                ORDER BY
-                    sequence_schema, sequence_name ASC;
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !sequences.is_empty() {
-                schema.push_str("-- SEQUENCES \n\n");
-                for ele in sequences.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
+    fn render_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + '_>> {
+        let fut = async move {
+            let catalog = pg_catalog::introspect(&mut self.db).await?;
+            let mut schema = pg_catalog::render(&catalog);
+
+            if options.migrations {
+                let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+                let table = utils::quote_identifier(&self.migrations_table, "\"");
+                utils::push_section(
+                    &mut schema,
+                    "MIGRATIONS",
+                    utils::schema_migration_inserts(&table, &migrations),
+                );
             }
 
-            let comments: Vec<String> = sqlx::query(
-                r#"
-                SELECT
-                    'COMMENT ON ' ||
-                    CASE
-                        WHEN pa.attnum > 0 THEN
-                            'COLUMN ' || n.nspname || '.' || pc.relname || '.' || pa.attname
-                        ELSE
-                            'TABLE ' || n.nspname || '.' || pc.relname
-                    END ||
-                    ' IS ' || pd.description || ';' AS sql
-                FROM
-                    pg_class pc
-                    JOIN pg_namespace n ON n.oid = pc.relnamespace
-                    JOIN pg_attribute pa ON pc.oid = pa.attrelid
-                    LEFT JOIN pg_description pd ON pc.oid = pd.objoid AND pa.attnum = pd.objsubid
-                WHERE
-                    n.nspname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-                    AND pd.description IS NOT NULL
-                ORDER BY
-                    n.nspname,
-                    pc.relname,
-                    pa.attnum;
-                "#,
-            )
-            .map(|row: PgRow| row.get("sql"))
-            .fetch_all(&mut self.db)
-            .await?;
-
-            if !comments.is_empty() {
-                schema.push_str("-- COMMENTS \n\n");
-                for ele in comments.iter() {
-                    schema.push_str(ele.as_str());
-                    schema.push_str("\n\n")
-                }
-            }
+            Ok(schema)
+        };
 
-            utils::write_to_schema_file(
-                schema.to_string(),
-                self.migrations_folder.clone(),
-                self.schema_file.clone(),
-            )
-            .await?;
+        Box::pin(fut)
+    }
 
-            Ok(())
+    fn introspect_database_schema(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Schema, anyhow::Error>> + '_>> {
+        let fut = async move {
+            let catalog = pg_catalog::introspect(&mut self.db).await?;
+            Ok(pg_catalog::to_schema(&catalog))
         };
 
         Box::pin(fut)
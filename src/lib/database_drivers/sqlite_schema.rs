@@ -0,0 +1,628 @@
+// Schema dump shared by the SQLite, libSQL and Turso drivers. The connections only run queries,
+// filtering, ordering and formatting of the sqlite_master entries happens here.
+use super::utils::push_section;
+use super::DumpOptions;
+use crate::schema::{
+    Column, Constraint, ConstraintKind, Identity, Index, Reference, Schema, Table, View,
+};
+use anyhow::Result;
+use regex::Regex;
+
+pub enum Value {
+    Integer(i64),
+    Text(String),
+    Other,
+}
+
+// Runs a query and returns the rows with their values
+pub trait Query {
+    async fn rows(&self, query: &str) -> Result<Vec<Vec<Value>>>;
+}
+
+impl Query for libsql::Connection {
+    async fn rows(&self, query: &str) -> Result<Vec<Vec<Value>>> {
+        let mut result = self.query(query, libsql::params![]).await?;
+
+        let mut rows = vec![];
+        while let Some(row) = result.next().await? {
+            let mut values = vec![];
+            for i in 0..row.column_count() {
+                values.push(match row.get_value(i)? {
+                    libsql::Value::Integer(v) => Value::Integer(v),
+                    libsql::Value::Text(v) => Value::Text(v),
+                    _ => Value::Other,
+                });
+            }
+            rows.push(values);
+        }
+
+        Ok(rows)
+    }
+}
+
+impl Query for turso::Connection {
+    async fn rows(&self, query: &str) -> Result<Vec<Vec<Value>>> {
+        let mut stmt = self.prepare(query).await?;
+        let mut result = stmt.query(()).await?;
+
+        let mut rows = vec![];
+        while let Some(row) = result.next().await? {
+            let mut values = vec![];
+            for i in 0..row.column_count() {
+                values.push(match row.get_value(i)? {
+                    turso::Value::Integer(v) => Value::Integer(v),
+                    turso::Value::Text(v) => Value::Text(v),
+                    _ => Value::Other,
+                });
+            }
+            rows.push(values);
+        }
+
+        Ok(rows)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub kind: String,
+    pub name: String,
+    pub sql: String,
+}
+
+async fn objects(conn: &impl Query) -> Result<Vec<Object>> {
+    Ok(conn
+        .rows("SELECT type, name, sql FROM sqlite_master")
+        .await?
+        .into_iter()
+        .filter_map(|row| match <[Value; 3]>::try_from(row).ok()? {
+            [Value::Text(kind), Value::Text(name), Value::Text(sql)] => {
+                Some(Object { kind, name, sql })
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+pub async fn dump(conn: &impl Query, title: &str, options: &DumpOptions) -> Result<String> {
+    let objects = objects(conn).await?;
+
+    let user_version = if options.user_version {
+        match conn.rows("PRAGMA user_version").await?.first() {
+            Some(row) => match row.first() {
+                Some(Value::Integer(version)) => Some(*version),
+                _ => None,
+            },
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(render(title, objects, user_version))
+}
+
+// Objects named sqlite_ are created by SQLite itself, autoindexes have no sql at all. Virtual
+// tables keep their data in shadow tables which are created again with the virtual table.
+fn is_internal(object: &Object, virtual_tables: &[&str]) -> bool {
+    object.name.starts_with("sqlite_")
+        || (object.kind == "table"
+            && virtual_tables
+                .iter()
+                .any(|vtab| object.name.starts_with(&format!("{}_", vtab))))
+}
+
+fn format_statement(sql: &str) -> String {
+    let statement = sql
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    format!("{};", statement.trim().trim_end_matches(';').trim_end())
+}
+
+// The objects a dump or the schema model contains, in name order
+fn user_objects(mut objects: Vec<Object>) -> Vec<Object> {
+    let virtual_tables = objects
+        .iter()
+        .filter(|o| o.kind == "table" && o.sql.to_uppercase().starts_with("CREATE VIRTUAL TABLE"))
+        .map(|o| o.name.clone())
+        .collect::<Vec<String>>();
+    let virtual_tables = virtual_tables
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    objects.retain(|o| !is_internal(o, &virtual_tables));
+    objects.sort_by(|a, b| a.name.cmp(&b.name));
+
+    objects
+}
+
+pub fn render(title: &str, objects: Vec<Object>, user_version: Option<i64>) -> String {
+    let mut schema = [
+        "--",
+        &format!("-- {} SQL Schema dump automatic generated by geni", title),
+        "--",
+        "",
+        "",
+    ]
+    .join("\n");
+
+    if let Some(version) = user_version {
+        schema.push_str(&format!("PRAGMA user_version = {};\n\n", version));
+    }
+
+    let objects = user_objects(objects);
+
+    for (kind, title) in [
+        ("table", "TABLES"),
+        ("index", "INDEXES"),
+        ("view", "VIEWS"),
+        ("trigger", "TRIGGERS"),
+    ] {
+        push_section(
+            &mut schema,
+            title,
+            objects
+                .iter()
+                .filter(|o| o.kind == kind)
+                .map(|o| format_statement(&o.sql)),
+        );
+    }
+
+    schema
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Text(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+fn integer(value: &Value) -> i64 {
+    match value {
+        Value::Integer(v) => *v,
+        _ => 0,
+    }
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Reads the schema model from sqlite_master and the table pragmas. SQLite doesn't keep the names
+// of constraints and check constraints only exist in the table definition, they're left out.
+pub async fn introspect(conn: &impl Query) -> Result<Schema> {
+    let objects = user_objects(objects(conn).await?);
+    let mut schema = Schema::default();
+
+    for object in objects.iter().filter(|o| o.kind == "table") {
+        let table = quote_literal(&object.name);
+        let rows = conn
+            .rows(&format!(
+                "SELECT name, type, \"notnull\", dflt_value, pk, hidden FROM pragma_table_xinfo({}) ORDER BY cid",
+                table
+            ))
+            .await?;
+
+        let mut columns = vec![];
+        let mut primary_key = vec![];
+        for row in rows.iter().filter(|row| integer(&row[5]) != 1) {
+            let name = text(&row[0]).unwrap_or_default();
+            if integer(&row[4]) > 0 {
+                primary_key.push((integer(&row[4]), name.clone()));
+            }
+
+            columns.push(Column {
+                generated: match integer(&row[5]) {
+                    2 | 3 => generated_expression(&object.sql, &name),
+                    _ => None,
+                },
+                name,
+                data_type: text(&row[1]).unwrap_or_default(),
+                nullable: integer(&row[2]) == 0,
+                default: text(&row[3]),
+                identity: None,
+                collation: None,
+            });
+        }
+        primary_key.sort();
+        let primary_key = primary_key
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect::<Vec<String>>();
+
+        // AUTOINCREMENT is only allowed on a single INTEGER PRIMARY KEY column
+        if let [column] = primary_key.as_slice() {
+            if object.sql.to_uppercase().contains("AUTOINCREMENT") {
+                if let Some(c) = columns.iter_mut().find(|c| &c.name == column) {
+                    c.identity = Some(Identity::AutoIncrement);
+                }
+            }
+        }
+
+        if !primary_key.is_empty() {
+            schema.constraints.push(Constraint {
+                schema: None,
+                table: object.name.clone(),
+                name: None,
+                kind: ConstraintKind::PrimaryKey,
+                definition: format!("PRIMARY KEY ({})", primary_key.join(", ")),
+                columns: primary_key,
+                references: None,
+            });
+        }
+
+        let indexes = conn
+            .rows(&format!(
+                "SELECT name, \"unique\", origin FROM pragma_index_list({}) ORDER BY name",
+                table
+            ))
+            .await?;
+        for index in indexes {
+            let name = text(&index[0]).unwrap_or_default();
+            let columns = conn
+                .rows(&format!(
+                    "SELECT name FROM pragma_index_info({}) ORDER BY seqno",
+                    quote_literal(&name)
+                ))
+                .await?
+                .iter()
+                .filter_map(|row| text(&row[0]))
+                .collect::<Vec<String>>();
+
+            match text(&index[2]).as_deref() {
+                Some("u") => schema.constraints.push(Constraint {
+                    schema: None,
+                    table: object.name.clone(),
+                    name: None,
+                    kind: ConstraintKind::Unique,
+                    definition: format!("UNIQUE ({})", columns.join(", ")),
+                    columns,
+                    references: None,
+                }),
+                Some("c") => {
+                    let Some(index_object) = objects.iter().find(|o| o.name == name) else {
+                        continue;
+                    };
+                    schema.indexes.push(Index {
+                        schema: None,
+                        table: object.name.clone(),
+                        name,
+                        columns,
+                        unique: integer(&index[1]) == 1,
+                        definition: format_statement(&index_object.sql),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let foreign_keys = conn
+            .rows(&format!(
+                "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete FROM pragma_foreign_key_list({}) ORDER BY id, seq",
+                table
+            ))
+            .await?;
+        let mut ids = foreign_keys
+            .iter()
+            .map(|row| integer(&row[0]))
+            .collect::<Vec<i64>>();
+        ids.dedup();
+        for id in ids {
+            let rows = foreign_keys
+                .iter()
+                .filter(|row| integer(&row[0]) == id)
+                .collect::<Vec<&Vec<Value>>>();
+            schema
+                .constraints
+                .push(foreign_key(&object.name, rows.as_slice()));
+        }
+
+        schema.tables.push(Table {
+            schema: None,
+            name: object.name.clone(),
+            columns,
+        });
+    }
+
+    schema.views = objects
+        .iter()
+        .filter(|o| o.kind == "view")
+        .map(|o| View {
+            schema: None,
+            name: o.name.clone(),
+            materialized: false,
+            definition: format_statement(&o.sql),
+        })
+        .collect();
+
+    Ok(schema)
+}
+
+// A foreign key from the rows pragma_foreign_key_list returns for it, in column order
+fn foreign_key(table: &str, rows: &[&Vec<Value>]) -> Constraint {
+    let columns = rows
+        .iter()
+        .filter_map(|row| text(&row[2]))
+        .collect::<Vec<String>>();
+    // Without target columns the foreign key references the primary key
+    let foreign_columns = rows
+        .iter()
+        .filter_map(|row| text(&row[3]))
+        .collect::<Vec<String>>();
+    let foreign_table = text(&rows[0][1]).unwrap_or_default();
+
+    let mut definition = format!(
+        "FOREIGN KEY ({}) REFERENCES {}",
+        columns.join(", "),
+        foreign_table
+    );
+    if !foreign_columns.is_empty() {
+        definition.push_str(&format!(" ({})", foreign_columns.join(", ")));
+    }
+    for (action, index) in [("UPDATE", 4), ("DELETE", 5)] {
+        match text(&rows[0][index]) {
+            Some(rule) if rule != "NO ACTION" => {
+                definition.push_str(&format!(" ON {} {}", action, rule))
+            }
+            _ => {}
+        }
+    }
+
+    Constraint {
+        schema: None,
+        table: table.to_string(),
+        name: None,
+        kind: ConstraintKind::ForeignKey,
+        columns,
+        references: Some(Reference {
+            schema: None,
+            table: foreign_table,
+            columns: foreign_columns,
+        }),
+        definition,
+    }
+}
+
+// Splits on the commas outside of parentheses and quotes
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut quote, mut start) = (0, None, 0);
+
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+}
+
+// SQLite doesn't report the expression of a generated column, it's read from the table definition
+fn generated_expression(sql: &str, column: &str) -> Option<String> {
+    let body = &sql[sql.find('(')? + 1..sql.rfind(')')?];
+    let definition = split_top_level(body).into_iter().find(|definition| {
+        let name = definition.split_whitespace().next().unwrap_or_default();
+        name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'))
+            .eq_ignore_ascii_case(column)
+    })?;
+
+    let open = Regex::new(r"(?i)\bAS\s*\(")
+        .unwrap()
+        .find(definition)?
+        .end();
+    let rest = &definition[open..];
+    let (mut depth, mut quote) = (1, None);
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(rest[..i].trim().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(kind: &str, name: &str, sql: &str) -> Object {
+        Object {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            sql: sql.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_orders_and_skips_internal_objects() {
+        let objects = vec![
+            object("trigger", "users_touch", "CREATE TRIGGER users_touch AFTER UPDATE ON users BEGIN SELECT 1; END"),
+            object("view", "active_users", "CREATE VIEW active_users AS SELECT * FROM users"),
+            object("table", "users", "CREATE TABLE users (\n  id INTEGER PRIMARY KEY AUTOINCREMENT,   \n  email TEXT UNIQUE\n)"),
+            object("table", "sqlite_sequence", "CREATE TABLE sqlite_sequence(name,seq)"),
+            object("index", "idx_users_email", "CREATE INDEX idx_users_email ON users(email)"),
+            object("table", "accounts", "CREATE TABLE accounts (id INTEGER);"),
+            object("table", "docs", "CREATE VIRTUAL TABLE docs USING fts5(body)"),
+            object("table", "docs_data", "CREATE TABLE 'docs_data'(id INTEGER PRIMARY KEY, block BLOB)"),
+        ];
+
+        let schema = render("Sqlite", objects, None);
+
+        assert_eq!(
+            schema,
+            [
+                "--",
+                "-- Sqlite SQL Schema dump automatic generated by geni",
+                "--",
+                "",
+                "-- TABLES ",
+                "",
+                "CREATE TABLE accounts (id INTEGER);",
+                "",
+                "CREATE VIRTUAL TABLE docs USING fts5(body);",
+                "",
+                "CREATE TABLE users (",
+                "  id INTEGER PRIMARY KEY AUTOINCREMENT,",
+                "  email TEXT UNIQUE",
+                ");",
+                "",
+                "-- INDEXES ",
+                "",
+                "CREATE INDEX idx_users_email ON users(email);",
+                "",
+                "-- VIEWS ",
+                "",
+                "CREATE VIEW active_users AS SELECT * FROM users;",
+                "",
+                "-- TRIGGERS ",
+                "",
+                "CREATE TRIGGER users_touch AFTER UPDATE ON users BEGIN SELECT 1; END;",
+                "",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_user_version() {
+        let schema = render(
+            "Turso",
+            vec![object(
+                "table",
+                "schema_migrations",
+                "CREATE TABLE schema_migrations (id VARCHAR(255) PRIMARY KEY)",
+            )],
+            Some(3),
+        );
+
+        assert!(schema.contains("--\n\nPRAGMA user_version = 3;\n\n-- TABLES \n"));
+    }
+
+    #[test]
+    fn test_generated_expression() {
+        let sql = "CREATE TABLE \"items\" (\n  id INTEGER PRIMARY KEY,\n  price REAL,\n  \"total\" REAL GENERATED ALWAYS AS (round(price * 1.25, 2)) STORED,\n  label TEXT AS ('a,b' || (id)) VIRTUAL\n)";
+
+        assert_eq!(
+            generated_expression(sql, "total").as_deref(),
+            Some("round(price * 1.25, 2)")
+        );
+        assert_eq!(
+            generated_expression(sql, "label").as_deref(),
+            Some("'a,b' || (id)")
+        );
+        assert_eq!(generated_expression(sql, "price"), None);
+    }
+
+    #[tokio::test]
+    async fn test_introspect() {
+        let conn = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap()
+            .connect()
+            .unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, email TEXT NOT NULL UNIQUE);
+            CREATE TABLE posts (
+                id INTEGER PRIMARY KEY,
+                user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+                title TEXT DEFAULT 'untitled',
+                slug TEXT AS (lower(title))
+            );
+            CREATE INDEX idx_posts_user_id ON posts(user_id);
+            CREATE VIEW titles AS SELECT title FROM posts;
+            "#,
+        )
+        .await
+        .unwrap();
+
+        let schema = introspect(&conn).await.unwrap();
+
+        let tables = schema
+            .tables
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(tables, vec!["posts", "users"]);
+        assert_eq!(
+            schema.tables[0].columns[2],
+            Column {
+                name: "title".to_string(),
+                data_type: "TEXT".to_string(),
+                nullable: true,
+                default: Some("'untitled'".to_string()),
+                identity: None,
+                generated: None,
+                collation: None,
+            }
+        );
+        assert_eq!(
+            schema.tables[0].columns[3].generated.as_deref(),
+            Some("lower(title)")
+        );
+        assert_eq!(
+            schema.tables[1].columns[0].identity,
+            Some(Identity::AutoIncrement)
+        );
+
+        let definitions = schema
+            .constraints
+            .iter()
+            .map(|c| (c.table.as_str(), c.definition.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(
+            definitions,
+            vec![
+                ("posts", "PRIMARY KEY (id)"),
+                (
+                    "posts",
+                    "FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE"
+                ),
+                ("users", "PRIMARY KEY (id)"),
+                ("users", "UNIQUE (email)"),
+            ]
+        );
+
+        assert_eq!(
+            schema.indexes,
+            vec![Index {
+                schema: None,
+                table: "posts".to_string(),
+                name: "idx_posts_user_id".to_string(),
+                columns: vec!["user_id".to_string()],
+                unique: false,
+                definition: "CREATE INDEX idx_posts_user_id ON posts(user_id);".to_string(),
+            }]
+        );
+        assert_eq!(
+            schema.views[0].definition,
+            "CREATE VIEW titles AS SELECT title FROM posts;"
+        );
+    }
+}
@@ -1,11 +1,12 @@
-use crate::database_drivers::DatabaseDriver;
+use crate::database_drivers::{DatabaseDriver, DumpOptions, SchemaMigration};
+use crate::schema::Schema;
 use anyhow::{bail, Result};
 use log::info;
 use std::future::Future;
 use std::pin::Pin;
-use turso::{Builder, Connection};
+use turso::{Builder, Connection, Row, Value};
 
-use super::utils;
+use super::{sqlite_schema, utils};
 
 pub struct TursoDriver {
     conn: Connection,
@@ -40,32 +41,72 @@ impl TursoDriver {
             schema_file,
         })
     }
+
+    // Empty when the migrations table doesn't exist
+    async fn migrations_table_columns(&mut self) -> Result<Vec<String>> {
+        let (_, name) = utils::split_table_name(&self.migrations_table);
+        let mut stmt = self
+            .conn
+            .prepare(
+                format!(
+                    "PRAGMA table_info({});",
+                    utils::quote_identifier(name, "\"")
+                )
+                .as_str(),
+            )
+            .await?;
+        let mut rows = stmt.query(()).await?;
+
+        let mut columns: Vec<String> = vec![];
+        while let Some(row) = rows.next().await? {
+            if let Some(column) = text_value(&row, 1)? {
+                columns.push(column);
+            }
+        }
+
+        Ok(columns)
+    }
+}
+
+fn text_value(row: &Row, idx: usize) -> Result<Option<String>> {
+    match row.get_value(idx)? {
+        Value::Text(v) => Ok(Some(v)),
+        _ => Ok(None),
+    }
+}
+
+fn integer_value(row: &Row, idx: usize) -> Result<Option<i64>> {
+    match row.get_value(idx)? {
+        Value::Integer(v) => Ok(Some(v)),
+        _ => Ok(None),
+    }
 }
 
 impl DatabaseDriver for TursoDriver {
-    fn execute<'a>(
+    fn execute_all<'a>(
         &'a mut self,
-        query: &'a str,
+        queries: &'a [&'a str],
         run_in_transaction: bool,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             if run_in_transaction {
                 // Turso SDK doesn't have execute_transactional_batch, so we wrap in BEGIN/COMMIT
                 self.conn.execute("BEGIN TRANSACTION", ()).await?;
-                match self.conn.execute(query, ()).await {
-                    Ok(_) => {
-                        self.conn.execute("COMMIT", ()).await?;
-                        Ok(())
-                    }
-                    Err(e) => {
+                for query in queries {
+                    if let Err(e) = self.conn.execute(query, ()).await {
                         let _ = self.conn.execute("ROLLBACK", ()).await;
-                        Err(e.into())
+                        return Err(e.into());
                     }
                 }
-            } else {
+                self.conn.execute("COMMIT", ()).await?;
+                return Ok(());
+            }
+
+            for query in queries {
                 self.conn.execute(query, ()).await?;
-                Ok(())
             }
+
+            Ok(())
         };
 
         Box::pin(fut)
@@ -88,21 +129,54 @@ impl DatabaseDriver for TursoDriver {
                 )
                 .await?;
 
+            let columns = self.migrations_table_columns().await?;
+            for query in utils::add_tracking_columns_queries(&table, &columns) {
+                self.conn.execute(query.as_str(), ()).await?;
+            }
+
+            let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+
+            Ok(migrations.into_iter().map(|m| m.id).collect())
+        };
+
+        Box::pin(fut)
+    }
+
+    fn get_schema_migrations(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Vec<SchemaMigration>>, anyhow::Error>> + '_>>
+    {
+        let fut = async move {
+            let columns = self.migrations_table_columns().await?;
+            if columns.is_empty() {
+                return Ok(None);
+            }
+
+            let table = utils::quote_identifier(&self.migrations_table, "\"");
             let mut stmt = self
                 .conn
-                .prepare(format!("SELECT id FROM {} ORDER BY id DESC;", table).as_str())
+                .prepare(utils::select_schema_migrations_query(&table, &columns).as_str())
                 .await?;
 
             let mut rows = stmt.query(()).await?;
 
-            let mut schema_migrations: Vec<String> = vec![];
+            let mut schema_migrations: Vec<SchemaMigration> = vec![];
             while let Some(row) = rows.next().await? {
-                if let Ok(id) = row.get::<String>(0) {
-                    schema_migrations.push(id);
-                }
+                let Some(id) = text_value(&row, 0)? else {
+                    continue;
+                };
+
+                schema_migrations.push(SchemaMigration {
+                    id,
+                    name: text_value(&row, 1)?,
+                    applied_at: utils::parse_applied_at(text_value(&row, 2)?),
+                    duration_ms: integer_value(&row, 3)?,
+                    applied_by: text_value(&row, 4)?,
+                    batch: integer_value(&row, 5)?,
+                });
             }
 
-            Ok(schema_migrations)
+            Ok(Some(utils::newest_first(schema_migrations)))
         };
 
         Box::pin(fut)
@@ -110,14 +184,25 @@ impl DatabaseDriver for TursoDriver {
 
     fn insert_schema_migration<'a>(
         &'a mut self,
-        id: &'a str,
+        migration: &'a SchemaMigration,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
             let table = utils::quote_identifier(&self.migrations_table, "\"");
             self.conn
                 .execute(
-                    format!("INSERT INTO {} (id) VALUES (?)", table).as_str(),
-                    [id],
+                    format!(
+                        "INSERT INTO {} (id, name, applied_at, duration_ms, applied_by, batch) VALUES (?, ?, ?, ?, ?, ?)",
+                        table
+                    )
+                    .as_str(),
+                    (
+                        migration.id.as_str(),
+                        migration.name.clone(),
+                        utils::format_applied_at(&migration.applied_at),
+                        migration.duration_ms,
+                        migration.applied_by.clone(),
+                        migration.batch,
+                    ),
                 )
                 .await?;
             Ok(())
@@ -170,51 +255,54 @@ impl DatabaseDriver for TursoDriver {
         Box::pin(fut)
     }
 
-    fn dump_database_schema(
-        &mut self,
+    fn dump_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + '_>> {
         let fut = async move {
-            let schema = r#"
-                --
-                -- Turso SQL Schema dump automatic generated by geni
-                --
-
-            "#;
-            let mut schema = schema
-                .lines()
-                .map(str::trim_start)
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            let mut stmt = self.conn.prepare("SELECT sql FROM sqlite_master").await?;
-
-            let mut rows = stmt.query(()).await?;
-
-            let mut schemas: Vec<String> = vec![];
-            while let Some(row) = rows.next().await? {
-                if let Ok(sql) = row.get::<String>(0) {
-                    let text = sql
-                        .trim_start_matches('"')
-                        .trim_end_matches('"')
-                        .replace("\\n", "\n");
-                    schemas.push(format!("{};", text));
-                }
-            }
-
-            schema.push_str(schemas.join("\n").as_str());
-
-            utils::write_to_schema_file(
+            let schema = self.render_database_schema(options).await?;
+            utils::write_schema(
                 schema,
                 self.migrations_folder.clone(),
                 self.schema_file.clone(),
+                options,
             )
-            .await?;
+            .await
+        };
 
-            Ok(())
+        Box::pin(fut)
+    }
+
+    fn render_database_schema<'a>(
+        &'a mut self,
+        options: &'a DumpOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + '_>> {
+        let fut = async move {
+            let mut schema = sqlite_schema::dump(&self.conn, "Turso", options).await?;
+
+            if options.migrations {
+                let migrations = self.get_schema_migrations().await?.unwrap_or_default();
+                let table = utils::quote_identifier(&self.migrations_table, "\"");
+                utils::push_section(
+                    &mut schema,
+                    "MIGRATIONS",
+                    utils::schema_migration_inserts(&table, &migrations),
+                );
+            }
+
+            Ok(schema)
         };
 
         Box::pin(fut)
     }
+
+    fn introspect_database_schema(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Schema, anyhow::Error>> + '_>> {
+        let fut = async move { sqlite_schema::introspect(&self.conn).await };
+
+        Box::pin(fut)
+    }
 }
 
 #[cfg(test)]
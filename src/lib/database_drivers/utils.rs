@@ -1,7 +1,12 @@
+use super::{DumpOptions, SchemaMigration};
+use crate::split_schema;
 use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::cmp::Ordering;
 use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::path::Path;
+use url::Url;
 
 pub fn quote_identifier(name: &str, quote: &str) -> String {
     if name.contains('.') {
@@ -14,6 +19,106 @@ pub fn quote_identifier(name: &str, quote: &str) -> String {
     }
 }
 
+// Tracking columns next to id. Tables created by older versions only have id, so missing
+// columns are added when the table is opened for writing and read as NULL otherwise
+pub const TRACKING_COLUMNS: [(&str, &str); 5] = [
+    ("name", "VARCHAR(255)"),
+    ("applied_at", "VARCHAR(64)"),
+    ("duration_ms", "BIGINT"),
+    ("applied_by", "VARCHAR(255)"),
+    ("batch", "BIGINT"),
+];
+
+fn has_column(columns: &[String], name: &str) -> bool {
+    columns.iter().any(|c| c.eq_ignore_ascii_case(name))
+}
+
+pub fn add_tracking_columns_queries(table: &str, columns: &[String]) -> Vec<String> {
+    TRACKING_COLUMNS
+        .iter()
+        .filter(|(name, _)| !has_column(columns, name))
+        .map(|(name, column_type)| {
+            format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, column_type)
+        })
+        .collect()
+}
+
+pub fn select_schema_migrations_query(table: &str, columns: &[String]) -> String {
+    let selected = TRACKING_COLUMNS
+        .iter()
+        .map(|(name, _)| {
+            if has_column(columns, name) {
+                name.to_string()
+            } else {
+                format!("NULL AS {}", name)
+            }
+        })
+        .collect::<Vec<String>>();
+
+    format!("SELECT id, {} FROM {}", selected.join(", "), table)
+}
+
+// Ids are stored as text, they're compared as the numbers they are so 10 comes after 9
+pub fn compare_ids(a: &str, b: &str) -> Ordering {
+    match (a.parse::<i64>(), b.parse::<i64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+pub fn newest_first(mut migrations: Vec<SchemaMigration>) -> Vec<SchemaMigration> {
+    migrations.sort_by(|a, b| compare_ids(&b.id, &a.id));
+    migrations
+}
+
+// applied_at is stored as text so every driver sorts and compares it the same way
+pub fn format_applied_at(applied_at: &Option<DateTime<Utc>>) -> Option<String> {
+    applied_at.map(|a| a.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+pub fn parse_applied_at(applied_at: Option<String>) -> Option<DateTime<Utc>> {
+    applied_at
+        .and_then(|a| DateTime::parse_from_rfc3339(&a).ok())
+        .map(|a| a.with_timezone(&Utc))
+}
+
+// INSERT statements recreating the rows of the migrations table, oldest first. Appended to the
+// schema dump so a database loaded from it knows which migrations it already contains
+pub fn schema_migration_inserts(table: &str, migrations: &[SchemaMigration]) -> Vec<String> {
+    let text = |value: Option<&str>| match value {
+        Some(v) => format!("'{}'", v.replace('\'', "''")),
+        None => "NULL".to_string(),
+    };
+    let number = |value: Option<i64>| value.map_or("NULL".to_string(), |v| v.to_string());
+
+    let mut migrations = migrations.iter().collect::<Vec<&SchemaMigration>>();
+    migrations.sort_by(|a, b| compare_ids(&a.id, &b.id));
+
+    migrations
+        .iter()
+        .map(|m| {
+            format!(
+                "INSERT INTO {} (id, name, applied_at, duration_ms, applied_by, batch) VALUES ({}, {}, {}, {}, {}, {});",
+                table,
+                text(Some(&m.id)),
+                text(m.name.as_deref()),
+                text(format_applied_at(&m.applied_at).as_deref()),
+                number(m.duration_ms),
+                text(m.applied_by.as_deref()),
+                number(m.batch),
+            )
+        })
+        .collect()
+}
+
+// Splits a schema qualified table name, as used for lookups in the catalog tables
+pub fn split_table_name(name: &str) -> (Option<&str>, &str) {
+    match name.split_once('.') {
+        Some((schema, table)) => (Some(schema), table),
+        None => (None, name),
+    }
+}
+
 pub async fn write_to_schema_file(
     content: String,
     migrations_folder: String,
@@ -39,11 +144,156 @@ pub async fn write_to_schema_file(
     Ok(())
 }
 
+// Writes the dump to the schema file, or split into one file per object with options.split
+pub async fn write_schema(
+    content: String,
+    migrations_folder: String,
+    schema_file: String,
+    options: &DumpOptions,
+) -> Result<()> {
+    if options.split {
+        return split_schema::write(&content, &migrations_folder, &schema_file);
+    }
+
+    write_to_schema_file(content, migrations_folder, schema_file).await
+}
+
+// Appends a "-- TITLE" section with one statement per paragraph, empty sections are left out
+pub fn push_section(
+    schema: &mut String,
+    title: &str,
+    statements: impl IntoIterator<Item = String>,
+) {
+    let statements = statements.into_iter().collect::<Vec<String>>();
+    if statements.is_empty() {
+        return;
+    }
+
+    schema.push_str(&format!("-- {} \n\n", title));
+    for statement in statements {
+        schema.push_str(&statement);
+        schema.push_str("\n\n");
+    }
+}
+
+// Hides the password so the url can be logged
+pub fn redact_database_url(database_url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(database_url) else {
+        return database_url.to_string();
+    };
+
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some("******"));
+    }
+
+    parsed.to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_add_tracking_columns_queries() {
+        let queries = add_tracking_columns_queries("\"schema_migrations\"", &["id".to_string()]);
+        assert_eq!(queries.len(), 5);
+        assert_eq!(
+            queries[0],
+            "ALTER TABLE \"schema_migrations\" ADD COLUMN name VARCHAR(255)"
+        );
+
+        let columns = [
+            "ID",
+            "NAME",
+            "applied_at",
+            "duration_ms",
+            "applied_by",
+            "batch",
+        ]
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<String>>();
+        assert!(add_tracking_columns_queries("schema_migrations", &columns).is_empty());
+    }
+
+    #[test]
+    fn test_select_schema_migrations_query() {
+        let columns = vec!["id".to_string(), "applied_at".to_string()];
+
+        assert_eq!(
+            select_schema_migrations_query("schema_migrations", &columns),
+            "SELECT id, NULL AS name, applied_at, NULL AS duration_ms, NULL AS applied_by, NULL AS batch FROM schema_migrations"
+        );
+    }
+
+    #[test]
+    fn test_newest_first_compares_ids_as_numbers() {
+        let migration = |id: &str| SchemaMigration {
+            id: id.to_string(),
+            ..Default::default()
+        };
+
+        let ids = newest_first(vec![migration("9"), migration("10"), migration("100")])
+            .into_iter()
+            .map(|m| m.id)
+            .collect::<Vec<String>>();
+        assert_eq!(ids, vec!["100", "10", "9"]);
+        assert_eq!(compare_ids("abc", "abd"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_applied_at_round_trip() {
+        let applied_at = DateTime::parse_from_rfc3339("2024-01-02T03:04:05.678Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let formatted = format_applied_at(&Some(applied_at));
+        assert_eq!(formatted.as_deref(), Some("2024-01-02T03:04:05.678Z"));
+        assert_eq!(parse_applied_at(formatted), Some(applied_at));
+        assert_eq!(parse_applied_at(Some("yesterday".to_string())), None);
+        assert_eq!(parse_applied_at(None), None);
+    }
+
+    #[test]
+    fn test_schema_migration_inserts() {
+        let applied_at = parse_applied_at(Some("2024-05-01T10:00:00.000Z".to_string()));
+        let migrations = vec![
+            SchemaMigration {
+                id: "10".to_string(),
+                ..Default::default()
+            },
+            SchemaMigration {
+                id: "9".to_string(),
+                name: Some("o'brien".to_string()),
+                applied_at,
+                duration_ms: Some(12),
+                applied_by: Some("ci".to_string()),
+                batch: Some(1),
+            },
+        ];
+
+        assert_eq!(
+            schema_migration_inserts("\"schema_migrations\"", &migrations),
+            vec![
+                "INSERT INTO \"schema_migrations\" (id, name, applied_at, duration_ms, applied_by, batch) VALUES ('9', 'o''brien', '2024-05-01T10:00:00.000Z', 12, 'ci', 1);",
+                "INSERT INTO \"schema_migrations\" (id, name, applied_at, duration_ms, applied_by, batch) VALUES ('10', NULL, NULL, NULL, NULL, NULL);",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_table_name() {
+        assert_eq!(
+            split_table_name("schema_migrations"),
+            (None, "schema_migrations")
+        );
+        assert_eq!(
+            split_table_name("migrations.schema_migrations"),
+            (Some("migrations"), "schema_migrations")
+        );
+    }
+
     #[tokio::test]
     async fn test_write_to_schema_file_new_file() {
         let tmp_dir = tempdir().unwrap();
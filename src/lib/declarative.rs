@@ -0,0 +1,1066 @@
+use crate::config::{Database, VersionScheme};
+use crate::database_drivers::{self, utils::split_table_name};
+use crate::generate::generate_migration;
+use crate::schema::{Column, Constraint, ConstraintKind, Identity, Schema, Sequence, Table, View};
+use crate::utils::read_file_content;
+use anyhow::{bail, Result};
+use log::{info, warn};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Postgres,
+    // MySQL and MariaDB
+    MySQL,
+    // SQLite, libSQL and Turso
+    SQLite,
+}
+
+// The statements of one migration file. Destructive statements and changes geni can't write
+// are marked with a comment in the file and listed as warnings.
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+    pub statements: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Plan {
+    fn push(&mut self, statement: String) {
+        self.statements.push(statement);
+    }
+
+    fn destructive(&mut self, description: String, statement: String) {
+        self.statements
+            .push(format!("-- DESTRUCTIVE: {}\n{}", description, statement));
+        self.warnings.push(format!("Destructive: {}", description));
+    }
+
+    fn manual(&mut self, description: String) {
+        self.statements.push(format!(
+            "-- TODO: {}, write this change by hand",
+            description
+        ));
+        self.warnings
+            .push(format!("Not generated: {}", description));
+    }
+
+    pub fn render(&self, desired_file: &str) -> String {
+        let mut sql = format!(
+            "-- Generated by geni diff-migration from {}\n",
+            desired_file
+        );
+        for statement in &self.statements {
+            sql.push('\n');
+            sql.push_str(statement);
+            sql.push('\n');
+        }
+
+        sql
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn diff_migration(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    desired_file: String,
+    name: &str,
+    version_scheme: &VersionScheme,
+) -> Result<()> {
+    let desired_path = Path::new(&migration_folder).join(&desired_file);
+    if !desired_path.exists() {
+        bail!(
+            "Didn't find the desired schema file {}",
+            desired_path.display()
+        );
+    }
+    let desired_sql = read_file_content(&desired_path);
+    let dialect = dialect(&database_url)?;
+
+    let mut database = database_drivers::new(
+        database_url.clone(),
+        database_token,
+        migration_table.clone(),
+        migration_folder.clone(),
+        schema_file.clone(),
+        wait_timeout,
+        true,
+    )
+    .await?;
+    let current = database.introspect_database_schema().await?;
+    let desired = introspect_desired(
+        &database_url,
+        &migration_table,
+        &migration_folder,
+        &schema_file,
+        wait_timeout,
+        &desired_sql,
+    )
+    .await?;
+
+    // The migrations table is geni's own and never part of desired.sql
+    let (_, table) = split_table_name(&migration_table);
+    let current = without_table(current, table);
+    let desired = without_table(desired, table);
+
+    let up = plan(&current, &desired, dialect);
+    if up.statements.is_empty() {
+        info!(
+            "The database already matches {}, no migration was written",
+            desired_path.display()
+        );
+        return Ok(());
+    }
+    let down = plan(&desired, &current, dialect);
+
+    for warning in &up.warnings {
+        warn!("{}", warning);
+    }
+
+    generate_migration(
+        &migration_folder,
+        name,
+        version_scheme,
+        &up.render(&desired_file),
+        &down.render(&desired_file),
+    )
+}
+
+fn dialect(database_url: &str) -> Result<Dialect> {
+    let url = url::Url::parse(database_url)?;
+
+    Ok(match Database::new(url.scheme())? {
+        Database::Postgres => Dialect::Postgres,
+        Database::MySQL | Database::MariaDB => Dialect::MySQL,
+        Database::SQLite | Database::LibSQL | Database::Turso => Dialect::SQLite,
+    })
+}
+
+// desired.sql is loaded into a new empty database of the same kind which is removed afterwards.
+// SQLite files are used for libSQL as remote databases can't be created from here.
+async fn introspect_desired(
+    database_url: &str,
+    migration_table: &str,
+    migration_folder: &str,
+    schema_file: &str,
+    wait_timeout: Option<usize>,
+    sql: &str,
+) -> Result<Schema> {
+    let url = url::Url::parse(database_url)?;
+    let scratch_name = format!("geni_desired_{}", rand::random::<u32>());
+
+    let load = |scratch_url: String| async move {
+        let mut scratch = database_drivers::new(
+            scratch_url,
+            None,
+            migration_table.to_string(),
+            migration_folder.to_string(),
+            schema_file.to_string(),
+            wait_timeout,
+            true,
+        )
+        .await?;
+        scratch.execute(sql, true).await?;
+        scratch.introspect_database_schema().await
+    };
+
+    match Database::new(url.scheme())? {
+        Database::Postgres | Database::MySQL | Database::MariaDB => {
+            let mut scratch_url = url.clone();
+            scratch_url.set_path(&format!("/{}", scratch_name));
+
+            let mut server = database_drivers::new(
+                scratch_url.to_string(),
+                None,
+                migration_table.to_string(),
+                migration_folder.to_string(),
+                schema_file.to_string(),
+                wait_timeout,
+                false,
+            )
+            .await?;
+            server.create_database().await?;
+            let schema = load(scratch_url.to_string()).await;
+            server.drop_database().await?;
+
+            schema
+        }
+        driver => {
+            let folder = std::env::temp_dir().join(&scratch_name);
+            fs::create_dir_all(&folder)?;
+            let scheme = match driver {
+                Database::Turso => "turso",
+                _ => "sqlite",
+            };
+            let schema = load(format!(
+                "{}://{}",
+                scheme,
+                folder.join("desired.db").display()
+            ))
+            .await;
+            fs::remove_dir_all(&folder)?;
+
+            schema
+        }
+    }
+}
+
+fn without_table(mut schema: Schema, table: &str) -> Schema {
+    schema.tables.retain(|t| t.name != table);
+    schema.constraints.retain(|c| c.table != table);
+    schema.indexes.retain(|i| i.table != table);
+
+    schema
+}
+
+// The statements turning the current schema into the desired one. Objects are dropped before
+// the objects they depend on and created after them.
+pub fn plan(current: &Schema, desired: &Schema, dialect: Dialect) -> Plan {
+    let mut plan = Plan::default();
+
+    let table_in = |schema: &Schema, t: &Table| {
+        schema
+            .tables
+            .iter()
+            .any(|other| other.schema == t.schema && other.name == t.name)
+    };
+    let dropped_tables = current
+        .tables
+        .iter()
+        .filter(|t| !table_in(desired, t))
+        .collect::<Vec<&Table>>();
+    let created_tables = desired
+        .tables
+        .iter()
+        .filter(|t| !table_in(current, t))
+        .collect::<Vec<&Table>>();
+    let is_dropped = |schema: &Option<String>, table: &str| {
+        dropped_tables
+            .iter()
+            .any(|t| &t.schema == schema && t.name == table)
+    };
+    let is_created = |schema: &Option<String>, table: &str| {
+        created_tables
+            .iter()
+            .any(|t| &t.schema == schema && t.name == table)
+    };
+
+    for view in current.views.iter().filter(|v| !desired.views.contains(v)) {
+        let kind = if view.materialized {
+            "MATERIALIZED VIEW"
+        } else {
+            "VIEW"
+        };
+        plan.push(format!(
+            "DROP {} {};",
+            kind,
+            qualified(&view.schema, &view.name, dialect)
+        ));
+    }
+
+    // Foreign keys go first as they depend on the keys and tables of other tables
+    let mut dropped_constraints = current
+        .constraints
+        .iter()
+        .filter(|c| !desired.constraints.contains(c))
+        .collect::<Vec<&Constraint>>();
+    dropped_constraints.sort_by_key(|c| c.kind != ConstraintKind::ForeignKey);
+    for constraint in dropped_constraints {
+        match (dialect, is_dropped(&constraint.schema, &constraint.table)) {
+            (Dialect::SQLite, true) => {}
+            (Dialect::SQLite, false) => plan.manual(format!(
+                "SQLite can't drop {} from {}",
+                constraint.definition, constraint.table
+            )),
+            (_, true) if constraint.kind != ConstraintKind::ForeignKey => {}
+            _ => plan.push(drop_constraint(constraint, dialect)),
+        }
+    }
+
+    for index in current
+        .indexes
+        .iter()
+        .filter(|i| !desired.indexes.contains(i))
+    {
+        if is_dropped(&index.schema, &index.table) {
+            continue;
+        }
+        plan.push(match dialect {
+            Dialect::MySQL => format!(
+                "DROP INDEX {} ON {};",
+                quote(&index.name, dialect),
+                quote(&index.table, dialect)
+            ),
+            _ => format!(
+                "DROP INDEX {};",
+                qualified(&index.schema, &index.name, dialect)
+            ),
+        });
+    }
+
+    for table in &dropped_tables {
+        let name = qualified(&table.schema, &table.name, dialect);
+        plan.destructive(
+            format!("drops the table {} and its data", table.name),
+            format!("DROP TABLE {};", name),
+        );
+    }
+
+    let function_key = |f: &crate::schema::Function| {
+        (
+            f.schema.clone(),
+            f.name.clone(),
+            f.kind.clone(),
+            f.arguments.clone(),
+        )
+    };
+    // Postgres replaces a changed function in place, MySQL has to create it again
+    let desired_functions = desired
+        .functions
+        .iter()
+        .filter(|f| dialect != Dialect::Postgres || !current.functions.contains(f))
+        .map(function_key)
+        .collect::<HashSet<_>>();
+    for function in current
+        .functions
+        .iter()
+        .filter(|f| !desired.functions.contains(f))
+        .filter(|f| dialect != Dialect::Postgres || !desired_functions.contains(&function_key(f)))
+    {
+        let arguments = match dialect {
+            Dialect::Postgres => format!("({})", function.arguments),
+            _ => String::new(),
+        };
+        plan.push(format!(
+            "DROP {} {}{};",
+            function.kind,
+            qualified(&function.schema, &function.name, dialect),
+            arguments
+        ));
+    }
+
+    let sequence_in = |schema: &Schema, s: &Sequence| {
+        schema
+            .sequences
+            .iter()
+            .find(|other| other.schema == s.schema && other.name == s.name)
+            .cloned()
+    };
+    for sequence in current
+        .sequences
+        .iter()
+        .filter(|s| sequence_in(desired, s).is_none())
+    {
+        // Sequences owned by a dropped column are already gone
+        plan.push(format!(
+            "DROP SEQUENCE IF EXISTS {};",
+            qualified(&sequence.schema, &sequence.name, dialect)
+        ));
+    }
+    for sequence in &desired.sequences {
+        match sequence_in(current, sequence) {
+            None => plan.push(format!(
+                "CREATE SEQUENCE {}{};",
+                qualified(&sequence.schema, &sequence.name, dialect),
+                sequence_options(sequence, dialect)
+            )),
+            Some(existing) if existing != *sequence => plan.push(format!(
+                "ALTER SEQUENCE {}{};",
+                qualified(&sequence.schema, &sequence.name, dialect),
+                sequence_options(sequence, dialect)
+            )),
+            Some(_) => {}
+        }
+    }
+
+    for function in desired
+        .functions
+        .iter()
+        .filter(|f| !current.functions.contains(f))
+    {
+        plan.push(function.definition.clone());
+    }
+
+    for table in &created_tables {
+        plan.push(create_table(table, &desired.constraints, dialect));
+    }
+
+    for table in &desired.tables {
+        if let Some(existing) = current
+            .tables
+            .iter()
+            .find(|t| t.schema == table.schema && t.name == table.name)
+        {
+            alter_columns(&mut plan, existing, table, dialect);
+        }
+    }
+
+    if dialect == Dialect::Postgres {
+        for sequence in desired
+            .sequences
+            .iter()
+            .filter(|s| sequence_in(current, s).is_none())
+        {
+            let Some(owned_by) = &sequence.owned_by else {
+                continue;
+            };
+            let column = owned_by
+                .split('.')
+                .map(|part| quote(part, dialect))
+                .collect::<Vec<String>>()
+                .join(".");
+            plan.push(format!(
+                "ALTER SEQUENCE {} OWNED BY {};",
+                qualified(&sequence.schema, &sequence.name, dialect),
+                column
+            ));
+        }
+    }
+
+    let mut added_constraints = desired
+        .constraints
+        .iter()
+        .filter(|c| !current.constraints.contains(c))
+        .filter(|c| !is_inline(c, is_created(&c.schema, &c.table), dialect))
+        .collect::<Vec<&Constraint>>();
+    added_constraints.sort_by_key(|c| c.kind == ConstraintKind::ForeignKey);
+    for constraint in added_constraints {
+        if dialect == Dialect::SQLite {
+            plan.manual(format!(
+                "SQLite can't add {} to {}",
+                constraint.definition, constraint.table
+            ));
+            continue;
+        }
+        plan.push(format!(
+            "ALTER TABLE {} ADD {};",
+            qualified(&constraint.schema, &constraint.table, dialect),
+            constraint_clause(constraint, dialect)
+        ));
+    }
+
+    for index in desired
+        .indexes
+        .iter()
+        .filter(|i| !current.indexes.contains(i))
+    {
+        plan.push(index.definition.clone());
+    }
+
+    let created_views = desired
+        .views
+        .iter()
+        .filter(|v| !current.views.contains(v))
+        .collect::<Vec<&View>>();
+    for view in sort_views(created_views) {
+        plan.push(view.definition.clone());
+    }
+
+    plan
+}
+
+fn quote(name: &str, dialect: Dialect) -> String {
+    let quote = match dialect {
+        Dialect::MySQL => "`",
+        _ => "\"",
+    };
+
+    format!(
+        "{}{}{}",
+        quote,
+        name.replace(quote, &quote.repeat(2)),
+        quote
+    )
+}
+
+fn qualified(schema: &Option<String>, name: &str, dialect: Dialect) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", quote(schema, dialect), quote(name, dialect)),
+        None => quote(name, dialect),
+    }
+}
+
+fn sequence_options(sequence: &Sequence, dialect: Dialect) -> String {
+    let data_type = match dialect {
+        Dialect::Postgres => format!(" AS {}", sequence.data_type),
+        _ => String::new(),
+    };
+
+    format!(
+        "{} START WITH {} INCREMENT BY {} MINVALUE {} MAXVALUE {} {}",
+        data_type,
+        sequence.start,
+        sequence.increment,
+        sequence.min,
+        sequence.max,
+        if sequence.cycle { "CYCLE" } else { "NO CYCLE" }
+    )
+}
+
+fn constraint_clause(constraint: &Constraint, dialect: Dialect) -> String {
+    match &constraint.name {
+        Some(name) => format!(
+            "CONSTRAINT {} {}",
+            quote(name, dialect),
+            constraint.definition
+        ),
+        None => constraint.definition.clone(),
+    }
+}
+
+fn drop_constraint(constraint: &Constraint, dialect: Dialect) -> String {
+    let table = qualified(&constraint.schema, &constraint.table, dialect);
+    let name = quote(constraint.name.as_deref().unwrap_or_default(), dialect);
+
+    match (dialect, constraint.kind) {
+        (Dialect::MySQL, ConstraintKind::PrimaryKey) => {
+            format!("ALTER TABLE {} DROP PRIMARY KEY;", table)
+        }
+        (Dialect::MySQL, ConstraintKind::ForeignKey) => {
+            format!("ALTER TABLE {} DROP FOREIGN KEY {};", table, name)
+        }
+        (Dialect::MySQL, ConstraintKind::Unique) => {
+            format!("ALTER TABLE {} DROP INDEX {};", table, name)
+        }
+        _ => format!("ALTER TABLE {} DROP CONSTRAINT {};", table, name),
+    }
+}
+
+// Constraints of a new table are part of CREATE TABLE, foreign keys only in SQLite which can't add
+// them later. Elsewhere they're added once every table exists.
+fn is_inline(constraint: &Constraint, table_created: bool, dialect: Dialect) -> bool {
+    table_created && (dialect == Dialect::SQLite || constraint.kind != ConstraintKind::ForeignKey)
+}
+
+fn column_definition(column: &Column, dialect: Dialect) -> String {
+    let mut sql = format!("{} {}", quote(&column.name, dialect), column.data_type);
+
+    if let Some(collation) = &column.collation {
+        sql.push_str(&format!(" COLLATE {}", collation));
+    }
+
+    match (&column.generated, column.identity, dialect) {
+        (Some(expression), _, Dialect::Postgres) => {
+            sql.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", expression))
+        }
+        (Some(expression), _, _) => sql.push_str(&format!(" GENERATED ALWAYS AS ({})", expression)),
+        (None, Some(Identity::Always), _) => sql.push_str(" GENERATED ALWAYS AS IDENTITY"),
+        (None, Some(Identity::ByDefault), _) => sql.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+        (None, Some(Identity::AutoIncrement), Dialect::SQLite) => {
+            sql.push_str(" PRIMARY KEY AUTOINCREMENT")
+        }
+        (None, Some(Identity::AutoIncrement), _) => sql.push_str(" AUTO_INCREMENT"),
+        (None, None, _) => {}
+    }
+
+    if let (None, Some(default)) = (&column.generated, &column.default) {
+        sql.push_str(&format!(" DEFAULT {}", default));
+    }
+
+    if !column.nullable {
+        sql.push_str(" NOT NULL");
+    }
+
+    sql
+}
+
+fn create_table(table: &Table, constraints: &[Constraint], dialect: Dialect) -> String {
+    let autoincrement = dialect == Dialect::SQLite
+        && table
+            .columns
+            .iter()
+            .any(|c| c.identity == Some(Identity::AutoIncrement));
+
+    let mut definitions = table
+        .columns
+        .iter()
+        .map(|c| column_definition(c, dialect))
+        .collect::<Vec<String>>();
+    definitions.extend(
+        constraints
+            .iter()
+            .filter(|c| c.schema == table.schema && c.table == table.name)
+            .filter(|c| is_inline(c, true, dialect))
+            // AUTOINCREMENT already made the column the primary key
+            .filter(|c| !(autoincrement && c.kind == ConstraintKind::PrimaryKey))
+            .map(|c| constraint_clause(c, dialect)),
+    );
+
+    format!(
+        "CREATE TABLE {} (\n    {}\n);",
+        qualified(&table.schema, &table.name, dialect),
+        definitions.join(",\n    ")
+    )
+}
+
+fn alter_columns(plan: &mut Plan, current: &Table, desired: &Table, dialect: Dialect) {
+    let table = qualified(&desired.schema, &desired.name, dialect);
+
+    for column in &current.columns {
+        if !desired.columns.iter().any(|c| c.name == column.name) {
+            plan.destructive(
+                format!(
+                    "drops the column {}.{} and its data",
+                    desired.name, column.name
+                ),
+                format!(
+                    "ALTER TABLE {} DROP COLUMN {};",
+                    table,
+                    quote(&column.name, dialect)
+                ),
+            );
+        }
+    }
+
+    for column in &desired.columns {
+        let Some(existing) = current.columns.iter().find(|c| c.name == column.name) else {
+            plan.push(format!(
+                "ALTER TABLE {} ADD COLUMN {};",
+                table,
+                column_definition(column, dialect)
+            ));
+            continue;
+        };
+        if existing == column {
+            continue;
+        }
+
+        let name = format!("{}.{}", desired.name, column.name);
+        let type_changed = existing.data_type != column.data_type;
+        match dialect {
+            Dialect::SQLite => plan.manual(format!(
+                "SQLite can't alter the column {}, the table has to be rebuilt",
+                name
+            )),
+            Dialect::MySQL => {
+                let statement = format!(
+                    "ALTER TABLE {} MODIFY COLUMN {};",
+                    table,
+                    column_definition(column, dialect)
+                );
+                if type_changed {
+                    plan.destructive(
+                        format!(
+                            "changes the type of {} from {} to {}",
+                            name, existing.data_type, column.data_type
+                        ),
+                        statement,
+                    );
+                } else {
+                    plan.push(statement);
+                }
+            }
+            Dialect::Postgres => alter_postgres_column(plan, &table, &name, existing, column),
+        }
+    }
+}
+
+fn alter_postgres_column(
+    plan: &mut Plan,
+    table: &str,
+    name: &str,
+    existing: &Column,
+    column: &Column,
+) {
+    let alter = format!(
+        "ALTER TABLE {} ALTER COLUMN {}",
+        table,
+        quote(&column.name, Dialect::Postgres)
+    );
+
+    if existing.identity != column.identity
+        || existing.generated != column.generated
+        || existing.collation != column.collation
+    {
+        plan.manual(format!(
+            "the identity, generated expression or collation of {} changed",
+            name
+        ));
+        return;
+    }
+
+    if existing.data_type != column.data_type {
+        plan.destructive(
+            format!(
+                "changes the type of {} from {} to {}",
+                name, existing.data_type, column.data_type
+            ),
+            format!("{} TYPE {};", alter, column.data_type),
+        );
+    }
+    if existing.default != column.default {
+        plan.push(match &column.default {
+            Some(default) => format!("{} SET DEFAULT {};", alter, default),
+            None => format!("{} DROP DEFAULT;", alter),
+        });
+    }
+    if existing.nullable != column.nullable {
+        plan.push(if column.nullable {
+            format!("{} DROP NOT NULL;", alter)
+        } else {
+            format!("{} SET NOT NULL;", alter)
+        });
+    }
+}
+
+// Views in name order, each one after the views it selects from
+fn sort_views(views: Vec<&View>) -> Vec<&View> {
+    fn visit<'a>(
+        index: usize,
+        views: &[&'a View],
+        done: &mut HashSet<usize>,
+        sorted: &mut Vec<&'a View>,
+    ) {
+        if !done.insert(index) {
+            return;
+        }
+
+        let body = views[index]
+            .definition
+            .split_once(" AS")
+            .map_or("", |(_, body)| body);
+        for (dependency, other) in views.iter().enumerate() {
+            if dependency != index && body.contains(&other.name) {
+                visit(dependency, views, done, sorted);
+            }
+        }
+
+        sorted.push(views[index]);
+    }
+
+    let mut views = views;
+    views.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut done = HashSet::new();
+    let mut sorted = vec![];
+    for index in 0..views.len() {
+        visit(index, &views, &mut done, &mut sorted);
+    }
+
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{Function, Index, Reference};
+
+    fn column(name: &str, data_type: &str, nullable: bool) -> Column {
+        Column {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable,
+            default: None,
+            identity: None,
+            generated: None,
+            collation: None,
+        }
+    }
+
+    fn table(schema: Option<&str>, name: &str, columns: Vec<Column>) -> Table {
+        Table {
+            schema: schema.map(str::to_string),
+            name: name.to_string(),
+            columns,
+        }
+    }
+
+    fn constraint(
+        schema: Option<&str>,
+        table: &str,
+        name: Option<&str>,
+        kind: ConstraintKind,
+        definition: &str,
+    ) -> Constraint {
+        Constraint {
+            schema: schema.map(str::to_string),
+            table: table.to_string(),
+            name: name.map(str::to_string),
+            kind,
+            columns: vec![],
+            references: None,
+            definition: definition.to_string(),
+        }
+    }
+
+    fn users(schema: Option<&str>) -> Schema {
+        let mut id = column("id", "integer", false);
+        id.identity = Some(Identity::ByDefault);
+
+        Schema {
+            tables: vec![table(
+                schema,
+                "users",
+                vec![id, column("name", "text", true)],
+            )],
+            constraints: vec![constraint(
+                schema,
+                "users",
+                Some("users_pkey"),
+                ConstraintKind::PrimaryKey,
+                "PRIMARY KEY (id)",
+            )],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_same_schema() {
+        let schema = users(Some("public"));
+
+        assert_eq!(plan(&schema, &schema, Dialect::Postgres), Plan::default());
+    }
+
+    #[test]
+    fn test_plan_postgres() {
+        let current = users(Some("public"));
+
+        let mut desired = users(Some("public"));
+        desired.tables[0].columns[1] = column("name", "character varying(100)", false);
+        desired.tables[0]
+            .columns
+            .push(column("email", "text", true));
+        desired.tables.push(table(
+            Some("public"),
+            "posts",
+            vec![
+                column("id", "integer", false),
+                column("user_id", "integer", true),
+            ],
+        ));
+        desired.constraints.push(constraint(
+            Some("public"),
+            "posts",
+            Some("posts_pkey"),
+            ConstraintKind::PrimaryKey,
+            "PRIMARY KEY (id)",
+        ));
+        desired.constraints.push(Constraint {
+            references: Some(Reference {
+                schema: Some("public".to_string()),
+                table: "users".to_string(),
+                columns: vec!["id".to_string()],
+            }),
+            ..constraint(
+                Some("public"),
+                "posts",
+                Some("posts_user_id_fkey"),
+                ConstraintKind::ForeignKey,
+                "FOREIGN KEY (user_id) REFERENCES users(id)",
+            )
+        });
+        desired.indexes.push(Index {
+            schema: Some("public".to_string()),
+            table: "users".to_string(),
+            name: "users_email_idx".to_string(),
+            columns: vec!["email".to_string()],
+            unique: false,
+            definition: "CREATE INDEX users_email_idx ON public.users USING btree (email);"
+                .to_string(),
+        });
+        desired.views.push(View {
+            schema: Some("public".to_string()),
+            name: "names".to_string(),
+            materialized: false,
+            definition: "CREATE VIEW public.names AS\n SELECT name FROM users;".to_string(),
+        });
+
+        let up = plan(&current, &desired, Dialect::Postgres);
+        assert_eq!(
+            up.statements,
+            vec![
+                "CREATE TABLE \"public\".\"posts\" (\n    \"id\" integer NOT NULL,\n    \"user_id\" integer,\n    CONSTRAINT \"posts_pkey\" PRIMARY KEY (id)\n);",
+                "-- DESTRUCTIVE: changes the type of users.name from text to character varying(100)\nALTER TABLE \"public\".\"users\" ALTER COLUMN \"name\" TYPE character varying(100);",
+                "ALTER TABLE \"public\".\"users\" ALTER COLUMN \"name\" SET NOT NULL;",
+                "ALTER TABLE \"public\".\"users\" ADD COLUMN \"email\" text;",
+                "ALTER TABLE \"public\".\"posts\" ADD CONSTRAINT \"posts_user_id_fkey\" FOREIGN KEY (user_id) REFERENCES users(id);",
+                "CREATE INDEX users_email_idx ON public.users USING btree (email);",
+                "CREATE VIEW public.names AS\n SELECT name FROM users;",
+            ]
+        );
+        assert_eq!(up.warnings.len(), 1);
+
+        let down = plan(&desired, &current, Dialect::Postgres);
+        assert_eq!(
+            down.statements,
+            vec![
+                "DROP VIEW \"public\".\"names\";",
+                "ALTER TABLE \"public\".\"posts\" DROP CONSTRAINT \"posts_user_id_fkey\";",
+                "DROP INDEX \"public\".\"users_email_idx\";",
+                "-- DESTRUCTIVE: drops the table posts and its data\nDROP TABLE \"public\".\"posts\";",
+                "-- DESTRUCTIVE: drops the column users.email and its data\nALTER TABLE \"public\".\"users\" DROP COLUMN \"email\";",
+                "-- DESTRUCTIVE: changes the type of users.name from character varying(100) to text\nALTER TABLE \"public\".\"users\" ALTER COLUMN \"name\" TYPE text;",
+                "ALTER TABLE \"public\".\"users\" ALTER COLUMN \"name\" DROP NOT NULL;",
+            ]
+        );
+        assert_eq!(down.warnings.len(), 3);
+    }
+
+    #[test]
+    fn test_plan_postgres_functions_and_sequences() {
+        let function = |body: &str| Function {
+            schema: Some("public".to_string()),
+            name: "f".to_string(),
+            kind: "FUNCTION".to_string(),
+            arguments: "a integer".to_string(),
+            definition: format!("CREATE OR REPLACE FUNCTION public.f(a integer) {};", body),
+        };
+        let current = Schema {
+            functions: vec![function("RETURNS integer AS 'SELECT 1'")],
+            ..Default::default()
+        };
+        let desired = Schema {
+            functions: vec![function("RETURNS integer AS 'SELECT 2'")],
+            sequences: vec![Sequence {
+                schema: Some("public".to_string()),
+                name: "users_id_seq".to_string(),
+                data_type: "integer".to_string(),
+                start: 1,
+                increment: 1,
+                min: 1,
+                max: 2147483647,
+                cycle: false,
+                owned_by: Some("public.users.id".to_string()),
+            }],
+            ..Default::default()
+        };
+
+        // A changed function is replaced in place
+        assert_eq!(
+            plan(&current, &desired, Dialect::Postgres).statements,
+            vec![
+                "CREATE SEQUENCE \"public\".\"users_id_seq\" AS integer START WITH 1 INCREMENT BY 1 MINVALUE 1 MAXVALUE 2147483647 NO CYCLE;",
+                "CREATE OR REPLACE FUNCTION public.f(a integer) RETURNS integer AS 'SELECT 2';",
+                "ALTER SEQUENCE \"public\".\"users_id_seq\" OWNED BY \"public\".\"users\".\"id\";",
+            ]
+        );
+        assert_eq!(
+            plan(&desired, &Schema::default(), Dialect::Postgres).statements,
+            vec![
+                "DROP FUNCTION \"public\".\"f\"(a integer);",
+                "DROP SEQUENCE IF EXISTS \"public\".\"users_id_seq\";",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_mysql() {
+        let mut current = users(None);
+        current.tables[0].columns[0].identity = Some(Identity::AutoIncrement);
+        current.constraints.push(constraint(
+            None,
+            "users",
+            Some("name"),
+            ConstraintKind::Unique,
+            "UNIQUE (`name`)",
+        ));
+
+        let mut desired = current.clone();
+        desired.tables[0].columns[1] = column("name", "varchar(100)", false);
+        desired.constraints.pop();
+
+        assert_eq!(
+            plan(&current, &desired, Dialect::MySQL).statements,
+            vec![
+                "ALTER TABLE `users` DROP INDEX `name`;",
+                "-- DESTRUCTIVE: changes the type of users.name from text to varchar(100)\nALTER TABLE `users` MODIFY COLUMN `name` varchar(100) NOT NULL;",
+            ]
+        );
+        assert_eq!(
+            plan(&current, &Schema::default(), Dialect::MySQL).statements,
+            vec!["-- DESTRUCTIVE: drops the table users and its data\nDROP TABLE `users`;"]
+        );
+        assert_eq!(
+            plan(&Schema::default(), &current, Dialect::MySQL).statements,
+            vec![
+                "CREATE TABLE `users` (\n    `id` integer AUTO_INCREMENT NOT NULL,\n    `name` text,\n    CONSTRAINT `users_pkey` PRIMARY KEY (id),\n    CONSTRAINT `name` UNIQUE (`name`)\n);"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_sqlite() {
+        let mut current = users(None);
+        current.tables[0].columns[0].identity = Some(Identity::AutoIncrement);
+        current.constraints[0].name = None;
+
+        let mut desired = current.clone();
+        desired.tables[0].columns[1].nullable = false;
+        desired.tables.push(table(
+            None,
+            "posts",
+            vec![column("user_id", "INTEGER", true)],
+        ));
+        desired.constraints.push(constraint(
+            None,
+            "posts",
+            None,
+            ConstraintKind::ForeignKey,
+            "FOREIGN KEY (user_id) REFERENCES users (id)",
+        ));
+        desired.constraints.push(constraint(
+            None,
+            "users",
+            None,
+            ConstraintKind::Check,
+            "CHECK (name <> '')",
+        ));
+
+        let up = plan(&current, &desired, Dialect::SQLite);
+        assert_eq!(
+            up.statements,
+            vec![
+                "CREATE TABLE \"posts\" (\n    \"user_id\" INTEGER,\n    FOREIGN KEY (user_id) REFERENCES users (id)\n);",
+                "-- TODO: SQLite can't alter the column users.name, the table has to be rebuilt, write this change by hand",
+                "-- TODO: SQLite can't add CHECK (name <> '') to users, write this change by hand",
+            ]
+        );
+        assert_eq!(up.warnings.len(), 2);
+
+        assert_eq!(
+            plan(&Schema::default(), &current, Dialect::SQLite).statements,
+            vec!["CREATE TABLE \"users\" (\n    \"id\" integer PRIMARY KEY AUTOINCREMENT NOT NULL,\n    \"name\" text\n);"]
+        );
+    }
+
+    #[test]
+    fn test_sort_views() {
+        let view = |name: &str, definition: &str| View {
+            schema: None,
+            name: name.to_string(),
+            materialized: false,
+            definition: definition.to_string(),
+        };
+        let active = view("active", "CREATE VIEW active AS SELECT * FROM users");
+        let active_names = view(
+            "active_names",
+            "CREATE VIEW active_names AS SELECT name FROM recent",
+        );
+        let recent = view("recent", "CREATE VIEW recent AS SELECT * FROM active");
+
+        let names = sort_views(vec![&recent, &active_names, &active])
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["active", "recent", "active_names"]);
+    }
+
+    #[test]
+    fn test_plan_render() {
+        let plan = Plan {
+            statements: vec!["DROP TABLE \"users\";".to_string()],
+            warnings: vec![],
+        };
+
+        assert_eq!(
+            plan.render("desired.sql"),
+            "-- Generated by geni diff-migration from desired.sql\n\nDROP TABLE \"users\";\n"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::database_drivers::utils::split_table_name;
+use crate::database_drivers::{self, DumpOptions};
+use crate::split_schema;
+use crate::utils::read_file_content;
+use anyhow::{bail, Result};
+use similar::{DiffTag, TextDiff};
+use std::path::Path;
+use std::time::Duration;
+
+// Lines of unchanged schema shown around every change
+const CONTEXT: usize = 3;
+
+// Comparing takes at most this long, larger differences are shown as coarser hunks
+const DIFF_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn drift(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    options: DumpOptions,
+) -> Result<Option<String>> {
+    // Only the statements are compared, so the files of a split dump are compared as one
+    let (committed, committed_name) = if options.split {
+        let dir = split_schema::schema_dir(&schema_file);
+        let files = split_schema::read(&migration_folder, &schema_file)?;
+        if files.is_empty() {
+            bail!(
+                "Didn't find the schema directory {}",
+                Path::new(&migration_folder).join(&dir).display()
+            );
+        }
+        (
+            files.into_values().collect::<Vec<String>>().join("\n"),
+            dir.display().to_string(),
+        )
+    } else {
+        let schema_path = Path::new(&migration_folder).join(&schema_file);
+        if !schema_path.exists() {
+            bail!("Didn't find the schema file {}", schema_path.display());
+        }
+        (read_file_content(&schema_path), schema_file.clone())
+    };
+    let committed = without_migration_rows(&committed, &migration_table);
+
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file.clone(),
+        wait_timeout,
+        true,
+    )
+    .await?;
+    // Which migrations ran and when differs between environments, only the schema is compared
+    let options = DumpOptions {
+        migrations: false,
+        ..options
+    };
+    let live = database.render_database_schema(&options).await?;
+
+    Ok(schema_diff(&committed, &live, &committed_name, "database"))
+}
+
+fn without_migration_rows(schema: &str, migration_table: &str) -> String {
+    let (_, table) = split_table_name(migration_table);
+    schema
+        .lines()
+        .filter(|line| inserted_table(line) != Some(unquote(table)))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+// The table an INSERT statement writes to, without schema and quotes
+fn inserted_table(line: &str) -> Option<&str> {
+    let target = line.strip_prefix("INSERT INTO ")?.trim_start();
+    let target = target
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()?;
+
+    target.rsplit('.').next().map(unquote)
+}
+
+fn unquote(identifier: &str) -> &str {
+    identifier.trim_matches(|c| c == '"' || c == '`')
+}
+
+// Unified diff of two schema dumps, None when they only differ in ordering, whitespace or comments
+pub fn schema_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    unified_diff(&normalize(old), &normalize(new), old_name, new_name)
+}
+
+// Unified diff of every line, None when the texts are the same
+pub fn text_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let lines = |text: &str| text.lines().map(str::to_string).collect::<Vec<String>>();
+    if old == new {
+        return None;
+    }
+
+    // Texts only differing in line endings still differ
+    Some(
+        unified_diff(&lines(old), &lines(new), old_name, new_name)
+            .unwrap_or_else(|| format!("--- {}\n+++ {}\n", old_name, new_name)),
+    )
+}
+
+// Statements are separated by empty lines in every dump. They're compared with comments left out,
+// whitespace collapsed and sorted, so only the statements themselves are compared.
+fn normalize(schema: &str) -> Vec<String> {
+    let mut statements: Vec<Vec<String>> = vec![vec![]];
+    for line in schema.lines() {
+        let line = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if line.is_empty() {
+            statements.push(vec![]);
+        } else if !line.starts_with("--") {
+            statements.last_mut().unwrap().push(line);
+        }
+    }
+    statements.retain(|statement| !statement.is_empty());
+    statements.sort();
+
+    statements.into_iter().flatten().collect()
+}
+
+fn unified_diff(old: &[String], new: &[String], old_name: &str, new_name: &str) -> Option<String> {
+    let old = old.iter().map(String::as_str).collect::<Vec<&str>>();
+    let new = new.iter().map(String::as_str).collect::<Vec<&str>>();
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_slices(&old, &new);
+    if diff.ops().iter().all(|op| op.tag() == DiffTag::Equal) {
+        return None;
+    }
+
+    Some(
+        diff.unified_diff()
+            .context_radius(CONTEXT)
+            .header(old_name, new_name)
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_diff_ignores_order_whitespace_and_comments() {
+        let committed = "--\n-- dump\n--\n\n-- TABLES \n\nCREATE TABLE b (\n  id int\n);\n\nCREATE TABLE a (id int);\n\n";
+        let live =
+            "-- TABLES\n\nCREATE TABLE a  (id int);\n\n\n\nCREATE TABLE b (\n    id   int\n);\n";
+
+        assert_eq!(schema_diff(committed, live, "schema.sql", "database"), None);
+    }
+
+    #[test]
+    fn test_schema_diff_unified_output() {
+        let committed = "CREATE TABLE a (id int);\n\nCREATE TABLE b (\n  id int,\n  name text\n);\n\nCREATE TABLE c (id int);\n";
+        let live = "CREATE TABLE a (id int);\n\nCREATE TABLE b (\n  id int,\n  email text\n);\n\nCREATE TABLE c (id int);\n\nCREATE TABLE d (id int);\n";
+
+        assert_eq!(
+            schema_diff(committed, live, "schema.sql", "database").unwrap(),
+            [
+                "--- schema.sql",
+                "+++ database",
+                "@@ -1,6 +1,7 @@",
+                " CREATE TABLE a (id int);",
+                " CREATE TABLE b (",
+                " id int,",
+                "-name text",
+                "+email text",
+                " );",
+                " CREATE TABLE c (id int);",
+                "+CREATE TABLE d (id int);",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_text_diff_keeps_order_and_whitespace() {
+        let old = "CREATE TABLE a (id int);\n\nCREATE TABLE b (id int);\n";
+
+        assert_eq!(text_diff(old, old, "a", "b"), None);
+        assert_eq!(
+            text_diff(old, "CREATE TABLE b (id int);\n\nCREATE TABLE a (id int);\n", "a", "b").unwrap(),
+            "--- a\n+++ b\n@@ -1,3 +1,3 @@\n+CREATE TABLE b (id int);\n+\n CREATE TABLE a (id int);\n-\n-CREATE TABLE b (id int);\n"
+        );
+        assert_eq!(
+            text_diff(old, &old.replace('\n', "\r\n"), "a", "b").unwrap(),
+            "--- a\n+++ b\n"
+        );
+    }
+
+    #[test]
+    fn test_without_migration_rows() {
+        let schema = "CREATE TABLE users (id int);\n\n-- MIGRATIONS \n\nINSERT INTO \"schema_migrations\" (id) VALUES ('1');\nINSERT INTO users (id) VALUES (1);\n";
+
+        assert_eq!(
+            without_migration_rows(schema, "public.schema_migrations"),
+            "CREATE TABLE users (id int);\n\n-- MIGRATIONS \n\nINSERT INTO users (id) VALUES (1);"
+        );
+    }
+
+    #[test]
+    fn test_without_migration_rows_only_drops_the_migrations_table() {
+        let schema = [
+            "INSERT INTO `geni`.`schema_migrations` (id) VALUES ('1');",
+            "INSERT INTO schema_migrations(id) VALUES ('2');",
+            "INSERT INTO \"schema_migrations_archive\" (id) VALUES ('1');",
+            "INSERT INTO notes (body) VALUES ('see schema_migrations');",
+        ]
+        .join("\n");
+
+        assert_eq!(
+            without_migration_rows(&schema, "schema_migrations"),
+            "INSERT INTO \"schema_migrations_archive\" (id) VALUES ('1');\nINSERT INTO notes (body) VALUES ('see schema_migrations');"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_separate_hunks() {
+        let old = (1..=20).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut new = old.clone();
+        new[1] = "two".to_string();
+        new.remove(17);
+
+        assert_eq!(
+            unified_diff(&old, &new, "a", "b").unwrap(),
+            [
+                "--- a",
+                "+++ b",
+                "@@ -1,5 +1,5 @@",
+                " 1",
+                "-2",
+                "+two",
+                " 3",
+                " 4",
+                " 5",
+                "@@ -15,6 +15,5 @@",
+                " 15",
+                " 16",
+                " 17",
+                "-18",
+                " 19",
+                " 20",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -1,5 +1,13 @@
-use crate::database_drivers;
+use crate::database_drivers::DumpOptions;
+use crate::drift::text_diff;
+use crate::schema::Schema;
+use crate::{database_drivers, split_schema, utils};
 use anyhow::Result;
+use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{field, info_span, Instrument};
 
 pub async fn dump(
     database_url: String,
@@ -8,19 +16,118 @@ pub async fn dump(
     migrations_folder: String,
     schema_file: String,
     wait_timeout: Option<usize>,
+    options: DumpOptions,
 ) -> Result<()> {
+    let span = info_span!(
+        "geni.dump",
+        db.system = utils::database_kind(&database_url),
+        outcome = field::Empty,
+    );
+
+    let result = async {
+        let mut database = database_drivers::new(
+            database_url,
+            database_token,
+            migrations_table,
+            migrations_folder,
+            schema_file.clone(),
+            wait_timeout,
+            true,
+        )
+        .await?;
+
+        database.dump_database_schema(&options).await
+    }
+    .instrument(span.clone())
+    .await;
+    span.record("outcome", utils::outcome(&result));
+    result?;
+
+    info!(
+        schema_file = schema_file.as_str(),
+        outcome = "success";
+        "Dumped database schema to {}",
+        schema_file
+    );
+
+    Ok(())
+}
+
+// Renders the dump without writing it and returns the diff to the schema file when it's outdated
+pub async fn check(
+    database_url: String,
+    database_token: Option<String>,
+    migrations_table: String,
+    migrations_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    options: DumpOptions,
+) -> Result<Option<String>> {
+    let schema_path = Path::new(&migrations_folder).join(&schema_file);
+    let committed = fs::read_to_string(&schema_path).unwrap_or_default();
+    let committed_files = if options.split {
+        split_schema::read(&migrations_folder, &schema_file)?
+    } else {
+        BTreeMap::new()
+    };
+
     let mut database = database_drivers::new(
         database_url,
         database_token,
         migrations_table,
         migrations_folder,
-        schema_file,
+        schema_file.clone(),
         wait_timeout,
         true,
     )
     .await?;
+    let schema = database.render_database_schema(&options).await?;
 
-    database.dump_database_schema().await?;
+    if !options.split {
+        return Ok(text_diff(&committed, &schema, &schema_file, "database"));
+    }
 
-    Ok(())
+    // Every file that changed, is missing or belongs to an object that's gone
+    let files = split_schema::split(&schema);
+    let dir = split_schema::schema_dir(&schema_file);
+    let paths = committed_files
+        .keys()
+        .chain(files.keys())
+        .collect::<BTreeSet<&PathBuf>>();
+    let diffs = paths
+        .into_iter()
+        .filter_map(|path| {
+            let name = dir.join(path).display().to_string();
+            text_diff(
+                committed_files.get(path).map_or("", String::as_str),
+                files.get(path).map_or("", String::as_str),
+                &name,
+                "database",
+            )
+        })
+        .collect::<Vec<String>>();
+
+    Ok((!diffs.is_empty()).then(|| diffs.concat()))
+}
+
+pub async fn inspect(
+    database_url: String,
+    database_token: Option<String>,
+    migrations_table: String,
+    migrations_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+) -> Result<Schema> {
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migrations_table,
+        migrations_folder,
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    database.introspect_database_schema().await
 }
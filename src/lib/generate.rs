@@ -1,39 +1,167 @@
-use anyhow::Result;
-use chrono::Utc;
+use crate::config::VersionScheme;
+use crate::utils::get_local_migrations;
+use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
 use log::info;
-use std::fs::{self, File};
-use std::io::Write;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// Versions taken by other processes between reading the folder and creating the files are skipped
+const MAX_ATTEMPTS: usize = 100;
+
+const DATETIME_FORMAT: &str = "%Y%m%d%H%M%S";
 
 pub fn generate_new_migration(migration_folder: &String, migration_name: &str) -> Result<()> {
-    let timestamp = Utc::now().timestamp();
+    generate_new_migration_with_version_scheme(
+        migration_folder,
+        migration_name,
+        &VersionScheme::Timestamp,
+    )
+}
+
+pub fn generate_new_migration_with_version_scheme(
+    migration_folder: &String,
+    migration_name: &str,
+    version_scheme: &VersionScheme,
+) -> Result<()> {
+    generate_migration(
+        migration_folder,
+        migration_name,
+        version_scheme,
+        "-- Write your up sql migration here",
+        "-- Write your down sql migration here",
+    )
+}
+
+// Writes a new pair of migration files with the given contents
+pub fn generate_migration(
+    migration_folder: &String,
+    migration_name: &str,
+    version_scheme: &VersionScheme,
+    up: &str,
+    down: &str,
+) -> Result<()> {
     let name = migration_name.replace(' ', "_").to_lowercase();
 
-    let file_endings = vec!["up", "down"];
+    // Generate the folder if it don't exist
+    fs::create_dir_all(migration_folder)?;
+
+    let folder = PathBuf::from(migration_folder);
+    let mut existing = local_versions(&folder)?;
 
-    for f in file_endings {
-        let filename = format!("{migration_folder}/{timestamp}_{name}.{f}.sql");
-        let filename_str = filename.as_str();
-        let path = std::path::Path::new(filename_str);
+    for _ in 0..MAX_ATTEMPTS {
+        let version = next_version(version_scheme, &existing, Utc::now());
+        let version_str = format_version(version_scheme, version);
 
-        // Generate the folder if it don't exist
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+        if create_migration_files(migration_folder, &version_str, &name, up, down)? {
+            return Ok(());
         }
 
-        let mut file = File::create(path)?;
+        existing.push(version);
+    }
 
-        file.write_all(format!("-- Write your {f} sql migration here").as_bytes())?;
+    bail!(
+        "Couldn't find a free migration version at {} after {} attempts",
+        migration_folder,
+        MAX_ATTEMPTS
+    )
+}
 
-        info!("Generated {}", filename_str)
+// Returns false without touching existing files when the version is already taken
+fn create_migration_files(
+    migration_folder: &str,
+    version: &str,
+    name: &str,
+    up: &str,
+    down: &str,
+) -> Result<bool> {
+    let mut created: Vec<String> = vec![];
+
+    for (f, contents) in [("up", up), ("down", down)] {
+        let filename = format!("{migration_folder}/{version}_{name}.{f}.sql");
+        let path = Path::new(filename.as_str());
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                for c in created {
+                    fs::remove_file(c)?;
+                }
+                return Ok(false);
+            }
+            Err(err) => bail!(err),
+        };
+
+        file.write_all(contents.as_bytes())?;
+        created.push(filename);
+    }
+
+    for c in created {
+        info!("Generated {}", c)
     }
 
-    Ok(())
+    Ok(true)
+}
+
+fn local_versions(folder: &PathBuf) -> Result<Vec<i64>> {
+    let mut versions = vec![];
+
+    for ending in ["up", "down"] {
+        for (version, _) in get_local_migrations(folder, ending)? {
+            versions.push(version);
+        }
+    }
+
+    Ok(versions)
+}
+
+// Versions are compared as numbers, so a new version is always after every existing one
+pub(crate) fn next_version(
+    version_scheme: &VersionScheme,
+    existing: &[i64],
+    now: DateTime<Utc>,
+) -> i64 {
+    let after_existing = existing.iter().max().map(|v| v + 1).unwrap_or(1);
+
+    match version_scheme {
+        VersionScheme::Timestamp => std::cmp::max(now.timestamp(), after_existing),
+        VersionScheme::DateTime => {
+            let format = |datetime: NaiveDateTime| {
+                datetime
+                    .format(DATETIME_FORMAT)
+                    .to_string()
+                    .parse::<i64>()
+                    .unwrap()
+            };
+            let datetime = format(now.naive_utc());
+
+            // One second after the newest version, so the version stays a valid date and time
+            match existing.iter().max() {
+                Some(max) if *max >= datetime => {
+                    NaiveDateTime::parse_from_str(&max.to_string(), DATETIME_FORMAT)
+                        .map(|max| format(max + TimeDelta::seconds(1)))
+                        .unwrap_or(after_existing)
+                }
+                _ => datetime,
+            }
+        }
+        VersionScheme::Sequential => after_existing,
+    }
+}
+
+pub(crate) fn format_version(version_scheme: &VersionScheme, version: i64) -> String {
+    match version_scheme {
+        VersionScheme::Sequential => format!("{:04}", version),
+        _ => version.to_string(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use chrono::TimeZone;
     use std::fs;
     use tempfile::tempdir;
 
@@ -64,4 +192,126 @@ mod tests {
         let down_contents = fs::read_to_string(&down_file).unwrap();
         assert!(down_contents.contains("Write your down sql migration here"));
     }
+
+    #[test]
+    fn test_generate_migration_twice_in_same_second() {
+        let tmp_dir = tempdir().unwrap();
+        let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+
+        for _ in 0..3 {
+            generate_new_migration(&migration_folder_string, "create users").unwrap();
+        }
+
+        let up_files = get_local_migrations(&tmp_dir.path().to_path_buf(), "up").unwrap();
+        let down_files = get_local_migrations(&tmp_dir.path().to_path_buf(), "down").unwrap();
+        assert_eq!(up_files.len(), 3);
+        assert_eq!(down_files.len(), 3);
+        assert!(up_files[0].0 < up_files[1].0 && up_files[1].0 < up_files[2].0);
+    }
+
+    #[test]
+    fn test_generate_migration_does_not_overwrite() {
+        let tmp_dir = tempdir().unwrap();
+        let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+
+        let existing = format!("{migration_folder_string}/0001_create_users.up.sql");
+        fs::write(&existing, "CREATE TABLE users (id INTEGER);").unwrap();
+
+        generate_new_migration_with_version_scheme(
+            &migration_folder_string,
+            "create users",
+            &VersionScheme::Sequential,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&existing).unwrap(),
+            "CREATE TABLE users (id INTEGER);"
+        );
+        assert!(tmp_dir.path().join("0002_create_users.up.sql").exists());
+        assert!(tmp_dir.path().join("0002_create_users.down.sql").exists());
+    }
+
+    #[test]
+    fn test_create_migration_files_existing_version() {
+        let tmp_dir = tempdir().unwrap();
+        let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+
+        let existing = format!("{migration_folder_string}/1234567890_test.down.sql");
+        fs::write(&existing, "DROP TABLE test;").unwrap();
+
+        let created = create_migration_files(
+            &migration_folder_string,
+            "1234567890",
+            "test",
+            "CREATE TABLE test (id INTEGER);",
+            "DROP TABLE test;",
+        );
+        assert!(!created.unwrap());
+
+        // The up file created before the conflict was found is removed again
+        assert!(!tmp_dir.path().join("1234567890_test.up.sql").exists());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "DROP TABLE test;");
+    }
+
+    #[test]
+    fn test_next_version_timestamp() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+
+        assert_eq!(
+            next_version(&VersionScheme::Timestamp, &[], now),
+            now.timestamp()
+        );
+        assert_eq!(
+            next_version(&VersionScheme::Timestamp, &[now.timestamp()], now),
+            now.timestamp() + 1
+        );
+    }
+
+    #[test]
+    fn test_next_version_datetime() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+
+        assert_eq!(
+            next_version(&VersionScheme::DateTime, &[], now),
+            20240102030405
+        );
+        assert_eq!(
+            next_version(&VersionScheme::DateTime, &[20240102030405], now),
+            20240102030406
+        );
+        assert_eq!(
+            next_version(&VersionScheme::DateTime, &[20240102030459], now),
+            20240102030500
+        );
+        assert_eq!(
+            next_version(&VersionScheme::DateTime, &[20241231235959], now),
+            20250101000000
+        );
+    }
+
+    #[test]
+    fn test_next_version_sequential() {
+        let now = Utc::now();
+
+        assert_eq!(next_version(&VersionScheme::Sequential, &[], now), 1);
+        assert_eq!(
+            next_version(&VersionScheme::Sequential, &[9, 10, 2], now),
+            11
+        );
+    }
+
+    #[test]
+    fn test_format_version() {
+        assert_eq!(format_version(&VersionScheme::Sequential, 7), "0007");
+        assert_eq!(format_version(&VersionScheme::Sequential, 12345), "12345");
+        assert_eq!(
+            format_version(&VersionScheme::Timestamp, 1700000000),
+            "1700000000"
+        );
+        assert_eq!(
+            format_version(&VersionScheme::DateTime, 20240102030405),
+            "20240102030405"
+        );
+    }
 }
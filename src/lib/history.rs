@@ -0,0 +1,141 @@
+use crate::database_drivers::{self, SchemaMigration};
+use crate::utils::{get_local_migrations, migration_name};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+pub async fn history(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+) -> Result<Vec<SchemaMigration>> {
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder.clone(),
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    let mut migrations = database.get_schema_migrations().await?.unwrap_or_default();
+
+    // Rows recorded before names were tracked get the name of the local file
+    let files = get_local_migrations(&PathBuf::from(&migration_folder), "up").unwrap_or_default();
+    for m in migrations.iter_mut().filter(|m| m.name.is_none()) {
+        if let Some((_, path)) = files.iter().find(|(id, _)| id.to_string() == m.id) {
+            m.name = migration_name(path);
+        }
+    }
+
+    Ok(filter_history(migrations, since, until, limit))
+}
+
+// Oldest first. Rows without applied_at were recorded by older versions and come first
+fn filter_history(
+    mut migrations: Vec<SchemaMigration>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+) -> Vec<SchemaMigration> {
+    migrations.sort_by_key(|m| (m.applied_at, m.batch, m.id.parse::<i64>().unwrap_or(0)));
+
+    migrations.retain(|m| {
+        let after_since = since.is_none_or(|s| m.applied_at.is_some_and(|a| a >= s));
+        let before_until = until.is_none_or(|u| m.applied_at.is_some_and(|a| a < u));
+
+        after_since && before_until
+    });
+
+    if let Some(limit) = limit {
+        let skip = migrations.len().saturating_sub(limit);
+        migrations.drain(..skip);
+    }
+
+    migrations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn migration(
+        id: &str,
+        applied_at: Option<DateTime<Utc>>,
+        batch: Option<i64>,
+    ) -> SchemaMigration {
+        SchemaMigration {
+            id: id.to_string(),
+            applied_at,
+            batch,
+            ..Default::default()
+        }
+    }
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, d, 12, 0, 0).unwrap()
+    }
+
+    fn ids(migrations: &[SchemaMigration]) -> Vec<&str> {
+        migrations.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_history_chronological() {
+        let migrations = vec![
+            migration("1234567893", Some(day(3)), Some(2)),
+            migration("1234567891", Some(day(1)), Some(1)),
+            migration("1234567890", None, None),
+            migration("1234567892", Some(day(1)), Some(1)),
+        ];
+
+        let result = filter_history(migrations, None, None, None);
+        assert_eq!(
+            ids(&result),
+            vec!["1234567890", "1234567891", "1234567892", "1234567893"]
+        );
+    }
+
+    #[test]
+    fn test_filter_history_date_range() {
+        let migrations = vec![
+            migration("1234567890", None, None),
+            migration("1234567891", Some(day(1)), Some(1)),
+            migration("1234567892", Some(day(2)), Some(2)),
+            migration("1234567893", Some(day(3)), Some(3)),
+        ];
+
+        let result = filter_history(migrations.clone(), Some(day(2)), None, None);
+        assert_eq!(ids(&result), vec!["1234567892", "1234567893"]);
+
+        let result = filter_history(migrations.clone(), None, Some(day(2)), None);
+        assert_eq!(ids(&result), vec!["1234567891"]);
+
+        let result = filter_history(migrations, Some(day(1)), Some(day(3)), None);
+        assert_eq!(ids(&result), vec!["1234567891", "1234567892"]);
+    }
+
+    #[test]
+    fn test_filter_history_limit_keeps_newest() {
+        let migrations = vec![
+            migration("1234567891", Some(day(1)), Some(1)),
+            migration("1234567892", Some(day(2)), Some(2)),
+            migration("1234567893", Some(day(3)), Some(3)),
+        ];
+
+        let result = filter_history(migrations.clone(), None, None, Some(2));
+        assert_eq!(ids(&result), vec!["1234567892", "1234567893"]);
+
+        let result = filter_history(migrations, None, None, Some(10));
+        assert_eq!(result.len(), 3);
+    }
+}
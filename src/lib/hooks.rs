@@ -0,0 +1,277 @@
+use crate::config;
+use crate::observer::{Direction, MigrationObserver, PlannedMigration};
+use crate::utils::outcome;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use log::{error, info};
+use std::time::Duration;
+use tokio::process::Command;
+
+// Shell commands to run around migrations, every hook is optional
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    pub before_up: Option<String>,
+    pub after_up: Option<String>,
+    pub before_each_up: Option<String>,
+    pub after_each_up: Option<String>,
+    pub before_down: Option<String>,
+    pub after_down: Option<String>,
+    pub before_each_down: Option<String>,
+    pub after_each_down: Option<String>,
+}
+
+// Runs the hooks as a MigrationObserver. The before hook runs before the migrations table is
+// prepared, also when there is nothing to migrate. A failing before hook aborts the run, after
+// hooks are only logged when they fail.
+pub struct ShellHooks {
+    hooks: Hooks,
+    database: String,
+    started: bool,
+}
+
+impl ShellHooks {
+    pub fn new(hooks: Hooks, database_url: &str) -> Result<ShellHooks> {
+        let parsed = url::Url::parse(database_url)?;
+        let database = config::Database::new(parsed.scheme())?
+            .as_str()?
+            .to_string();
+
+        Ok(ShellHooks {
+            hooks,
+            database,
+            started: false,
+        })
+    }
+
+    async fn run(
+        &self,
+        hook: &str,
+        command: &Option<String>,
+        direction: Direction,
+        env: &[(&str, String)],
+    ) -> Result<()> {
+        let Some(command) = command else {
+            return Ok(());
+        };
+
+        info!(hook = hook; "Running {} hook", hook);
+        let status = shell(command)
+            .env("GENI_MIGRATION_DIRECTION", direction.as_str())
+            .env("GENI_DATABASE", &self.database)
+            .envs(env.iter().map(|(k, v)| (*k, v.as_str())))
+            .status()
+            .await
+            .with_context(|| format!("Couldn't run {} hook", hook))?;
+
+        if !status.success() {
+            bail!("{} hook failed with {}", hook, status)
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MigrationObserver for ShellHooks {
+    async fn on_plan(
+        &mut self,
+        direction: Direction,
+        migrations: &[PlannedMigration],
+    ) -> Result<()> {
+        let (hook, command) = match direction {
+            Direction::Up => ("before_up", &self.hooks.before_up),
+            Direction::Down => ("before_down", &self.hooks.before_down),
+        };
+        let ids = migrations
+            .iter()
+            .map(|m| m.id.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        self.run(hook, command, direction, &[("GENI_MIGRATION_IDS", ids)])
+            .await?;
+
+        self.started = true;
+        Ok(())
+    }
+
+    async fn before_migration(
+        &mut self,
+        direction: Direction,
+        migration: &PlannedMigration,
+    ) -> Result<()> {
+        let (hook, command) = match direction {
+            Direction::Up => ("before_each_up", &self.hooks.before_each_up),
+            Direction::Down => ("before_each_down", &self.hooks.before_each_down),
+        };
+
+        self.run(hook, command, direction, &migration_env(migration))
+            .await
+    }
+
+    async fn after_migration(
+        &mut self,
+        direction: Direction,
+        migration: &PlannedMigration,
+        _duration: Duration,
+        result: &Result<()>,
+    ) {
+        let (hook, command) = match direction {
+            Direction::Up => ("after_each_up", &self.hooks.after_each_up),
+            Direction::Down => ("after_each_down", &self.hooks.after_each_down),
+        };
+        let mut env = migration_env(migration);
+        env.push(("GENI_MIGRATION_OUTCOME", outcome(result).to_string()));
+
+        if let Err(err) = self.run(hook, command, direction, &env).await {
+            error!("{:?}", err);
+        }
+    }
+
+    async fn on_finish(&mut self, direction: Direction, result: &Result<()>) {
+        if !self.started {
+            return;
+        }
+        self.started = false;
+
+        let (hook, command) = match direction {
+            Direction::Up => ("after_up", &self.hooks.after_up),
+            Direction::Down => ("after_down", &self.hooks.after_down),
+        };
+        let env = [("GENI_MIGRATION_OUTCOME", outcome(result).to_string())];
+
+        if let Err(err) = self.run(hook, command, direction, &env).await {
+            error!("{:?}", err);
+        }
+    }
+}
+
+fn migration_env(migration: &PlannedMigration) -> Vec<(&'static str, String)> {
+    vec![
+        ("GENI_MIGRATION_ID", migration.id.to_string()),
+        (
+            "GENI_MIGRATION_NAME",
+            migration.name.clone().unwrap_or_default(),
+        ),
+    ]
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn migration(id: i64) -> PlannedMigration {
+        PlannedMigration {
+            id,
+            name: Some("create_users".to_string()),
+            path: PathBuf::from(format!("{}_create_users.up.sql", id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks_get_migration_env() {
+        let tmp_dir = tempdir().unwrap();
+        let log = tmp_dir.path().join("hooks.log");
+        let append = |line: &str| Some(format!("echo \"{}\" >> {}", line, log.display()));
+
+        let mut hooks = ShellHooks::new(
+            Hooks {
+                before_up: append("before_up $GENI_MIGRATION_IDS $GENI_DATABASE"),
+                before_each_up: append(
+                    "before_each $GENI_MIGRATION_DIRECTION $GENI_MIGRATION_ID $GENI_MIGRATION_NAME",
+                ),
+                after_each_up: append("after_each $GENI_MIGRATION_ID $GENI_MIGRATION_OUTCOME"),
+                after_up: append("after_up $GENI_MIGRATION_OUTCOME"),
+                ..Default::default()
+            },
+            "postgres://localhost:5432/app",
+        )
+        .unwrap();
+
+        let plan = vec![migration(1), migration(2)];
+        hooks.on_plan(Direction::Up, &plan).await.unwrap();
+        hooks
+            .before_migration(Direction::Up, &plan[0])
+            .await
+            .unwrap();
+        hooks
+            .after_migration(Direction::Up, &plan[0], Duration::ZERO, &Ok(()))
+            .await;
+        hooks
+            .on_finish(Direction::Up, &Err(anyhow::anyhow!("failed")))
+            .await;
+
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "before_up 1,2 postgres\nbefore_each up 1 create_users\nafter_each 1 success\nafter_up failure\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_before_hook_aborts() {
+        let mut hooks = ShellHooks::new(
+            Hooks {
+                before_down: Some("exit 3".to_string()),
+                after_down: Some("exit 1".to_string()),
+                before_each_up: Some("false".to_string()),
+                ..Default::default()
+            },
+            "sqlite://./app.sqlite",
+        )
+        .unwrap();
+
+        let err = hooks
+            .on_plan(Direction::Down, &[migration(1)])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("before_down hook failed"));
+        assert!(!hooks.started);
+
+        assert!(hooks
+            .before_migration(Direction::Up, &migration(1))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_on_empty_plan() {
+        let tmp_dir = tempdir().unwrap();
+        let log = tmp_dir.path().join("hooks.log");
+        let append = |line: &str| Some(format!("echo \"{}\" >> {}", line, log.display()));
+
+        let mut hooks = ShellHooks::new(
+            Hooks {
+                before_up: append("before_up [$GENI_MIGRATION_IDS]"),
+                after_up: append("after_up $GENI_MIGRATION_OUTCOME"),
+                ..Default::default()
+            },
+            "mysql://localhost:3306/app",
+        )
+        .unwrap();
+
+        hooks.on_plan(Direction::Up, &[]).await.unwrap();
+        assert!(hooks.started);
+        hooks.on_finish(Direction::Up, &Ok(())).await;
+
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "before_up []\nafter_up success\n"
+        );
+    }
+}
@@ -1,10 +1,23 @@
+pub mod audit;
+mod baseline;
 pub mod config;
 pub mod database_drivers;
+mod declarative;
+mod drift;
 pub mod dump;
 pub mod generate;
+mod history;
+pub mod hooks;
+mod load;
 mod management;
 pub mod migrate;
-mod status;
+pub mod observer;
+mod renumber;
+mod repair;
+pub mod schema;
+mod split_schema;
+mod squash;
+pub mod status;
 mod utils;
 
 #[cfg(test)]
@@ -18,6 +31,29 @@ pub async fn migrate_database(
     schema_file: String,
     wait_timeout: Option<usize>,
     dump_schema: bool,
+) -> anyhow::Result<()> {
+    migrate_database_with_options(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        database_drivers::DumpOptions::default(),
+    )
+    .await
+}
+
+pub async fn migrate_database_with_options(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: database_drivers::DumpOptions,
 ) -> anyhow::Result<()> {
     migrate::up(
         database_url,
@@ -27,6 +63,7 @@ pub async fn migrate_database(
         schema_file,
         wait_timeout,
         dump_schema,
+        dump_options,
     )
     .await
 }
@@ -40,6 +77,31 @@ pub async fn migate_down(
     wait_timeout: Option<usize>,
     dump_schema: bool,
     rollback_amount: i64,
+) -> anyhow::Result<()> {
+    migrate_down_with_options(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        database_drivers::DumpOptions::default(),
+        rollback_amount,
+    )
+    .await
+}
+
+pub async fn migrate_down_with_options(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: database_drivers::DumpOptions,
+    rollback_amount: i64,
 ) -> anyhow::Result<()> {
     migrate::down(
         database_url,
@@ -49,7 +111,60 @@ pub async fn migate_down(
         schema_file,
         wait_timeout,
         dump_schema,
+        dump_options,
+        &rollback_amount,
+    )
+    .await
+}
+
+pub async fn migrate_database_with_observer(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: database_drivers::DumpOptions,
+    observer: &mut dyn observer::MigrationObserver,
+) -> anyhow::Result<()> {
+    migrate::up_with_observer(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        dump_options,
+        observer,
+    )
+    .await
+}
+
+pub async fn migrate_down_with_observer(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: database_drivers::DumpOptions,
+    rollback_amount: i64,
+    observer: &mut dyn observer::MigrationObserver,
+) -> anyhow::Result<()> {
+    migrate::down_with_observer(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        dump_options,
         &rollback_amount,
+        observer,
     )
     .await
 }
@@ -96,6 +211,14 @@ pub async fn new_migration(migration_path: String, name: &str) -> anyhow::Result
     generate::generate_new_migration(&migration_path, name)
 }
 
+pub async fn new_migration_with_version_scheme(
+    migration_path: String,
+    name: &str,
+    version_scheme: config::VersionScheme,
+) -> anyhow::Result<()> {
+    generate::generate_new_migration_with_version_scheme(&migration_path, name, &version_scheme)
+}
+
 pub async fn status_migrations(
     database_url: String,
     database_token: Option<String>,
@@ -105,7 +228,7 @@ pub async fn status_migrations(
     wait_timeout: Option<usize>,
     verbose: bool,
 ) -> anyhow::Result<()> {
-    status::status(
+    status::log_status(
         database_url,
         database_token,
         migration_table,
@@ -117,6 +240,69 @@ pub async fn status_migrations(
     .await
 }
 
+pub async fn migration_status(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+) -> anyhow::Result<Vec<status::MigrationStatus>> {
+    status::status(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+    )
+    .await
+}
+
+pub async fn applied_migrations(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+) -> anyhow::Result<Option<Vec<database_drivers::SchemaMigration>>> {
+    status::applied_migrations(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+    )
+    .await
+}
+
+pub async fn migration_history(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<database_drivers::SchemaMigration>> {
+    history::history(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        since,
+        until,
+        limit,
+    )
+    .await
+}
+
 pub async fn dump_database(
     database_url: String,
     database_token: Option<String>,
@@ -124,6 +310,27 @@ pub async fn dump_database(
     migration_folder: String,
     schema_file: String,
     wait_timeout: Option<usize>,
+) -> anyhow::Result<()> {
+    dump_database_with_options(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        database_drivers::DumpOptions::default(),
+    )
+    .await
+}
+
+pub async fn dump_database_with_options(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_options: database_drivers::DumpOptions,
 ) -> anyhow::Result<()> {
     dump::dump(
         database_url,
@@ -132,6 +339,241 @@ pub async fn dump_database(
         migration_folder,
         schema_file,
         wait_timeout,
+        dump_options,
+    )
+    .await
+}
+
+// The unified diff between the schema file and the live database, None when they match
+pub async fn schema_drift(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_options: database_drivers::DumpOptions,
+) -> anyhow::Result<Option<String>> {
+    drift::drift(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_options,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn load_database(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    version: Option<i64>,
+    split: bool,
+) -> anyhow::Result<()> {
+    load::load(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        version,
+        split,
+    )
+    .await
+}
+
+// The diff between a fresh dump and the schema file, None when the schema file is up to date
+pub async fn check_dump(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_options: database_drivers::DumpOptions,
+) -> anyhow::Result<Option<String>> {
+    dump::check(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_options,
+    )
+    .await
+}
+
+// The tables, indexes, constraints, views, sequences and functions of the database
+pub async fn inspect_schema(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+) -> anyhow::Result<schema::Schema> {
+    dump::inspect(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+    )
+    .await
+}
+
+// Writes a migration turning the database into the schema of the desired schema file
+#[allow(clippy::too_many_arguments)]
+pub async fn diff_migration(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    desired_file: String,
+    name: &str,
+    version_scheme: config::VersionScheme,
+) -> anyhow::Result<()> {
+    declarative::diff_migration(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        desired_file,
+        name,
+        &version_scheme,
+    )
+    .await
+}
+
+// Asks confirm with the migration files the baseline replaces before anything is written
+#[allow(clippy::too_many_arguments)]
+pub async fn squash_migrations<F>(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    version: i64,
+    confirm: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce(&[std::path::PathBuf]) -> bool,
+{
+    squash::squash(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        version,
+        confirm,
+    )
+    .await
+}
+
+pub async fn baseline_database<F>(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    version: i64,
+    confirm: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce(&[String]) -> bool,
+{
+    baseline::baseline(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        version,
+        confirm,
+    )
+    .await
+}
+
+pub async fn mark_applied(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    id: i64,
+) -> anyhow::Result<()> {
+    repair::mark_applied(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        id,
+    )
+    .await
+}
+
+pub async fn mark_unapplied(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    id: i64,
+) -> anyhow::Result<()> {
+    repair::mark_unapplied(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        id,
+    )
+    .await
+}
+
+pub async fn renumber_migrations(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    names: Vec<String>,
+    version_scheme: config::VersionScheme,
+) -> anyhow::Result<()> {
+    renumber::renumber(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        names,
+        version_scheme,
     )
     .await
 }
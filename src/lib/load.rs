@@ -0,0 +1,144 @@
+use crate::config::Database;
+use crate::database_drivers;
+use crate::split_schema;
+use crate::utils::{applied_migration, get_local_migrations, next_batch, read_file_content};
+use anyhow::{bail, Result};
+use log::info;
+use std::path::{Path, PathBuf};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn load(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    version: Option<i64>,
+    split: bool,
+) -> Result<()> {
+    let (schema, schema_path) = if split {
+        let dir = Path::new(&migration_folder).join(split_schema::schema_dir(&schema_file));
+        let files = split_schema::read(&migration_folder, &schema_file)?;
+        if files.is_empty() {
+            bail!("Didn't find the schema directory {}", dir.display());
+        }
+        (split_schema::join(&files), dir)
+    } else {
+        let schema_path = Path::new(&migration_folder).join(&schema_file);
+        if !schema_path.exists() {
+            bail!("Didn't find the schema file {}", schema_path.display());
+        }
+        (read_file_content(&schema_path), schema_path)
+    };
+
+    let path = PathBuf::from(&migration_folder);
+    let files = match get_local_migrations(&path, "up") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+
+    // LibSQL and Turso databases are created through their own interface
+    let driver = Database::new(url::Url::parse(&database_url)?.scheme())?;
+    if !matches!(driver, Database::LibSQL | Database::Turso) {
+        let mut server = database_drivers::new(
+            database_url.clone(),
+            database_token.clone(),
+            migration_table.clone(),
+            migration_folder.clone(),
+            schema_file.clone(),
+            wait_timeout,
+            false,
+        )
+        .await?;
+
+        // Postgres can't create a database only if it's missing, an existing database is fine here
+        match server.create_database().await {
+            Ok(()) => {}
+            Err(err) if already_exists(&err) => {
+                info!("The database already exists, loading into it")
+            }
+            Err(err) => return Err(err.context("Couldn't create the database")),
+        }
+    }
+
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    if let Some(migrations) = database.get_schema_migrations().await? {
+        if !migrations.is_empty() {
+            bail!(
+                "Aborted load, the migrations table already contains {} migrations",
+                migrations.len()
+            );
+        }
+    }
+
+    database.execute(&schema, true).await?;
+    info!("Loaded {}", schema_path.display());
+
+    // A dump made with DATABASE_DUMP_MIGRATIONS brings its own rows, otherwise the dump is
+    // taken to contain every local migration up to the version
+    if !database.get_or_create_schema_migrations().await?.is_empty() {
+        return Ok(());
+    }
+
+    let batch = next_batch(&mut database).await?;
+    for (id, file) in migrations_in_schema(&files, version) {
+        database
+            .insert_schema_migration(&applied_migration(*id, file, None, batch))
+            .await?;
+        info!("Marked {} as applied", id);
+    }
+
+    Ok(())
+}
+
+// Postgres' duplicate_database, MySQL and MariaDB use CREATE DATABASE IF NOT EXISTS
+fn already_exists(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<sqlx::Error>()
+        .and_then(|err| err.as_database_error())
+        .and_then(|err| err.code())
+        .is_some_and(|code| code == "42P04")
+}
+
+fn migrations_in_schema(
+    files: &[(i64, PathBuf)],
+    version: Option<i64>,
+) -> impl Iterator<Item = &(i64, PathBuf)> {
+    files
+        .iter()
+        .filter(move |(id, _)| version.is_none_or(|version| *id <= version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_in_schema() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+        ];
+
+        let ids = |version| {
+            migrations_in_schema(&files, version)
+                .map(|(id, _)| *id)
+                .collect::<Vec<i64>>()
+        };
+        assert_eq!(ids(None), vec![1234567890, 1234567891]);
+        assert_eq!(ids(Some(1234567890)), vec![1234567890]);
+        assert!(ids(Some(1)).is_empty());
+    }
+}
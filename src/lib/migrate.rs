@@ -1,8 +1,12 @@
-use crate::utils::{get_local_migrations, read_file_content};
-use crate::{database_drivers, utils};
-use anyhow::{bail, Result};
-use log::info;
+use crate::database_drivers::{self, DumpOptions};
+use crate::observer::{Direction, MigrationObserver, NoopObserver, PlannedMigration};
+use crate::utils;
+use crate::utils::{get_local_migrations, migration_name, read_file_content, EachMigrationSql};
+use anyhow::{anyhow, bail, Result};
+use log::{error, info};
 use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{field, info_span, Instrument};
 
 pub async fn up(
     database_url: String,
@@ -12,6 +16,70 @@ pub async fn up(
     schema_file: String,
     wait_timeout: Option<usize>,
     dump_schema: bool,
+    dump_options: DumpOptions,
+) -> Result<()> {
+    up_with_observer(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        dump_options,
+        &mut NoopObserver,
+    )
+    .await
+}
+
+pub async fn up_with_observer(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: DumpOptions,
+    observer: &mut dyn MigrationObserver,
+) -> Result<()> {
+    let database_kind = utils::database_kind(&database_url);
+    let span = info_span!(
+        "geni.migrate",
+        direction = "up",
+        db.system = database_kind.as_str(),
+        outcome = field::Empty,
+    );
+    let result = run_up(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        dump_options,
+        observer,
+        &database_kind,
+    )
+    .instrument(span.clone())
+    .await;
+    span.record("outcome", utils::outcome(&result));
+
+    result
+}
+
+async fn run_up(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: DumpOptions,
+    observer: &mut dyn MigrationObserver,
+    database_kind: &str,
 ) -> Result<()> {
     let path = PathBuf::from(&migration_folder);
     let files = match get_local_migrations(&path, "up") {
@@ -33,42 +101,116 @@ pub async fn up(
         database_token,
         migration_table,
         migration_folder.clone(),
-        schema_file,
+        schema_file.clone(),
         wait_timeout,
         true,
     )
     .await?;
 
-    let migrations: Vec<String> = database
-        .get_or_create_schema_migrations()
-        .await?
-        .into_iter()
-        .map(|s| s.into_boxed_str())
-        .collect::<Vec<Box<str>>>()
+    let each = EachMigrationSql::read(&path)?;
+    let migrations = applied_migration_ids(&mut database).await?;
+
+    let plan = files
         .into_iter()
-        .map(|s| s.into())
-        .collect();
+        .filter(|(id, _)| !migrations.contains(id))
+        .map(|(id, path)| planned_migration(id, path))
+        .collect::<Vec<PlannedMigration>>();
 
-    for f in files {
-        let id = Box::new(f.0.to_string());
+    let result = match observer.on_plan(Direction::Up, &plan).await {
+        Err(err) => Err(anyhow!("Migrations were aborted: {:?}", err)),
+        Ok(_) => apply_up(&mut database, &plan, &each, observer, database_kind).await,
+    };
 
-        if !migrations.contains(&id) {
-            info!("Running migration {}", id);
-            let query = read_file_content(&f.1);
-            let run_in_transaction = utils::should_run_in_transaction(&query);
+    if result.is_ok() && dump_schema {
+        dump(
+            &mut database,
+            &schema_file,
+            &dump_options,
+            observer,
+            database_kind,
+        )
+        .await;
+    }
+
+    observer.on_finish(Direction::Up, &result).await;
 
-            if let Err(e) = database.execute(&query, run_in_transaction).await {
-                bail!(e)
-            }
+    result
+}
 
-            database.insert_schema_migration(&id).await?;
+async fn apply_up(
+    database: &mut Box<dyn database_drivers::DatabaseDriver>,
+    plan: &[PlannedMigration],
+    each: &EachMigrationSql,
+    observer: &mut dyn MigrationObserver,
+    database_kind: &str,
+) -> Result<()> {
+    database.get_or_create_schema_migrations().await?;
+    let batch = utils::next_batch(database).await?;
+
+    for migration in plan {
+        let id = migration.id;
+
+        if let Err(err) = observer.before_migration(Direction::Up, migration).await {
+            bail!("Migration {} was aborted: {:?}", id, err)
         }
-    }
 
-    if dump_schema {
-        if let Err(err) = database.dump_database_schema().await {
-            log::error!("Skipping dumping database schema: {:?}", err);
+        info!(migration_id = id, direction = "up"; "Running migration {}", id);
+        let query = read_file_content(&migration.path);
+        let run_in_transaction = utils::should_run_in_transaction(&query);
+
+        let span = info_span!(
+            "geni.migration",
+            migration.id = id,
+            direction = "up",
+            db.system = database_kind,
+            outcome = field::Empty,
+        );
+        let started = Instant::now();
+        let mut result = database
+            .execute_all(&each.around(&query), run_in_transaction)
+            .instrument(span.clone())
+            .await;
+        let duration = started.elapsed();
+        let duration_ms = duration.as_millis() as i64;
+        if result.is_ok() {
+            result = database
+                .insert_schema_migration(&utils::applied_migration(
+                    id,
+                    &migration.path,
+                    Some(duration_ms),
+                    batch,
+                ))
+                .instrument(span.clone())
+                .await;
         }
+        span.record("outcome", utils::outcome(&result));
+
+        observer
+            .after_migration(Direction::Up, migration, duration, &result)
+            .await;
+
+        if let Err(e) = result {
+            error!(
+                migration_id = id,
+                direction = "up",
+                duration_ms = duration_ms,
+                outcome = "failure";
+                "Migration {} failed after {}ms",
+                id,
+                duration_ms
+            );
+            bail!(e)
+        }
+
+        info!(
+            migration_id = id,
+            direction = "up",
+            duration_ms = duration_ms,
+            outcome = "success";
+            "Applied migration {} in {}ms",
+            id,
+            duration_ms
+        );
     }
 
     Ok(())
@@ -82,7 +224,76 @@ pub async fn down(
     schema_file: String,
     wait_timeout: Option<usize>,
     dump_schema: bool,
+    dump_options: DumpOptions,
+    rollback_amount: &i64,
+) -> Result<()> {
+    down_with_observer(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        dump_options,
+        rollback_amount,
+        &mut NoopObserver,
+    )
+    .await
+}
+
+pub async fn down_with_observer(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: DumpOptions,
     rollback_amount: &i64,
+    observer: &mut dyn MigrationObserver,
+) -> Result<()> {
+    let database_kind = utils::database_kind(&database_url);
+    let span = info_span!(
+        "geni.migrate",
+        direction = "down",
+        db.system = database_kind.as_str(),
+        outcome = field::Empty,
+    );
+    let result = run_down(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        dump_schema,
+        dump_options,
+        rollback_amount,
+        observer,
+        &database_kind,
+    )
+    .instrument(span.clone())
+    .await;
+    span.record("outcome", utils::outcome(&result));
+
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_down(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    dump_schema: bool,
+    dump_options: DumpOptions,
+    rollback_amount: &i64,
+    observer: &mut dyn MigrationObserver,
+    database_kind: &str,
 ) -> Result<()> {
     let path = PathBuf::from(&migration_folder);
     let files = match get_local_migrations(&path, "down") {
@@ -104,50 +315,165 @@ pub async fn down(
         database_token,
         migration_table,
         migration_folder.clone(),
-        schema_file,
+        schema_file.clone(),
         wait_timeout,
         true,
     )
     .await?;
 
-    let migrations = database
-        .get_or_create_schema_migrations()
-        .await?
-        .into_iter()
-        .map(|s| s.into_boxed_str().parse::<i64>().unwrap())
-        .collect::<Vec<i64>>();
-
-    let migrations_to_run = migrations.into_iter().take(*rollback_amount as usize);
+    let each = EachMigrationSql::read(&path)?;
+    let migrations = applied_migration_ids(&mut database).await?;
 
-    for migration in migrations_to_run {
-        let rollback_file = files.iter().find(|(timestamp, _)| timestamp == &migration);
-
-        match rollback_file {
+    // Every rollback file has to exist before anything is rolled back
+    let mut plan = vec![];
+    for migration in migrations.into_iter().take(*rollback_amount as usize) {
+        match files.iter().find(|(timestamp, _)| timestamp == &migration) {
             None => bail!("No rollback file found for {}", migration),
-            Some(f) => {
-                info!("Running rollback for {}", migration);
-                let query = read_file_content(&f.1);
-                let run_in_transaction = utils::should_run_in_transaction(&query);
-
-                if let Err(e) = database.execute(&query, run_in_transaction).await {
-                    bail!(e)
-                }
-
-                database
-                    .remove_schema_migration(migration.to_string().as_str())
-                    .await?;
-            }
+            Some(f) => plan.push(planned_migration(f.0, f.1.clone())),
         }
     }
 
-    if dump_schema {
-        if let Err(err) = database.dump_database_schema().await {
-            log::error!("Skipping dumping database schema: {:?}", err);
+    let result = match observer.on_plan(Direction::Down, &plan).await {
+        Err(err) => Err(anyhow!("Rollback was aborted: {:?}", err)),
+        Ok(_) => apply_down(&mut database, &plan, &each, observer, database_kind).await,
+    };
+
+    if result.is_ok() && dump_schema {
+        dump(
+            &mut database,
+            &schema_file,
+            &dump_options,
+            observer,
+            database_kind,
+        )
+        .await;
+    }
+
+    observer.on_finish(Direction::Down, &result).await;
+
+    result
+}
+
+async fn apply_down(
+    database: &mut Box<dyn database_drivers::DatabaseDriver>,
+    plan: &[PlannedMigration],
+    each: &EachMigrationSql,
+    observer: &mut dyn MigrationObserver,
+    database_kind: &str,
+) -> Result<()> {
+    database.get_or_create_schema_migrations().await?;
+
+    for migration in plan {
+        let id = migration.id;
+
+        if let Err(err) = observer.before_migration(Direction::Down, migration).await {
+            bail!("Rollback of {} was aborted: {:?}", id, err)
+        }
+
+        info!(migration_id = id, direction = "down"; "Running rollback for {}", id);
+        let query = read_file_content(&migration.path);
+        let run_in_transaction = utils::should_run_in_transaction(&query);
+
+        let span = info_span!(
+            "geni.migration",
+            migration.id = id,
+            direction = "down",
+            db.system = database_kind,
+            outcome = field::Empty,
+        );
+        let started = Instant::now();
+        let mut result = database
+            .execute_all(&each.around(&query), run_in_transaction)
+            .instrument(span.clone())
+            .await;
+        let duration = started.elapsed();
+        let duration_ms = duration.as_millis() as i64;
+        if result.is_ok() {
+            result = database
+                .remove_schema_migration(id.to_string().as_str())
+                .instrument(span.clone())
+                .await;
         }
+        span.record("outcome", utils::outcome(&result));
+
+        observer
+            .after_migration(Direction::Down, migration, duration, &result)
+            .await;
+
+        if let Err(e) = result {
+            error!(
+                migration_id = id,
+                direction = "down",
+                duration_ms = duration_ms,
+                outcome = "failure";
+                "Rollback of {} failed after {}ms",
+                id,
+                duration_ms
+            );
+            bail!(e)
+        }
+
+        info!(
+            migration_id = id,
+            direction = "down",
+            duration_ms = duration_ms,
+            outcome = "success";
+            "Rolled back {} in {}ms",
+            id,
+            duration_ms
+        );
     }
 
     Ok(())
 }
+
+// Reads the applied migrations without creating the migrations table, the observer
+// gets the plan before anything is changed in the database
+async fn applied_migration_ids(
+    database: &mut Box<dyn database_drivers::DatabaseDriver>,
+) -> Result<Vec<i64>> {
+    let migrations = database.get_schema_migrations().await?.unwrap_or_default();
+    let ids = migrations
+        .into_iter()
+        .map(|m| m.id)
+        .collect::<Vec<String>>();
+
+    Ok(utils::applied_migration_ids(&ids))
+}
+
+fn planned_migration(id: i64, path: PathBuf) -> PlannedMigration {
+    PlannedMigration {
+        id,
+        name: migration_name(&path),
+        path,
+    }
+}
+
+// A failing dump doesn't fail the migration run
+async fn dump(
+    database: &mut Box<dyn database_drivers::DatabaseDriver>,
+    schema_file: &str,
+    options: &DumpOptions,
+    observer: &mut dyn MigrationObserver,
+    database_kind: &str,
+) {
+    let span = info_span!(
+        "geni.dump",
+        db.system = database_kind,
+        outcome = field::Empty,
+    );
+    let result = database
+        .dump_database_schema(options)
+        .instrument(span.clone())
+        .await;
+    span.record("outcome", utils::outcome(&result));
+    observer.on_dump(schema_file, &result).await;
+
+    if let Err(err) = result {
+        error!(outcome = "failure"; "Skipping dumping database schema: {:?}", err);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedMigration {
+    pub id: i64,
+    pub name: Option<String>,
+    pub path: PathBuf,
+}
+
+// Callbacks for embedding geni, every method does nothing by default.
+// on_plan is called before the migrations table is created or changed.
+// Returning an error from on_plan or before_migration aborts the run before anything more is executed.
+#[async_trait]
+pub trait MigrationObserver: Send {
+    async fn on_plan(
+        &mut self,
+        _direction: Direction,
+        _migrations: &[PlannedMigration],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn before_migration(
+        &mut self,
+        _direction: Direction,
+        _migration: &PlannedMigration,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn after_migration(
+        &mut self,
+        _direction: Direction,
+        _migration: &PlannedMigration,
+        _duration: Duration,
+        _result: &Result<()>,
+    ) {
+    }
+
+    async fn on_dump(&mut self, _schema_file: &str, _result: &Result<()>) {}
+
+    // Called once the run is over, also when it failed or was aborted
+    async fn on_finish(&mut self, _direction: Direction, _result: &Result<()>) {}
+}
+
+pub struct NoopObserver;
+
+#[async_trait]
+impl MigrationObserver for NoopObserver {}
+
+// Runs several observers in order, the first one to fail on_plan or before_migration aborts the run
+#[derive(Default)]
+pub struct Observers(pub Vec<Box<dyn MigrationObserver>>);
+
+#[async_trait]
+impl MigrationObserver for Observers {
+    async fn on_plan(
+        &mut self,
+        direction: Direction,
+        migrations: &[PlannedMigration],
+    ) -> Result<()> {
+        for observer in self.0.iter_mut() {
+            observer.on_plan(direction, migrations).await?;
+        }
+        Ok(())
+    }
+
+    async fn before_migration(
+        &mut self,
+        direction: Direction,
+        migration: &PlannedMigration,
+    ) -> Result<()> {
+        for observer in self.0.iter_mut() {
+            observer.before_migration(direction, migration).await?;
+        }
+        Ok(())
+    }
+
+    async fn after_migration(
+        &mut self,
+        direction: Direction,
+        migration: &PlannedMigration,
+        duration: Duration,
+        result: &Result<()>,
+    ) {
+        for observer in self.0.iter_mut() {
+            observer
+                .after_migration(direction, migration, duration, result)
+                .await;
+        }
+    }
+
+    async fn on_dump(&mut self, schema_file: &str, result: &Result<()>) {
+        for observer in self.0.iter_mut() {
+            observer.on_dump(schema_file, result).await;
+        }
+    }
+
+    async fn on_finish(&mut self, direction: Direction, result: &Result<()>) {
+        for observer in self.0.iter_mut() {
+            observer.on_finish(direction, result).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction_as_str() {
+        assert_eq!(Direction::Up.as_str(), "up");
+        assert_eq!(Direction::Down.as_str(), "down");
+        assert_eq!(serde_json::to_string(&Direction::Down).unwrap(), "\"down\"");
+    }
+
+    #[tokio::test]
+    async fn test_noop_observer_allows_every_migration() {
+        let migration = PlannedMigration {
+            id: 1,
+            name: Some("create_users".to_string()),
+            path: PathBuf::from("0001_create_users.up.sql"),
+        };
+
+        assert!(NoopObserver
+            .before_migration(Direction::Up, &migration)
+            .await
+            .is_ok());
+    }
+
+    struct Refuse;
+
+    #[async_trait]
+    impl MigrationObserver for Refuse {
+        async fn before_migration(&mut self, _: Direction, _: &PlannedMigration) -> Result<()> {
+            anyhow::bail!("refused")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observers_stop_at_first_error() {
+        let migration = PlannedMigration {
+            id: 1,
+            name: None,
+            path: PathBuf::from("0001.up.sql"),
+        };
+        let mut observers = Observers(vec![Box::new(NoopObserver), Box::new(Refuse)]);
+
+        assert!(observers
+            .on_plan(Direction::Up, std::slice::from_ref(&migration))
+            .await
+            .is_ok());
+        assert_eq!(
+            observers
+                .before_migration(Direction::Up, &migration)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "refused"
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use crate::config::VersionScheme;
+use crate::database_drivers;
+use crate::generate::{format_version, next_version};
+use crate::utils::{applied_migration_ids, get_local_migrations};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub async fn renumber(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    names: Vec<String>,
+    version_scheme: VersionScheme,
+) -> Result<()> {
+    let path = PathBuf::from(&migration_folder);
+    let up_files = match get_local_migrations(&path, "up") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+    let down_files = match get_local_migrations(&path, "down") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+
+    let selected = find_migrations(&names, &up_files, &migration_folder)?;
+
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    let migrations = applied_migration_ids(&database.get_or_create_schema_migrations().await?);
+
+    // An applied id is only left alone when another migration keeps it, e.g. the one that was
+    // merged first when two migrations were created in the same second
+    if let Some((id, _)) = selected.iter().find(|(id, _)| {
+        migrations.contains(id)
+            && up_files
+                .iter()
+                .filter(|(timestamp, _)| timestamp == id)
+                .count()
+                == selected.iter().filter(|(other, _)| other == id).count()
+    }) {
+        bail!(
+            "Migration {} is already applied to the database and can't be renumbered",
+            id
+        );
+    }
+
+    let ids = selected.iter().map(|(id, _)| *id).collect::<Vec<i64>>();
+    let new_ids = new_versions(&ids, &up_files, &migrations, &version_scheme, Utc::now());
+
+    rename_migrations(&selected, &new_ids, &up_files, &down_files, &version_scheme)
+}
+
+// Migrations are named by id, <id>_<name> or file name, the id alone only works when no other
+// migration shares it
+fn find_migrations(
+    names: &[String],
+    up_files: &[(i64, PathBuf)],
+    migration_folder: &str,
+) -> Result<Vec<(i64, String)>> {
+    let mut selected: Vec<(i64, String)> = vec![];
+
+    for name in names {
+        let name = Path::new(name)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(name);
+        let name = name
+            .strip_suffix(".up.sql")
+            .or_else(|| name.strip_suffix(".down.sql"))
+            .unwrap_or(name);
+
+        let (id_part, name_part) = match name.split_once('_') {
+            Some((id, migration)) => (id, Some(migration)),
+            None => (name, None),
+        };
+
+        let matching = up_files
+            .iter()
+            .map(|(id, file)| (*id, migration_name(file)))
+            .filter(|(id, migration)| {
+                id_part.parse::<i64>().ok() == Some(*id)
+                    && name_part.is_none_or(|name_part| name_part == migration)
+            })
+            .collect::<Vec<(i64, String)>>();
+
+        let migration = match matching.as_slice() {
+            [] => bail!("Didn't find migration {} at {}", name, migration_folder),
+            [migration] => migration.clone(),
+            _ => bail!(
+                "More than one migration has the id {}, name it as {}",
+                name,
+                matching
+                    .iter()
+                    .map(|(id, migration)| format!("{}_{}", id, migration))
+                    .collect::<Vec<String>>()
+                    .join(" or ")
+            ),
+        };
+
+        if selected.contains(&migration) {
+            bail!("Migration {} is listed more than once", name);
+        }
+        selected.push(migration);
+    }
+
+    Ok(selected)
+}
+
+fn rename_migrations(
+    selected: &[(i64, String)],
+    new_ids: &[i64],
+    up_files: &[(i64, PathBuf)],
+    down_files: &[(i64, PathBuf)],
+    version_scheme: &VersionScheme,
+) -> Result<()> {
+    for ((id, name), new_id) in selected.iter().zip(new_ids) {
+        for (_, file) in up_files
+            .iter()
+            .chain(down_files.iter())
+            .filter(|(timestamp, file)| timestamp == id && migration_name(file) == *name)
+        {
+            fs::rename(
+                file,
+                renamed_path(file, &format_version(version_scheme, *new_id)),
+            )?;
+        }
+
+        info!("Renumbered {}_{} to {}", id, name, new_id);
+    }
+
+    Ok(())
+}
+
+// Renumbered migrations are placed after the newest applied or local migration, in the given order
+fn new_versions(
+    ids: &[i64],
+    files: &[(i64, PathBuf)],
+    migrations: &[i64],
+    version_scheme: &VersionScheme,
+    now: DateTime<Utc>,
+) -> Vec<i64> {
+    let mut existing: Vec<i64> = files
+        .iter()
+        .map(|(id, _)| *id)
+        .chain(migrations.iter().copied())
+        .collect();
+
+    ids.iter()
+        .map(|_| {
+            let new_id = next_version(version_scheme, &existing, now);
+            existing.push(new_id);
+            new_id
+        })
+        .collect()
+}
+
+fn renamed_path(path: &Path, new_id: &str) -> PathBuf {
+    let filename = path.file_name().unwrap().to_str().unwrap();
+    let name = filename.split_once('_').unwrap().1;
+
+    path.with_file_name(format!("{}_{}", new_id, name))
+}
+
+// The part between the id and .up.sql or .down.sql
+fn migration_name(path: &Path) -> String {
+    let filename = path.file_name().unwrap().to_str().unwrap();
+    let name = filename.split_once('_').unwrap().1;
+
+    name.strip_suffix(".up.sql")
+        .or_else(|| name.strip_suffix(".down.sql"))
+        .unwrap_or(name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_new_versions_after_newest_local() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+            (1234567895, PathBuf::from("1234567895_add_posts.up.sql")),
+        ];
+        let migrations = vec![1234567891];
+
+        let result = new_versions(
+            &[1234567890, 1234567895],
+            &files,
+            &migrations,
+            &VersionScheme::Timestamp,
+            Utc.timestamp_opt(0, 0).unwrap(),
+        );
+        assert_eq!(result, vec![1234567896, 1234567897]);
+    }
+
+    #[test]
+    fn test_new_versions_after_newest_applied() {
+        let files = vec![(1234567890, PathBuf::from("1234567890_create_users.up.sql"))];
+        let migrations = vec![1234567899];
+
+        let result = new_versions(
+            &[1234567890],
+            &files,
+            &migrations,
+            &VersionScheme::Timestamp,
+            Utc.timestamp_opt(0, 0).unwrap(),
+        );
+        assert_eq!(result, vec![1234567900]);
+    }
+
+    #[test]
+    fn test_new_versions_uses_current_time() {
+        let files = vec![(1234567890, PathBuf::from("1234567890_create_users.up.sql"))];
+
+        let result = new_versions(
+            &[1234567890],
+            &files,
+            &[],
+            &VersionScheme::Timestamp,
+            Utc.timestamp_opt(1700000000, 0).unwrap(),
+        );
+        assert_eq!(result, vec![1700000000]);
+    }
+
+    #[test]
+    fn test_new_versions_sequential() {
+        let files = vec![
+            (1, PathBuf::from("0001_create_users.up.sql")),
+            (2, PathBuf::from("0002_add_index.up.sql")),
+            (10, PathBuf::from("0010_add_posts.up.sql")),
+        ];
+
+        let result = new_versions(&[2], &files, &[1], &VersionScheme::Sequential, Utc::now());
+        assert_eq!(result, vec![11]);
+    }
+
+    #[test]
+    fn test_renamed_path() {
+        let path = PathBuf::from("./migrations/1234567890_create_users.down.sql");
+
+        assert_eq!(
+            renamed_path(&path, "1234567899"),
+            PathBuf::from("./migrations/1234567899_create_users.down.sql")
+        );
+    }
+
+    #[test]
+    fn test_new_versions_for_migrations_sharing_an_id() {
+        let files = vec![
+            (1700000000, PathBuf::from("1700000000_a.up.sql")),
+            (1700000000, PathBuf::from("1700000000_b.up.sql")),
+        ];
+
+        let result = new_versions(
+            &[1700000000, 1700000000],
+            &files,
+            &[],
+            &VersionScheme::Timestamp,
+            Utc.timestamp_opt(0, 0).unwrap(),
+        );
+        assert_eq!(result, vec![1700000001, 1700000002]);
+    }
+
+    #[test]
+    fn test_find_migrations() {
+        let files = vec![
+            (
+                1700000000,
+                PathBuf::from("./migrations/1700000000_a.up.sql"),
+            ),
+            (
+                1700000000,
+                PathBuf::from("./migrations/1700000000_b.up.sql"),
+            ),
+            (
+                1700000001,
+                PathBuf::from("./migrations/1700000001_c.up.sql"),
+            ),
+        ];
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(
+            find_migrations(
+                &names(&[
+                    "1700000000_b",
+                    "migrations/1700000000_a.down.sql",
+                    "1700000001"
+                ]),
+                &files,
+                "./migrations"
+            )
+            .unwrap(),
+            vec![
+                (1700000000, "b".to_string()),
+                (1700000000, "a".to_string()),
+                (1700000001, "c".to_string())
+            ]
+        );
+
+        let err = find_migrations(&names(&["1700000000"]), &files, "./migrations").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("name it as 1700000000_a or 1700000000_b"));
+
+        assert!(find_migrations(&names(&["1700000000_d"]), &files, "./migrations").is_err());
+        assert!(find_migrations(
+            &names(&["1700000001", "1700000001_c"]),
+            &files,
+            "./migrations"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_rename_migrations_sharing_an_id() {
+        let tmp_dir = tempdir().unwrap();
+        for file in [
+            "1700000000_a.up.sql",
+            "1700000000_a.down.sql",
+            "1700000000_b.up.sql",
+            "1700000000_b.down.sql",
+        ] {
+            fs::write(tmp_dir.path().join(file), "").unwrap();
+        }
+        let folder = tmp_dir.path().to_path_buf();
+        let up_files = get_local_migrations(&folder, "up").unwrap();
+        let down_files = get_local_migrations(&folder, "down").unwrap();
+
+        let selected = find_migrations(
+            &["1700000000_a".to_string(), "1700000000_b".to_string()],
+            &up_files,
+            folder.to_str().unwrap(),
+        )
+        .unwrap();
+        let ids = selected.iter().map(|(id, _)| *id).collect::<Vec<i64>>();
+        let new_ids = new_versions(
+            &ids,
+            &up_files,
+            &[],
+            &VersionScheme::Timestamp,
+            Utc.timestamp_opt(0, 0).unwrap(),
+        );
+        rename_migrations(
+            &selected,
+            &new_ids,
+            &up_files,
+            &down_files,
+            &VersionScheme::Timestamp,
+        )
+        .unwrap();
+
+        let mut files = fs::read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<String>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "1700000001_a.down.sql",
+                "1700000001_a.up.sql",
+                "1700000002_b.down.sql",
+                "1700000002_b.up.sql",
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::database_drivers::{self, DatabaseDriver};
+use crate::utils::{
+    applied_migration, applied_migration_ids, current_os_user, get_local_migrations, next_batch,
+};
+use anyhow::{bail, Result};
+use log::info;
+use std::path::PathBuf;
+
+pub async fn mark_applied(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    id: i64,
+) -> Result<()> {
+    let (mut database, migrations, file) = connect_for_local_migration(
+        database_url,
+        database_token,
+        migration_table.clone(),
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        id,
+    )
+    .await?;
+
+    if migrations.contains(&id) {
+        bail!("Migration {} is already marked as applied", id);
+    }
+
+    let batch = next_batch(&mut database).await?;
+    database
+        .insert_schema_migration(&applied_migration(id, &file, None, batch))
+        .await?;
+
+    info!(
+        "Audit: {} marked migration {} as applied in {} without running it",
+        current_os_user(),
+        id,
+        migration_table
+    );
+
+    Ok(())
+}
+
+pub async fn mark_unapplied(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    id: i64,
+) -> Result<()> {
+    let (mut database, migrations, _) = connect_for_local_migration(
+        database_url,
+        database_token,
+        migration_table.clone(),
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        id,
+    )
+    .await?;
+
+    if !migrations.contains(&id) {
+        bail!("Migration {} is not marked as applied", id);
+    }
+
+    database.remove_schema_migration(&id.to_string()).await?;
+
+    info!(
+        "Audit: {} marked migration {} as unapplied in {} without rolling it back",
+        current_os_user(),
+        id,
+        migration_table
+    );
+
+    Ok(())
+}
+
+// Checks that the migration exists locally before touching the database
+async fn connect_for_local_migration(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    id: i64,
+) -> Result<(Box<dyn DatabaseDriver>, Vec<i64>, PathBuf)> {
+    let path = PathBuf::from(&migration_folder);
+    let files = match get_local_migrations(&path, "up") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+
+    let file = match files.into_iter().find(|(timestamp, _)| *timestamp == id) {
+        Some((_, file)) => file,
+        None => bail!("Didn't find migration {} at {}", id, migration_folder),
+    };
+
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    let migrations = applied_migration_ids(&database.get_or_create_schema_migrations().await?);
+
+    Ok((database, migrations, file))
+}
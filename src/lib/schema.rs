@@ -0,0 +1,200 @@
+// Database independent model of a schema, every driver fills it from its own catalog. Tools can
+// work on the model instead of parsing the schema dump, it serializes to JSON and YAML with serde.
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    pub tables: Vec<Table>,
+    pub indexes: Vec<Index>,
+    pub constraints: Vec<Constraint>,
+    pub views: Vec<View>,
+    pub sequences: Vec<Sequence>,
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Table {
+    // Only Postgres has schemas, MySQL, MariaDB and SQLite objects live in the connected database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub name: String,
+    // In the order they were defined
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    // The type as the database prints it, such as character varying(255) or varchar(255)
+    pub data_type: String,
+    pub nullable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<Identity>,
+    // Expression of a generated column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+    // Only set when it differs from the default collation of the type or table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collation: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Identity {
+    // GENERATED ALWAYS AS IDENTITY
+    Always,
+    // GENERATED BY DEFAULT AS IDENTITY
+    ByDefault,
+    // AUTO_INCREMENT in MySQL and MariaDB, AUTOINCREMENT in SQLite
+    AutoIncrement,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub table: String,
+    pub name: String,
+    // Key columns in order, expressions are only listed where the database reports them
+    pub columns: Vec<String>,
+    pub unique: bool,
+    // The CREATE INDEX statement
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstraintKind {
+    PrimaryKey,
+    Unique,
+    ForeignKey,
+    Check,
+    Exclusion,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Constraint {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub table: String,
+    // SQLite doesn't keep the names of constraints
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub kind: ConstraintKind,
+    pub columns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub references: Option<Reference>,
+    // The constraint as written in a table definition, such as PRIMARY KEY (id)
+    pub definition: String,
+}
+
+// The table and columns a foreign key points to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub name: String,
+    pub materialized: bool,
+    // The CREATE VIEW statement
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sequence {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub name: String,
+    pub data_type: String,
+    pub start: i64,
+    pub increment: i64,
+    pub min: i64,
+    pub max: i64,
+    pub cycle: bool,
+    // The column using the sequence, as schema.table.column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owned_by: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    pub name: String,
+    // FUNCTION or PROCEDURE
+    pub kind: String,
+    pub arguments: String,
+    // The CREATE FUNCTION or CREATE PROCEDURE statement
+    pub definition: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_json() {
+        let schema = Schema {
+            tables: vec![Table {
+                schema: None,
+                name: "users".to_string(),
+                columns: vec![Column {
+                    name: "id".to_string(),
+                    data_type: "INTEGER".to_string(),
+                    nullable: false,
+                    default: None,
+                    identity: Some(Identity::AutoIncrement),
+                    generated: None,
+                    collation: None,
+                }],
+            }],
+            constraints: vec![Constraint {
+                schema: None,
+                table: "users".to_string(),
+                name: None,
+                kind: ConstraintKind::PrimaryKey,
+                columns: vec!["id".to_string()],
+                references: None,
+                definition: "PRIMARY KEY (id)".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let value = serde_json::to_value(&schema).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "tables": [{
+                    "name": "users",
+                    "columns": [{
+                        "name": "id",
+                        "data_type": "INTEGER",
+                        "nullable": false,
+                        "identity": "auto_increment"
+                    }]
+                }],
+                "indexes": [],
+                "constraints": [{
+                    "table": "users",
+                    "kind": "primary_key",
+                    "columns": ["id"],
+                    "definition": "PRIMARY KEY (id)"
+                }],
+                "views": [],
+                "sequences": [],
+                "functions": []
+            })
+        );
+        assert_eq!(serde_json::from_value::<Schema>(value).unwrap(), schema);
+    }
+}
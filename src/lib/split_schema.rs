@@ -0,0 +1,669 @@
+// The schema dump split into one file per object, so changes to different objects don't touch
+// the same file. Statements go to the file of the object they create or change: indexes,
+// constraints, triggers, policies and comments of a table to the file of the table.
+use anyhow::Result;
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+// A quoted or plain name, optionally qualified
+const NAME: &str = r#"((?:"(?:[^"]|"")*"|`(?:[^`]|``)*`|[\w$]+)(?:\s*\.\s*(?:"(?:[^"]|"")*"|`(?:[^`]|``)*`|[\w$]+))*)"#;
+
+static CREATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?is)^CREATE\s+(?:OR\s+REPLACE\s+)?(?:(?:ALGORITHM|SQL\s+SECURITY)\s*=?\s*\w+\s+)*(?:VIRTUAL\s+|UNLOGGED\s+)?(MATERIALIZED\s+VIEW|TABLE|VIEW|FUNCTION|PROCEDURE|SEQUENCE|TYPE|DOMAIN|SCHEMA|EXTENSION|EVENT)\s+(?:IF\s+NOT\s+EXISTS\s+)?{}",
+        NAME
+    ))
+    .unwrap()
+});
+
+// Statements about a table other than CREATE TABLE
+static ON_TABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?is)^(?:CREATE\s+(?:UNIQUE\s+)?INDEX\s+.*?\s+ON\s+(?:ONLY\s+)?|CREATE\s+(?:OR\s+REPLACE\s+)?(?:CONSTRAINT\s+)?TRIGGER\s+.*?\s+ON\s+|CREATE\s+POLICY\s+.*?\s+ON\s+|ALTER\s+TABLE\s+(?:IF\s+EXISTS\s+)?(?:ONLY\s+)?){}",
+        NAME
+    ))
+    .unwrap()
+});
+
+static ALTER_SEQUENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"(?is)^ALTER\s+SEQUENCE\s+{}", NAME)).unwrap());
+
+static STATEMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(CREATE|ALTER|COMMENT|GRANT|REVOKE|INSERT|SET|PRAGMA|SELECT)\b").unwrap()
+});
+
+static FOREIGN_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)\bFOREIGN\s+KEY\b.*\bREFERENCES\b").unwrap());
+
+static DOLLAR_QUOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\w*\$").unwrap());
+
+// COMMENT ON, GRANT and REVOKE name the kind of the object
+static ON_OBJECT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?is)^(?:COMMENT|GRANT|REVOKE)\b.*?\bON\s+(MATERIALIZED\s+VIEW|TABLE|VIEW|COLUMN|FUNCTION|PROCEDURE|SEQUENCE|TYPE|DOMAIN|SCHEMA|EXTENSION|TRIGGER|POLICY|CONSTRAINT)\s+{}(?:\s+ON\s+{})?",
+        NAME, NAME
+    ))
+    .unwrap()
+});
+
+// The directory next to the schema file, schema/ for schema.sql
+pub fn schema_dir(schema_file: &str) -> PathBuf {
+    Path::new(schema_file).with_extension("")
+}
+
+// The file of every object relative to the schema directory and its statements in dump order.
+// Statements that don't belong to an object, like SET or the migration rows, go to a file
+// named after their section.
+pub fn split(schema: &str) -> BTreeMap<PathBuf, String> {
+    let statements = statements(schema);
+    let views = statements
+        .iter()
+        .filter_map(|(_, statement)| match created(statement) {
+            Some(("views", name)) => Some(name),
+            _ => None,
+        })
+        .collect::<HashSet<String>>();
+
+    let mut files: BTreeMap<PathBuf, Vec<&str>> = BTreeMap::new();
+    for (section, statement) in &statements {
+        let path = match object(statement, &views) {
+            Some((dir, name)) => Path::new(dir).join(format!("{}.sql", file_name(&name))),
+            None => PathBuf::from(format!("{}.sql", file_name(section))),
+        };
+        files.entry(path).or_default().push(statement);
+    }
+
+    files
+        .into_iter()
+        .map(|(path, statements)| (path, format!("{}\n", statements.join("\n\n"))))
+        .collect()
+}
+
+// Writes the files of the split dump and removes the .sql files of objects that are gone.
+// Unchanged files aren't touched.
+pub fn write(schema: &str, migrations_folder: &str, schema_file: &str) -> Result<()> {
+    let dir = Path::new(migrations_folder).join(schema_dir(schema_file));
+    let files = split(schema);
+
+    for (path, content) in &files {
+        let path = dir.join(path);
+        if fs::read_to_string(&path).ok().as_deref() == Some(content.as_str()) {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+    }
+
+    for path in sql_files(&dir)? {
+        if !files.contains_key(&path) {
+            fs::remove_file(dir.join(&path))?;
+        }
+    }
+    remove_empty_dirs(&dir)?;
+
+    Ok(())
+}
+
+// The .sql files in the schema directory by their path relative to it, empty when it's missing
+pub fn read(migrations_folder: &str, schema_file: &str) -> Result<BTreeMap<PathBuf, String>> {
+    let dir = Path::new(migrations_folder).join(schema_dir(schema_file));
+
+    sql_files(&dir)?
+        .into_iter()
+        .map(|path| Ok((path.clone(), fs::read_to_string(dir.join(&path))?)))
+        .collect()
+}
+
+// The statements of a split dump in an order that can be executed against an empty database.
+// The order of the files is lost, so statements are ordered by kind like the sections of the
+// dumps: functions using a table come after the tables and views after the views they select from.
+pub fn join(files: &BTreeMap<PathBuf, String>) -> String {
+    let statements = files
+        .values()
+        .flat_map(|content| statements(content).into_iter().map(|(_, s)| s))
+        .collect::<Vec<String>>();
+    let tables = statements
+        .iter()
+        .filter_map(|statement| match created(statement) {
+            Some(("tables", name)) => Some(short_name(&name).to_string()),
+            _ => None,
+        })
+        .collect::<HashSet<String>>();
+
+    let mut ranked = statements
+        .iter()
+        .map(|statement| (load_rank(statement, &tables), statement.as_str()))
+        .collect::<Vec<(u8, &str)>>();
+    ranked.sort_by_key(|(rank, _)| *rank);
+
+    // Views take the places of the views in dependency order
+    let mut views = dependency_order(
+        ranked
+            .iter()
+            .filter(|(rank, _)| *rank == VIEW_RANK)
+            .map(|(_, view)| *view)
+            .collect(),
+    )
+    .into_iter();
+    let ordered = ranked
+        .iter()
+        .map(|(rank, statement)| match *rank {
+            VIEW_RANK => views.next().unwrap(),
+            _ => *statement,
+        })
+        .collect::<Vec<&str>>();
+
+    format!("{}\n", ordered.join("\n\n"))
+}
+
+const VIEW_RANK: u8 = 11;
+
+fn load_rank(statement: &str, tables: &HashSet<String>) -> u8 {
+    if let Some((directory, _)) = created(statement) {
+        return match directory {
+            "schemas" => 1,
+            "extensions" => 2,
+            "types" => 3,
+            "functions" if mentioned(statement, tables).is_empty() => 4,
+            "sequences" => 5,
+            "tables" => 6,
+            "functions" => 10,
+            "views" => VIEW_RANK,
+            _ => 17,
+        };
+    }
+
+    let words = statement
+        .split_whitespace()
+        .take(4)
+        .map(str::to_uppercase)
+        .collect::<Vec<String>>();
+    match words.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["SET" | "PRAGMA" | "SELECT", ..] => 0,
+        ["ALTER", "SEQUENCE", ..] => 7,
+        // Foreign keys need the primary and unique keys of the tables they reference
+        ["ALTER", "TABLE", ..] if FOREIGN_KEY.is_match(statement) => 9,
+        ["CREATE", "INDEX" | "UNIQUE", ..] => 12,
+        ["CREATE", "POLICY", ..] => 14,
+        ["CREATE", ..] if words.iter().any(|word| word == "TRIGGER") => 13,
+        ["GRANT" | "REVOKE", ..] => 15,
+        ["COMMENT", ..] => 16,
+        ["INSERT", ..] => 18,
+        _ => 8,
+    }
+}
+
+// Views are created after the views they mention, views mentioning each other keep their order
+fn dependency_order(mut views: Vec<&str>) -> Vec<&str> {
+    let mut ordered = vec![];
+    while !views.is_empty() {
+        let names = views
+            .iter()
+            .filter_map(|view| created(view).map(|(_, name)| short_name(&name).to_string()))
+            .collect::<HashSet<String>>();
+        let next = views
+            .iter()
+            .position(|view| {
+                let own = created(view).map(|(_, name)| short_name(&name).to_string());
+                mentioned(view, &names)
+                    .iter()
+                    .all(|name| Some(name) == own.as_ref())
+            })
+            .unwrap_or(0);
+        ordered.push(views.remove(next));
+    }
+
+    ordered
+}
+
+// The names that appear as a word in the statement
+fn mentioned(statement: &str, names: &HashSet<String>) -> HashSet<String> {
+    statement
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .filter(|word| names.contains(*word))
+        .map(str::to_string)
+        .collect()
+}
+
+fn short_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+// The statements of a dump with the section they're in. Sections start with a "-- TITLE" line
+// and statements are separated by empty lines. Function bodies can contain empty lines, a
+// paragraph only starts a statement after a complete one.
+fn statements(schema: &str) -> Vec<(String, String)> {
+    let mut section = "settings".to_string();
+    let mut statements: Vec<(String, String)> = vec![];
+
+    for paragraph in paragraphs(schema) {
+        if let [line] = paragraph.as_slice() {
+            let title = line.trim_start_matches('-').trim();
+            if line.starts_with("--")
+                && !title.is_empty()
+                && title.chars().all(|c| c.is_ascii_uppercase() || c == ' ')
+            {
+                section = title.to_lowercase();
+                continue;
+            }
+        }
+
+        let paragraph = paragraph.join("\n");
+        match statements.last_mut() {
+            Some((_, last)) if !is_complete(last) || !STATEMENT.is_match(&paragraph) => {
+                last.push_str("\n\n");
+                last.push_str(&paragraph);
+            }
+            _ => {
+                // The comment heading the dump isn't part of any object
+                let paragraph = paragraph
+                    .lines()
+                    .skip_while(|line| line.starts_with("--"))
+                    .collect::<Vec<&str>>()
+                    .join("\n");
+                if !paragraph.is_empty() {
+                    statements.push((section.clone(), paragraph));
+                }
+            }
+        }
+    }
+
+    statements
+}
+
+fn paragraphs(schema: &str) -> Vec<Vec<&str>> {
+    let mut paragraphs = vec![vec![]];
+    for line in schema.lines() {
+        if line.trim().is_empty() {
+            paragraphs.push(vec![]);
+        } else {
+            paragraphs.last_mut().unwrap().push(line);
+        }
+    }
+    paragraphs.retain(|paragraph| !paragraph.is_empty());
+
+    paragraphs
+}
+
+// Ends with a semicolon outside of a dollar quoted body
+fn is_complete(statement: &str) -> bool {
+    statement.trim_end().ends_with(';')
+        && DOLLAR_QUOTE.find_iter(statement).count().is_multiple_of(2)
+}
+
+fn created(statement: &str) -> Option<(&'static str, String)> {
+    let captures = CREATE.captures(statement)?;
+    let kind = captures[1]
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    Some((directory(&kind)?, unquote(&captures[2])))
+}
+
+fn object(statement: &str, views: &HashSet<String>) -> Option<(&'static str, String)> {
+    let table_or_view = |name: String| {
+        if views.contains(&name) {
+            ("views", name)
+        } else {
+            ("tables", name)
+        }
+    };
+
+    if let Some(object) = created(statement) {
+        return Some(object);
+    }
+    if let Some(captures) = ON_TABLE.captures(statement) {
+        return Some(table_or_view(unquote(&captures[1])));
+    }
+    if let Some(captures) = ALTER_SEQUENCE.captures(statement) {
+        return Some(("sequences", unquote(&captures[1])));
+    }
+
+    let captures = ON_OBJECT.captures(statement)?;
+    let kind = captures[1]
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let name = unquote(&captures[2]);
+    match kind.to_uppercase().as_str() {
+        // table.column, the table may be qualified
+        "COLUMN" => name
+            .rsplit_once('.')
+            .map(|(table, _)| table_or_view(table.to_string())),
+        // name ON table
+        "TRIGGER" | "POLICY" | "CONSTRAINT" => captures
+            .get(3)
+            .map(|table| table_or_view(unquote(table.as_str()))),
+        _ => Some((directory(&kind)?, name)),
+    }
+}
+
+fn directory(kind: &str) -> Option<&'static str> {
+    Some(match kind.to_uppercase().as_str() {
+        "TABLE" => "tables",
+        "VIEW" | "MATERIALIZED VIEW" => "views",
+        "FUNCTION" | "PROCEDURE" => "functions",
+        "SEQUENCE" => "sequences",
+        "TYPE" | "DOMAIN" => "types",
+        "SCHEMA" => "schemas",
+        "EXTENSION" => "extensions",
+        "EVENT" => "events",
+        _ => return None,
+    })
+}
+
+// The parts of a name without quotes, joined by dots
+fn unquote(name: &str) -> String {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut quote = None;
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                if chars.peek() == Some(&q) {
+                    part.push(q);
+                    chars.next();
+                } else {
+                    quote = None;
+                }
+            }
+            (Some(_), c) => part.push(c),
+            (None, '"' | '`') => quote = Some(c),
+            (None, '.') => parts.push(std::mem::take(&mut part)),
+            (None, c) if c.is_whitespace() => {}
+            (None, c) => part.push(c),
+        }
+    }
+    parts.push(part);
+
+    parts.join(".")
+}
+
+// Names can contain anything once quoted, only characters safe in a file name are kept
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '$') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn sql_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    fn visit(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                visit(&entry.path(), &path, files)?;
+            } else if path.extension().is_some_and(|e| e == "sql") {
+                files.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut files = vec![];
+    if dir.is_dir() {
+        visit(dir, Path::new(""), &mut files)?;
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dirs(&entry.path())?;
+            if fs::read_dir(entry.path())?.next().is_none() {
+                fs::remove_dir(entry.path())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const POSTGRES: &str = r#"--
+-- Postgres SQL Schema dump automatic generated by geni
+--
+
+SET check_function_bodies = false;
+
+-- SCHEMAS 
+
+CREATE SCHEMA IF NOT EXISTS public;
+
+-- FUNCTIONS 
+
+CREATE OR REPLACE FUNCTION public.touch()
+ RETURNS trigger
+ LANGUAGE plpgsql
+AS $function$
+BEGIN
+
+  NEW.updated_at = now();
+
+  RETURN NEW;
+END
+$function$;
+
+-- SEQUENCES 
+
+CREATE SEQUENCE public."users_id_seq" AS integer START WITH 1 INCREMENT BY 1 MINVALUE 1 MAXVALUE 2147483647 CACHE 1 NO CYCLE;
+
+-- TABLES 
+
+CREATE TABLE public."users" (
+    id integer DEFAULT nextval('public.users_id_seq'::regclass) NOT NULL,
+    email text NOT NULL
+);
+
+-- SEQUENCE OWNERSHIP 
+
+ALTER SEQUENCE public."users_id_seq" OWNED BY public."users".id;
+
+-- CONSTRAINTS 
+
+ALTER TABLE public."users" ADD CONSTRAINT users_pkey PRIMARY KEY (id);
+
+-- VIEWS 
+
+CREATE MATERIALIZED VIEW public."emails" AS
+ SELECT users.email
+   FROM public.users;
+
+-- INDEXES 
+
+CREATE UNIQUE INDEX users_email ON public.users USING btree (email);
+
+CREATE INDEX emails_email ON public.emails USING btree (email);
+
+-- TRIGGERS 
+
+CREATE TRIGGER touch BEFORE UPDATE ON public.users FOR EACH ROW EXECUTE FUNCTION public.touch();
+
+-- GRANTS 
+
+GRANT USAGE ON SCHEMA public TO PUBLIC;
+
+-- COMMENTS 
+
+COMMENT ON COLUMN public.users.email IS 'Login';
+
+COMMENT ON INDEX public.users_email IS 'Unique';
+
+"#;
+
+    fn paths(files: &BTreeMap<PathBuf, String>) -> Vec<String> {
+        files
+            .keys()
+            .map(|path| path.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_postgres() {
+        let files = split(POSTGRES);
+
+        assert_eq!(
+            paths(&files),
+            vec![
+                "comments.sql",
+                "functions/public.touch.sql",
+                "schemas/public.sql",
+                "sequences/public.users_id_seq.sql",
+                "settings.sql",
+                "tables/public.users.sql",
+                "views/public.emails.sql",
+            ]
+        );
+        assert_eq!(
+            files[Path::new("tables/public.users.sql")],
+            [
+                "CREATE TABLE public.\"users\" (",
+                "    id integer DEFAULT nextval('public.users_id_seq'::regclass) NOT NULL,",
+                "    email text NOT NULL",
+                ");",
+                "",
+                "ALTER TABLE public.\"users\" ADD CONSTRAINT users_pkey PRIMARY KEY (id);",
+                "",
+                "CREATE UNIQUE INDEX users_email ON public.users USING btree (email);",
+                "",
+                "CREATE TRIGGER touch BEFORE UPDATE ON public.users FOR EACH ROW EXECUTE FUNCTION public.touch();",
+                "",
+                "COMMENT ON COLUMN public.users.email IS 'Login';",
+                "",
+            ]
+            .join("\n")
+        );
+        // Empty lines in a function body don't end the statement
+        assert!(files[Path::new("functions/public.touch.sql")]
+            .contains("BEGIN\n\n  NEW.updated_at = now();\n\n  RETURN NEW;\nEND\n$function$;\n"));
+        assert!(files[Path::new("views/public.emails.sql")].ends_with(
+            "FROM public.users;\n\nCREATE INDEX emails_email ON public.emails USING btree (email);\n"
+        ));
+        assert_eq!(
+            files[Path::new("settings.sql")],
+            "SET check_function_bodies = false;\n"
+        );
+        assert!(files[Path::new("schemas/public.sql")]
+            .ends_with("GRANT USAGE ON SCHEMA public TO PUBLIC;\n"));
+    }
+
+    #[test]
+    fn test_split_mysql_and_sqlite() {
+        let mysql = "-- TABLES \n\nCREATE TABLE `order items` (\n  `id` int NOT NULL\n);\n\n-- ROUTINES \n\nCREATE FUNCTION `total`(a int) RETURNS int\nBEGIN\n  RETURN a;\nEND;\n\n-- VIEWS \n\nCREATE ALGORITHM=UNDEFINED SQL SECURITY DEFINER VIEW `items` AS select 1 AS `1`;\n\n-- TRIGGERS \n\nCREATE TRIGGER `check_id` BEFORE INSERT ON `order items` FOR EACH ROW SET NEW.id = NEW.id;\n\n-- EVENTS \n\nCREATE EVENT `cleanup` ON SCHEDULE EVERY 1 DAY DO DELETE FROM `order items`;\n\n-- MIGRATIONS \n\nINSERT INTO `schema_migrations` (id) VALUES ('1');\n";
+        assert_eq!(
+            paths(&split(mysql)),
+            vec![
+                "events/cleanup.sql",
+                "functions/total.sql",
+                "migrations.sql",
+                "tables/order_items.sql",
+                "views/items.sql",
+            ]
+        );
+
+        let sqlite = "--\n-- Sqlite SQL Schema dump automatic generated by geni\n--\n\n\nPRAGMA user_version = 3;\n\n-- TABLES \n\nCREATE TABLE \"users\" (id INTEGER PRIMARY KEY);\n\nCREATE VIRTUAL TABLE docs USING fts5(body);\n\n-- INDEXES \n\nCREATE INDEX users_id ON \"users\" (id);\n";
+        let files = split(sqlite);
+        assert_eq!(
+            paths(&files),
+            vec!["settings.sql", "tables/docs.sql", "tables/users.sql"]
+        );
+        assert_eq!(
+            files[Path::new("settings.sql")],
+            "PRAGMA user_version = 3;\n"
+        );
+    }
+
+    #[test]
+    fn test_join_orders_statements_for_loading() {
+        let joined = join(&split(POSTGRES));
+        let position = |statement: &str| joined.find(statement).unwrap();
+
+        assert!(position("SET check_function_bodies") < position("CREATE SCHEMA"));
+        assert!(position("CREATE SCHEMA") < position("CREATE OR REPLACE FUNCTION public.touch"));
+        assert!(position("CREATE SEQUENCE") < position("CREATE TABLE"));
+        assert!(position("CREATE TABLE") < position("ALTER SEQUENCE"));
+        assert!(position("ADD CONSTRAINT") < position("CREATE MATERIALIZED VIEW"));
+        assert!(position("CREATE MATERIALIZED VIEW") < position("CREATE INDEX emails_email"));
+        assert!(position("CREATE TRIGGER") < position("GRANT USAGE"));
+        assert!(position("GRANT USAGE") < position("COMMENT ON COLUMN"));
+        assert_eq!(
+            crate::drift::schema_diff(POSTGRES, &joined, "schema.sql", "schema"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_join_orders_by_dependency() {
+        let schema = "-- TABLES \n\nCREATE TABLE posts (user_id int);\n\nCREATE TABLE users (id int);\n\n-- CONSTRAINTS \n\nALTER TABLE posts ADD CONSTRAINT posts_user FOREIGN KEY (user_id) REFERENCES users(id);\n\nALTER TABLE users ADD CONSTRAINT users_pkey PRIMARY KEY (id);\n\n-- FUNCTIONS \n\nCREATE FUNCTION all_users() RETURNS SETOF users LANGUAGE sql AS 'SELECT * FROM users';\n\nCREATE FUNCTION one() RETURNS int LANGUAGE sql AS 'SELECT 1';\n\n-- VIEWS \n\nCREATE VIEW a_active AS SELECT * FROM z_users;\n\nCREATE VIEW z_users AS SELECT * FROM users;\n\n-- MIGRATIONS \n\nINSERT INTO schema_migrations (id) VALUES ('1');\n";
+        let joined = join(&split(schema));
+        let position = |statement: &str| joined.find(statement).unwrap();
+
+        assert!(position("CREATE FUNCTION one") < position("CREATE TABLE users"));
+        assert!(position("ADD CONSTRAINT users_pkey") < position("ADD CONSTRAINT posts_user"));
+        assert!(position("CREATE TABLE users") < position("CREATE FUNCTION all_users"));
+        assert!(position("CREATE VIEW z_users") < position("CREATE VIEW a_active"));
+        assert!(position("CREATE VIEW a_active") < position("INSERT INTO"));
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("public.\"users\""), "public.users");
+        assert_eq!(unquote("\"my \"\"table\"\"\" . x"), "my \"table\".x");
+        assert_eq!(unquote("`a.b`"), "a.b");
+        assert_eq!(file_name("my \"table\"/x"), "my__table__x");
+    }
+
+    #[test]
+    fn test_write_removes_stale_files() {
+        let tmp_dir = tempdir().unwrap();
+        let folder = tmp_dir.path().to_str().unwrap();
+        let schema = "-- TABLES \n\nCREATE TABLE a (id int);\n\nCREATE TABLE b (id int);\n\n-- VIEWS \n\nCREATE VIEW v AS SELECT 1;\n";
+        fs::create_dir_all(tmp_dir.path().join("schema")).unwrap();
+        fs::write(tmp_dir.path().join("schema/README.md"), "Generated").unwrap();
+
+        write(schema, folder, "schema.sql").unwrap();
+        assert_eq!(
+            paths(&read(folder, "schema.sql").unwrap()),
+            vec!["tables/a.sql", "tables/b.sql", "views/v.sql"]
+        );
+
+        write(
+            "-- TABLES \n\nCREATE TABLE a (id bigint);\n",
+            folder,
+            "schema.sql",
+        )
+        .unwrap();
+        let files = read(folder, "schema.sql").unwrap();
+        assert_eq!(paths(&files), vec!["tables/a.sql"]);
+        assert_eq!(
+            files[Path::new("tables/a.sql")],
+            "CREATE TABLE a (id bigint);\n"
+        );
+        assert!(!tmp_dir.path().join("schema/views").exists());
+        // Only .sql files belong to the dump
+        assert!(tmp_dir.path().join("schema/README.md").exists());
+    }
+}
@@ -0,0 +1,322 @@
+use crate::database_drivers::{self, DumpOptions};
+use crate::utils::{applied_migration_ids, get_local_migrations};
+use anyhow::{bail, Result};
+use log::info;
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub async fn squash<F>(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+    version: i64,
+    confirm: F,
+) -> Result<()>
+where
+    F: FnOnce(&[PathBuf]) -> bool,
+{
+    let path = PathBuf::from(&migration_folder);
+    let up_files = match get_local_migrations(&path, "up") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+    let down_files = match get_local_migrations(&path, "down") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+
+    let squashed = migrations_to_squash(&up_files, version);
+    let baseline_id = match squashed.last() {
+        Some((id, _)) => *id,
+        None => bail!(
+            "Didn't find any migrations up to {} at {}",
+            version,
+            migration_folder
+        ),
+    };
+
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table.clone(),
+        migration_folder.clone(),
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    let migrations = database.get_or_create_schema_migrations().await?;
+    check_database_is_at_version(&up_files, &migrations, version)?;
+
+    // The baseline records itself, so the rows of the migrations table are left out. The dump
+    // is only rendered, the schema file is left untouched.
+    let schema = database
+        .render_database_schema(&DumpOptions::default())
+        .await?;
+
+    let up_file = path.join(format!("{baseline_id}_baseline.up.sql"));
+    let down_file = path.join(format!("{baseline_id}_baseline.down.sql"));
+    // A baseline squashed again is replaced in place, not removed
+    let removed = up_files
+        .iter()
+        .chain(down_files.iter())
+        .filter(|(id, file)| *id <= version && *file != up_file && *file != down_file)
+        .map(|(_, file)| file.clone())
+        .collect::<Vec<PathBuf>>();
+
+    if !confirm(&removed) {
+        bail!(
+            "Aborted squash, {} migration files would have been replaced",
+            removed.len()
+        );
+    }
+
+    // The baseline is complete on disk before any migration is removed
+    write_file(
+        &up_file,
+        &format!(
+            "-- Baseline of all migrations up to {}, generated by geni squash\n{}",
+            baseline_id,
+            strip_migrations_table(&schema, &migration_table)
+        ),
+    )?;
+    write_file(
+        &down_file,
+        "-- A baseline generated by geni squash can't be rolled back",
+    )?;
+
+    for file in &removed {
+        fs::remove_file(file)?;
+    }
+
+    // Databases that already applied the squashed range have the baseline id, so only the
+    // ids of the removed migrations are cleaned up
+    for (id, _) in squashed.iter().filter(|(id, _)| *id != baseline_id) {
+        database.remove_schema_migration(&id.to_string()).await?;
+    }
+
+    info!(
+        "Squashed {} migrations into {}",
+        squashed.len(),
+        up_file.display()
+    );
+
+    Ok(())
+}
+
+// Written to a temporary file first, so the file is either complete or not there at all
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    let tmp_path = path.with_extension("sql.tmp");
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+fn migrations_to_squash(files: &[(i64, PathBuf)], version: i64) -> Vec<(i64, PathBuf)> {
+    files
+        .iter()
+        .filter(|(id, _)| *id <= version)
+        .cloned()
+        .collect()
+}
+
+// The dump is taken from the database, so it has to contain exactly the squashed range
+fn check_database_is_at_version(
+    files: &[(i64, PathBuf)],
+    migrations: &[String],
+    version: i64,
+) -> Result<()> {
+    let migrations = applied_migration_ids(migrations);
+
+    for (id, _) in files {
+        let applied = migrations.contains(id);
+
+        if *id <= version && !applied {
+            bail!(
+                "Migration {} is not applied to the database, run geni up to {} before squashing",
+                id,
+                version
+            );
+        }
+
+        if *id > version && applied {
+            bail!(
+                "Migration {} is newer than {} but already applied, the schema dump would include it",
+                id,
+                version
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// The dump includes the migrations table, which geni creates on its own before the baseline runs
+fn strip_migrations_table(schema: &str, migrations_table: &str) -> String {
+    let (schema_prefix, table) = match migrations_table.rsplit_once('.') {
+        Some((s, t)) => (format!(r#"["`]?{}["`]?\."#, regex::escape(s)), t),
+        None => (r#"([\w"`]+\.)?"#.to_string(), migrations_table),
+    };
+
+    let pattern = Regex::new(&format!(
+        r#"(?i)\b(TABLE|ON|INTO)\s+{}["`]?{}["`]?(\s|\(|;|$)"#,
+        schema_prefix,
+        regex::escape(table)
+    ))
+    .unwrap();
+
+    let mut lines: Vec<&str> = vec![];
+    let mut statement: Vec<&str> = vec![];
+
+    for line in schema.lines() {
+        let trimmed = line.trim();
+        if statement.is_empty() && (trimmed.is_empty() || trimmed.starts_with("--")) {
+            lines.push(line);
+            continue;
+        }
+
+        statement.push(line);
+
+        if trimmed.ends_with(';') {
+            if !pattern.is_match(&statement.join("\n")) {
+                lines.append(&mut statement);
+            }
+            statement.clear();
+        }
+    }
+
+    lines.append(&mut statement);
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_file_replaces_existing_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("1_baseline.up.sql");
+        fs::write(&path, "old").unwrap();
+
+        write_file(&path, "CREATE TABLE users (id INTEGER);").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "CREATE TABLE users (id INTEGER);"
+        );
+        assert!(!tmp_dir.path().join("1_baseline.up.sql.tmp").exists());
+    }
+
+    #[test]
+    fn test_migrations_to_squash() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+            (1234567892, PathBuf::from("1234567892_add_posts.up.sql")),
+        ];
+
+        let result = migrations_to_squash(&files, 1234567891);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].0, 1234567891);
+
+        let result = migrations_to_squash(&files, 1234567889);
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_check_database_is_at_version() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+            (1234567892, PathBuf::from("1234567892_add_posts.up.sql")),
+        ];
+
+        let migrations = vec!["1234567890".to_string(), "1234567891".to_string()];
+        assert!(check_database_is_at_version(&files, &migrations, 1234567891).is_ok());
+    }
+
+    #[test]
+    fn test_check_database_is_at_version_not_applied() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+        ];
+
+        let migrations = vec!["1234567890".to_string()];
+        let result = check_database_is_at_version(&files, &migrations, 1234567891);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Migration 1234567891 is not applied"));
+    }
+
+    #[test]
+    fn test_check_database_is_at_version_newer_applied() {
+        let files = vec![
+            (1234567890, PathBuf::from("1234567890_create_users.up.sql")),
+            (1234567891, PathBuf::from("1234567891_add_index.up.sql")),
+        ];
+
+        let migrations = vec!["1234567890".to_string(), "1234567891".to_string()];
+        let result = check_database_is_at_version(&files, &migrations, 1234567890);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Migration 1234567891 is newer than 1234567890"));
+    }
+
+    #[test]
+    fn test_strip_migrations_table_postgres() {
+        let schema = "-- TABLES \n\nCREATE TABLE public.\"schema_migrations\" (\n id character varying (255) NOT NULL\n);\n\nCREATE TABLE public.\"users\" (\n id integer NOT NULL\n);\n\n-- CONSTRAINTS \n\nALTER TABLE public.\"schema_migrations\" ADD CONSTRAINT schema_migrations_pkey PRIMARY KEY (id);\n\nALTER TABLE public.\"users\" ADD CONSTRAINT users_pkey PRIMARY KEY (id);";
+
+        let result = strip_migrations_table(schema, "schema_migrations");
+        assert!(!result.contains("schema_migrations"));
+        assert!(result.contains("CREATE TABLE public.\"users\""));
+        assert!(result.contains("ADD CONSTRAINT users_pkey"));
+        assert!(result.contains("-- CONSTRAINTS"));
+    }
+
+    #[test]
+    fn test_strip_migrations_table_sqlite() {
+        let schema = "CREATE TABLE \"schema_migrations\" (id VARCHAR(255) PRIMARY KEY);\nCREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);";
+
+        let result = strip_migrations_table(schema, "schema_migrations");
+        assert_eq!(
+            result,
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);"
+        );
+    }
+
+    #[test]
+    fn test_strip_migrations_table_schema_qualified() {
+        let schema = "CREATE TABLE migrations.\"migrations\" (\n id character varying (255) NOT NULL\n);\n\nCREATE TABLE public.\"migrations\" (\n id integer NOT NULL\n);";
+
+        let result = strip_migrations_table(schema, "migrations.migrations");
+        assert!(!result.contains("CREATE TABLE migrations.\"migrations\""));
+        assert!(result.contains("CREATE TABLE public.\"migrations\""));
+    }
+
+    #[test]
+    fn test_strip_migrations_table_keeps_similar_names() {
+        let schema = "CREATE TABLE schema_migrations_archive (id INTEGER);";
+
+        let result = strip_migrations_table(schema, "schema_migrations");
+        assert_eq!(result, schema);
+    }
+}
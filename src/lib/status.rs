@@ -1,11 +1,45 @@
 use std::path::PathBuf;
 
 use crate::{
-    database_drivers,
-    utils::{get_local_migrations, read_file_content},
+    database_drivers::{self, SchemaMigration},
+    utils::{applied_migration_ids, get_local_migrations, migration_name, read_file_content},
 };
 use anyhow::{bail, Result};
 use log::info;
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MigrationState {
+    // The migration exists locally and is recorded in the migrations table
+    Applied,
+    // The migration exists locally and is newer than every applied migration
+    Pending,
+    // The migration is recorded in the migrations table but there is no local up file
+    MissingFile,
+    // The migration isn't applied but is older than the newest applied migration
+    OutOfOrder,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::MissingFile => "missing-file",
+            MigrationState::OutOfOrder => "out-of-order",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationStatus {
+    pub id: i64,
+    pub name: Option<String>,
+    pub state: MigrationState,
+    pub path: Option<PathBuf>,
+    pub applied: Option<SchemaMigration>,
+}
 
 pub async fn status(
     database_url: String,
@@ -14,6 +48,62 @@ pub async fn status(
     migration_folder: String,
     schema_file: String,
     wait_timeout: Option<usize>,
+) -> Result<Vec<MigrationStatus>> {
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder.clone(),
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    let path = PathBuf::from(&migration_folder);
+    let files = match get_local_migrations(&path, "up") {
+        Ok(f) => f,
+        Err(err) => {
+            bail!("Couldn't read migration folder: {:?}", err)
+        }
+    };
+
+    // Read-only so status works for replicas and users that can't create the table
+    let migrations = database.get_schema_migrations().await?.unwrap_or_default();
+
+    Ok(compare_migrations(&files, &migrations))
+}
+
+// Lists the applied migrations without creating anything, None when the table doesn't exist
+pub async fn applied_migrations(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
+) -> Result<Option<Vec<SchemaMigration>>> {
+    let mut database = database_drivers::new(
+        database_url,
+        database_token,
+        migration_table,
+        migration_folder,
+        schema_file,
+        wait_timeout,
+        true,
+    )
+    .await?;
+
+    database.get_schema_migrations().await
+}
+
+pub(crate) async fn log_status(
+    database_url: String,
+    database_token: Option<String>,
+    migration_table: String,
+    migration_folder: String,
+    schema_file: String,
+    wait_timeout: Option<usize>,
     verbose: bool,
 ) -> Result<()> {
     let mut database = database_drivers::new(
@@ -35,14 +125,12 @@ pub async fn status(
         }
     };
 
-    let migrations: Vec<String> = database
-        .get_or_create_schema_migrations()
+    let migrations = database
+        .get_schema_migrations()
         .await?
+        .unwrap_or_default()
         .into_iter()
-        .map(|s| s.into_boxed_str())
-        .collect::<Vec<Box<str>>>()
-        .into_iter()
-        .map(|s| s.into())
+        .map(|m| m.id)
         .collect();
 
     compare_migrations_and_log(files, migrations, verbose);
@@ -50,17 +138,82 @@ pub async fn status(
     Ok(())
 }
 
+// Returns every local and applied migration ordered by id
+fn compare_migrations(
+    files: &[(i64, PathBuf)],
+    migrations: &[SchemaMigration],
+) -> Vec<MigrationStatus> {
+    let ids = migrations
+        .iter()
+        .map(|m| m.id.clone())
+        .collect::<Vec<String>>();
+    let applied = applied_migration_ids(&ids);
+    let newest_applied = applied.first().copied();
+    let applied_row = |id: i64| {
+        migrations
+            .iter()
+            .find(|m| m.id.trim().parse::<i64>().ok() == Some(id))
+            .cloned()
+    };
+
+    let mut statuses: Vec<MigrationStatus> = files
+        .iter()
+        .map(|(id, path)| {
+            let is_applied = applied.contains(id);
+            let state = if is_applied {
+                MigrationState::Applied
+            } else if newest_applied.is_some_and(|newest| *id < newest) {
+                MigrationState::OutOfOrder
+            } else {
+                MigrationState::Pending
+            };
+
+            MigrationStatus {
+                id: *id,
+                name: migration_name(path),
+                state,
+                path: Some(path.clone()),
+                applied: applied_row(*id),
+            }
+        })
+        .collect();
+
+    for id in applied {
+        if !files.iter().any(|(file_id, _)| *file_id == id) {
+            let row = applied_row(id);
+            statuses.push(MigrationStatus {
+                id,
+                name: row.as_ref().and_then(|r| r.name.clone()),
+                state: MigrationState::MissingFile,
+                path: None,
+                applied: row,
+            });
+        }
+    }
+
+    statuses.sort_by_key(|s| s.id);
+    statuses
+}
+
 // Extracted for easier testing
 fn compare_migrations_and_log(files: Vec<(i64, PathBuf)>, migrations: Vec<String>, verbose: bool) {
+    let applied = applied_migration_ids(&migrations);
+
     for f in files {
-        let id = Box::new(f.0.to_string());
+        let id = f.0;
 
-        if !migrations.contains(&id) {
+        if !applied.contains(&id) {
             if verbose {
                 let query = read_file_content(&f.1);
-                info!("Pending migration {}: \n {}", id, query);
+                info!(
+                    migration_id = id,
+                    state = "pending";
+                    "Pending migration {}: \n {}",
+                    id,
+                    query
+                );
             } else {
-                info!("Pending {}", id);
+                info!(migration_id = id, state = "pending"; "Pending {}", id);
             }
         }
     }
@@ -173,4 +326,82 @@ mod tests {
         // Should only log 1234567891 as pending
         compare_migrations_and_log(files, migrations, false);
     }
+
+    #[test]
+    fn test_compare_migrations_states() {
+        let files = vec![
+            (
+                1234567890,
+                PathBuf::from("./migrations/1234567890_create_users.up.sql"),
+            ),
+            (
+                1234567891,
+                PathBuf::from("./migrations/1234567891_add_index.up.sql"),
+            ),
+            (
+                1234567892,
+                PathBuf::from("./migrations/1234567892_add_posts.up.sql"),
+            ),
+            (
+                1234567894,
+                PathBuf::from("./migrations/1234567894_add_tags.up.sql"),
+            ),
+        ];
+        let migrations = ["1234567893", "1234567892", "1234567890"]
+            .iter()
+            .map(|id| SchemaMigration {
+                id: id.to_string(),
+                name: (*id == "1234567893").then(|| "add_comments".to_string()),
+                batch: Some(1),
+                ..Default::default()
+            })
+            .collect::<Vec<SchemaMigration>>();
+
+        let result = compare_migrations(&files, &migrations);
+
+        let states = result
+            .iter()
+            .map(|s| (s.id, s.state.clone()))
+            .collect::<Vec<(i64, MigrationState)>>();
+        assert_eq!(
+            states,
+            vec![
+                (1234567890, MigrationState::Applied),
+                (1234567891, MigrationState::OutOfOrder),
+                (1234567892, MigrationState::Applied),
+                (1234567893, MigrationState::MissingFile),
+                (1234567894, MigrationState::Pending),
+            ]
+        );
+
+        assert_eq!(result[0].name.as_deref(), Some("create_users"));
+        assert_eq!(
+            result[0].applied.as_ref().map(|a| a.id.as_str()),
+            Some("1234567890")
+        );
+        assert_eq!(result[0].applied.as_ref().unwrap().batch, Some(1));
+        assert!(result[1].applied.is_none());
+        assert_eq!(result[3].name.as_deref(), Some("add_comments"));
+        assert!(result[3].path.is_none());
+    }
+
+    #[test]
+    fn test_compare_migrations_nothing_applied() {
+        let files = vec![
+            (1, PathBuf::from("0001_create_users.up.sql")),
+            (10, PathBuf::from("0010_add_index.up.sql")),
+        ];
+
+        let result = compare_migrations(&files, &[]);
+        assert!(result.iter().all(|s| s.state == MigrationState::Pending));
+    }
+
+    #[test]
+    fn test_migration_state_serializes_kebab_case() {
+        assert_eq!(
+            serde_json::to_string(&MigrationState::MissingFile).unwrap(),
+            "\"missing-file\""
+        );
+        assert_eq!(MigrationState::OutOfOrder.as_str(), "out-of-order");
+    }
 }
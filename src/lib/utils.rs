@@ -1,6 +1,9 @@
+use crate::config;
+use crate::database_drivers::{DatabaseDriver, SchemaMigration};
 use anyhow::{bail, Result};
+use chrono::Utc;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::vec;
 
 pub fn get_local_migrations(folder: &PathBuf, ending: &str) -> Result<Vec<(i64, PathBuf)>> {
@@ -18,7 +21,15 @@ pub fn get_local_migrations(folder: &PathBuf, ending: &str) -> Result<Vec<(i64,
         let entry = entry.unwrap();
         let path = entry.path();
 
-        if entry.file_name().to_str().unwrap().ends_with(&end) {
+        let filename = entry.file_name();
+        let filename = filename.to_str().unwrap();
+
+        // Files starting with _ such as _before_each.sql aren't migrations
+        if filename.starts_with('_') {
+            continue;
+        }
+
+        if filename.ends_with(&end) {
             migration_files.push((path.clone(), path));
         }
     }
@@ -39,10 +50,119 @@ pub fn get_local_migrations(folder: &PathBuf, ending: &str) -> Result<Vec<(i64,
     Ok(sorted)
 }
 
+// Ids are stored as strings, so they're parsed to keep e.g. 0010 after 0009. Newest first
+pub fn applied_migration_ids(migrations: &[String]) -> Vec<i64> {
+    let mut ids: Vec<i64> = migrations
+        .iter()
+        .filter_map(|id| id.trim().parse::<i64>().ok())
+        .collect();
+    ids.sort_by(|a, b| b.cmp(a));
+    ids
+}
+
+pub fn migration_name(path: &Path) -> Option<String> {
+    let filename = path.file_name()?.to_str()?;
+    let name = filename.split_once('_')?.1;
+
+    Some(
+        name.trim_end_matches(".up.sql")
+            .trim_end_matches(".down.sql")
+            .to_string(),
+    )
+}
+
+// Every run that records migrations gets its own batch
+pub async fn next_batch(database: &mut Box<dyn DatabaseDriver>) -> Result<i64> {
+    let migrations = database.get_schema_migrations().await?.unwrap_or_default();
+
+    Ok(migrations.iter().filter_map(|m| m.batch).max().unwrap_or(0) + 1)
+}
+
+pub fn applied_migration(
+    id: i64,
+    path: &Path,
+    duration_ms: Option<i64>,
+    batch: i64,
+) -> SchemaMigration {
+    SchemaMigration {
+        id: id.to_string(),
+        name: migration_name(path),
+        applied_at: Some(Utc::now()),
+        duration_ms,
+        applied_by: Some(current_os_user()),
+        batch: Some(batch),
+    }
+}
+
+pub const BEFORE_EACH_FILE: &str = "_before_each.sql";
+pub const AFTER_EACH_FILE: &str = "_after_each.sql";
+
+// Optional SQL from the migrations folder that runs around every migration, e.g. SET ROLE
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EachMigrationSql {
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl EachMigrationSql {
+    pub fn read(folder: &Path) -> Result<EachMigrationSql> {
+        let read = |file: &str| -> Result<Option<String>> {
+            let path = folder.join(file);
+            if !path.exists() {
+                return Ok(None);
+            }
+
+            Ok(Some(fs::read_to_string(path)?).filter(|sql| !sql.trim().is_empty()))
+        };
+
+        Ok(EachMigrationSql {
+            before: read(BEFORE_EACH_FILE)?,
+            after: read(AFTER_EACH_FILE)?,
+        })
+    }
+
+    // The before SQL, the migration and the after SQL as separate queries. They run one after
+    // another on the same connection so a migration without a transaction still works.
+    pub fn around<'a>(&'a self, query: &'a str) -> Vec<&'a str> {
+        [self.before.as_deref(), Some(query), self.after.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+// Database kind for logs and traces, unknown when the url can't be parsed
+pub fn database_kind(database_url: &str) -> String {
+    url::Url::parse(database_url)
+        .ok()
+        .and_then(|url| config::Database::new(url.scheme()).ok())
+        .and_then(|database| database.as_str().ok().map(|kind| kind.to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub fn outcome<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "success",
+        Err(_) => "failure",
+    }
+}
+
 pub fn read_file_content(path: &PathBuf) -> String {
     fs::read_to_string(path).unwrap()
 }
 
+pub fn current_os_user() -> String {
+    for key in ["USER", "USERNAME", "LOGNAME"] {
+        if let Ok(v) = std::env::var(key) {
+            if !v.is_empty() {
+                return v;
+            }
+        }
+    }
+
+    "unknown".to_string()
+}
+
 pub fn should_run_in_transaction(query: &str) -> bool {
     let first_line = query.split_once('\n').unwrap_or(("", "")).0;
 
@@ -94,6 +214,41 @@ mod tests {
         assert!(!should_run_in_transaction(query));
     }
 
+    #[test]
+    fn test_migration_name() {
+        assert_eq!(
+            migration_name(Path::new("./migrations/1234567890_create_users.up.sql")).as_deref(),
+            Some("create_users")
+        );
+        assert_eq!(
+            migration_name(Path::new("0001_add_index.down.sql")).as_deref(),
+            Some("add_index")
+        );
+        assert_eq!(migration_name(Path::new("schema.sql")), None);
+    }
+
+    #[test]
+    fn test_applied_migration() {
+        let migration = applied_migration(
+            1234567890,
+            Path::new("1234567890_create_users.up.sql"),
+            Some(12),
+            3,
+        );
+
+        assert_eq!(migration.id, "1234567890");
+        assert_eq!(migration.name.as_deref(), Some("create_users"));
+        assert!(migration.applied_at.is_some());
+        assert_eq!(migration.duration_ms, Some(12));
+        assert_eq!(migration.applied_by, Some(current_os_user()));
+        assert_eq!(migration.batch, Some(3));
+    }
+
+    #[test]
+    fn test_current_os_user_not_empty() {
+        assert!(!current_os_user().is_empty());
+    }
+
     #[test]
     fn test_get_local_migrations_with_valid_files() {
         let tmp_dir = tempdir().unwrap();
@@ -194,6 +349,75 @@ mod tests {
         assert_eq!(result[2].0, 1234567892);
     }
 
+    #[test]
+    fn test_get_local_migrations_skips_underscore_files() {
+        let tmp_dir = tempdir().unwrap();
+        let migration_folder = tmp_dir.path();
+
+        for filename in [
+            "1234567890_create_users.up.sql",
+            "_before_each.sql",
+            "_after_each.sql",
+            "_template.up.sql",
+        ] {
+            File::create(migration_folder.join(filename)).unwrap();
+        }
+
+        let result = get_local_migrations(&migration_folder.to_path_buf(), "up").unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, 1234567890);
+    }
+
+    #[test]
+    fn test_each_migration_sql_read() {
+        let tmp_dir = tempdir().unwrap();
+        let migration_folder = tmp_dir.path();
+
+        assert_eq!(
+            EachMigrationSql::read(migration_folder).unwrap(),
+            EachMigrationSql::default()
+        );
+
+        fs::write(
+            migration_folder.join(BEFORE_EACH_FILE),
+            "SET ROLE migrator;\n",
+        )
+        .unwrap();
+        fs::write(migration_folder.join(AFTER_EACH_FILE), "  \n").unwrap();
+
+        let each = EachMigrationSql::read(migration_folder).unwrap();
+        assert_eq!(each.before.as_deref(), Some("SET ROLE migrator;\n"));
+        assert!(each.after.is_none());
+    }
+
+    #[test]
+    fn test_each_migration_sql_around() {
+        let query = "-- transaction:no\nCREATE INDEX CONCURRENTLY idx ON users (name)";
+        assert_eq!(EachMigrationSql::default().around(query), vec![query]);
+
+        let each = EachMigrationSql {
+            before: Some("SET ROLE migrator;\nSET lock_timeout = '5s';\n".to_string()),
+            after: Some("RESET ROLE".to_string()),
+        };
+        assert_eq!(
+            each.around(query),
+            vec![
+                "SET ROLE migrator;\nSET lock_timeout = '5s';\n",
+                query,
+                "RESET ROLE"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_database_kind() {
+        assert_eq!(database_kind("postgresql://localhost:5432/app"), "postgres");
+        assert_eq!(database_kind("sqlite://./app.sqlite"), "sqlite");
+        assert_eq!(database_kind("https://app.turso.io"), "libsql");
+        assert_eq!(database_kind("redis://localhost"), "unknown");
+        assert_eq!(database_kind("not a url"), "unknown");
+    }
+
     #[test]
     fn test_read_file_content() {
         let tmp_dir = tempdir().unwrap();
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use chrono::Utc;
 use log::info;
 use std::env;
@@ -6,11 +7,15 @@ use std::fs;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
+use std::time::Duration;
 use tempfile::TempDir;
 
+use geni::audit::{self, AuditLog};
 use geni::config::Database;
-use geni::database_drivers;
+use geni::database_drivers::{self, DumpOptions};
+use geni::hooks::{Hooks, ShellHooks};
 use geni::migrate::{down, up};
+use geni::observer::{Direction, MigrationObserver, PlannedMigration};
 
 use testcontainers::core::wait::LogWaitStrategy;
 use testcontainers::core::{IntoContainerPort, WaitFor};
@@ -117,6 +122,7 @@ async fn test_migrate(database: Database, db_url: &str, migrations_table: &str)
         database_schema_file.clone(),
         Some(database_wait_timeout),
         true,
+        DumpOptions::default(),
     )
     .await;
     assert!(u.is_ok());
@@ -137,6 +143,7 @@ async fn test_migrate(database: Database, db_url: &str, migrations_table: &str)
         database_schema_file.clone(),
         Some(database_wait_timeout),
         false,
+        DumpOptions::default(),
         &1,
     )
     .await;
@@ -158,6 +165,7 @@ async fn test_migrate(database: Database, db_url: &str, migrations_table: &str)
         database_schema_file.clone(),
         Some(database_wait_timeout),
         false,
+        DumpOptions::default(),
         &3,
     )
     .await;
@@ -392,7 +400,1401 @@ async fn test_migrate_failure() {
         database_schema_file.clone(),
         Some(database_wait_timeout),
         true,
+        DumpOptions::default(),
     )
     .await;
     assert!(u.is_err());
 }
+
+fn local_migration_ids(migration_folder: &str, ending: &str) -> Vec<i64> {
+    let end = format!(".{}.sql", ending);
+    let mut ids = fs::read_dir(migration_folder)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .filter(|name| name.ends_with(&end))
+        .map(|name| name.split_once('_').unwrap().0.parse::<i64>().unwrap())
+        .collect::<Vec<i64>>();
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn test_squash_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/squash.sqlite", migration_folder_string);
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    // Squashing needs the database to be exactly at the squashed version
+    down(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        &3,
+    )
+    .await
+    .unwrap();
+
+    let ids = local_migration_ids(&migration_folder_string, "up");
+    let version = ids[2];
+
+    // Declining leaves every migration file in place
+    let declined = geni::squash_migrations(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        version,
+        |_| false,
+    )
+    .await;
+    assert!(declined.is_err());
+    assert_eq!(local_migration_ids(&migration_folder_string, "up"), ids);
+
+    geni::squash_migrations(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        version,
+        |files| files.len() == 6,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        local_migration_ids(&migration_folder_string, "up"),
+        vec![version, ids[3], ids[4], ids[5]]
+    );
+    assert_eq!(
+        local_migration_ids(&migration_folder_string, "down"),
+        vec![version, ids[3], ids[4], ids[5]]
+    );
+    assert!(Path::new(&format!(
+        "{}/{}_baseline.up.sql",
+        migration_folder_string, version
+    ))
+    .exists());
+
+    let mut client = database_drivers::new(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        client
+            .get_or_create_schema_migrations()
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // A new database built from the baseline ends up with the same migrations applied
+    let fresh_url = format!("sqlite://{}/fresh.sqlite", migration_folder_string);
+    up(
+        fresh_url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let mut fresh_client = database_drivers::new(
+        fresh_url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string,
+        database_schema_file,
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fresh_client
+            .get_or_create_schema_migrations()
+            .await
+            .unwrap()
+            .len(),
+        4
+    );
+}
+
+#[tokio::test]
+async fn test_baseline_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/baseline.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    geni::baseline_database(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        ids[2],
+        |_| panic!("An empty migrations table shouldn't need confirmation"),
+    )
+    .await
+    .unwrap();
+
+    let mut client = database_drivers::new(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        client
+            .get_or_create_schema_migrations()
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    let declined = geni::baseline_database(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        ids[5],
+        |migrations| {
+            assert_eq!(migrations.len(), 3);
+            false
+        },
+    )
+    .await;
+    assert!(declined.is_err());
+    assert_eq!(
+        client
+            .get_or_create_schema_migrations()
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_mark_applied_and_unapplied_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/mark.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    geni::mark_applied(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        ids[0],
+    )
+    .await
+    .unwrap();
+
+    let mut client = database_drivers::new(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        client.get_or_create_schema_migrations().await.unwrap(),
+        vec![ids[0].to_string()]
+    );
+
+    // Marking twice or marking an unknown migration is rejected
+    assert!(geni::mark_applied(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        ids[0],
+    )
+    .await
+    .is_err());
+    assert!(geni::mark_applied(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        1,
+    )
+    .await
+    .is_err());
+
+    geni::mark_unapplied(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string,
+        database_schema_file,
+        Some(30),
+        ids[0],
+    )
+    .await
+    .unwrap();
+    assert!(client
+        .get_or_create_schema_migrations()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_renumber_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/renumber.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    geni::mark_applied(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        ids[0],
+    )
+    .await
+    .unwrap();
+
+    // Applied migrations are never renumbered
+    assert!(geni::renumber_migrations(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        vec![ids[0].to_string()],
+        geni::config::VersionScheme::Timestamp,
+    )
+    .await
+    .is_err());
+
+    geni::renumber_migrations(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file,
+        Some(30),
+        vec![ids[1].to_string()],
+        geni::config::VersionScheme::Timestamp,
+    )
+    .await
+    .unwrap();
+
+    let up_ids = local_migration_ids(&migration_folder_string, "up");
+    let down_ids = local_migration_ids(&migration_folder_string, "down");
+    assert_eq!(up_ids.len(), 6);
+    assert_eq!(up_ids, down_ids);
+    assert!(!up_ids.contains(&ids[1]));
+    assert!(up_ids[5] > ids[5]);
+}
+
+#[tokio::test]
+async fn test_migration_status_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/status.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    for id in [ids[0], ids[2]] {
+        geni::mark_applied(
+            url.clone(),
+            None,
+            "schema_migrations".to_string(),
+            migration_folder_string.clone(),
+            database_schema_file.clone(),
+            Some(30),
+            id,
+        )
+        .await
+        .unwrap();
+    }
+
+    // An applied migration without a local file
+    let mut client = database_drivers::new(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    client
+        .insert_schema_migration(&database_drivers::SchemaMigration {
+            id: "1".to_string(),
+            name: Some("removed".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let statuses = geni::migration_status(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string,
+        database_schema_file,
+        Some(30),
+    )
+    .await
+    .unwrap();
+
+    let states = statuses
+        .iter()
+        .map(|s| (s.id, s.state.as_str()))
+        .collect::<Vec<(i64, &str)>>();
+    assert_eq!(states.len(), ids.len() + 1);
+    assert_eq!(states[0], (1, "missing-file"));
+    assert_eq!(states[1], (ids[0], "applied"));
+    assert_eq!(states[2], (ids[1], "out-of-order"));
+    assert_eq!(states[3], (ids[2], "applied"));
+    assert_eq!(states[4], (ids[3], "pending"));
+}
+
+#[tokio::test]
+async fn test_migration_status_read_only_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/read_only.sqlite", migration_folder_string);
+
+    let statuses = geni::migration_status(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap();
+    assert!(statuses.iter().all(|s| s.state.as_str() == "pending"));
+
+    geni::dump_database(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap();
+
+    let applied = geni::applied_migrations(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap();
+    assert!(applied.is_none());
+
+    // Neither status nor dump may create the migrations table
+    let mut client = database_drivers::new(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string,
+        database_schema_file,
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    assert!(client.get_schema_migrations().await.unwrap().is_none());
+
+    client.get_or_create_schema_migrations().await.unwrap();
+    assert_eq!(client.get_schema_migrations().await.unwrap(), Some(vec![]));
+}
+
+#[tokio::test]
+async fn test_history_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/history.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    // A table created by an older version only has the id column
+    let mut client = database_drivers::new(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    client
+        .execute(
+            &format!(
+                "CREATE TABLE schema_migrations (id VARCHAR(255) PRIMARY KEY); CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL); CREATE TABLE computers (id INTEGER PRIMARY KEY, name TEXT NOT NULL); INSERT INTO schema_migrations (id) VALUES ('{}');",
+                ids[0]
+            ),
+            true,
+        )
+        .await
+        .unwrap();
+
+    let history = geni::migration_history(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].id, ids[0].to_string());
+    assert_eq!(history[0].name.as_deref(), Some("0_test"));
+    assert!(history[0].applied_at.is_none());
+
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let history = geni::migration_history(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(history.len(), ids.len());
+    assert!(history[1..].iter().all(|m| m.applied_at.is_some()
+        && m.duration_ms.is_some()
+        && m.applied_by.is_some()
+        && m.batch == Some(1)));
+    assert_eq!(history[1].name.as_deref(), Some("1_test"));
+
+    let history = geni::migration_history(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string,
+        database_schema_file,
+        Some(30),
+        Some(Utc::now() - chrono::Duration::hours(1)),
+        None,
+        Some(2),
+    )
+    .await
+    .unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].id, ids[ids.len() - 1].to_string());
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    events: Vec<String>,
+    abort_at: Option<i64>,
+}
+
+#[async_trait]
+impl MigrationObserver for RecordingObserver {
+    async fn on_plan(
+        &mut self,
+        direction: Direction,
+        migrations: &[PlannedMigration],
+    ) -> Result<()> {
+        self.events
+            .push(format!("plan {} {}", direction.as_str(), migrations.len()));
+        Ok(())
+    }
+
+    async fn before_migration(
+        &mut self,
+        direction: Direction,
+        migration: &PlannedMigration,
+    ) -> Result<()> {
+        if self.abort_at == Some(migration.id) {
+            anyhow::bail!("deploys are frozen");
+        }
+        self.events
+            .push(format!("before {} {}", direction.as_str(), migration.id));
+        Ok(())
+    }
+
+    async fn after_migration(
+        &mut self,
+        direction: Direction,
+        migration: &PlannedMigration,
+        _duration: Duration,
+        result: &Result<()>,
+    ) {
+        self.events.push(format!(
+            "after {} {} {}",
+            direction.as_str(),
+            migration.id,
+            result.is_ok()
+        ));
+    }
+
+    async fn on_dump(&mut self, schema_file: &str, result: &Result<()>) {
+        self.events
+            .push(format!("dump {} {}", schema_file, result.is_ok()));
+    }
+}
+
+#[tokio::test]
+async fn test_migration_observer_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = tmp_dir
+        .path()
+        .join("sqlite_schema.sql")
+        .to_str()
+        .unwrap()
+        .to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/observer.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    let mut observer = RecordingObserver {
+        abort_at: Some(ids[2]),
+        ..Default::default()
+    };
+    let result = geni::migrate_database_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+        DumpOptions::default(),
+        &mut observer,
+    )
+    .await;
+    assert!(result.unwrap_err().to_string().contains("was aborted"));
+    assert_eq!(
+        observer.events,
+        vec![
+            format!("plan up {}", ids.len()),
+            format!("before up {}", ids[0]),
+            format!("after up {} true", ids[0]),
+            format!("before up {}", ids[1]),
+            format!("after up {} true", ids[1]),
+        ]
+    );
+
+    let mut observer = RecordingObserver::default();
+    geni::migrate_database_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+        DumpOptions::default(),
+        &mut observer,
+    )
+    .await
+    .unwrap();
+    assert_eq!(observer.events[0], format!("plan up {}", ids.len() - 2));
+    assert_eq!(
+        observer.events.last().unwrap(),
+        &format!("dump {} true", database_schema_file)
+    );
+
+    let mut observer = RecordingObserver::default();
+    geni::migrate_down_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        1,
+        &mut observer,
+    )
+    .await
+    .unwrap();
+    let newest = ids[ids.len() - 1];
+    assert_eq!(
+        observer.events,
+        vec![
+            "plan down 1".to_string(),
+            format!("before down {}", newest),
+            format!("after down {} true", newest),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_shell_hooks_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/hooks.sqlite", migration_folder_string);
+    let hooks_log = tmp_dir.path().join("hooks.log");
+
+    let mut hooks = ShellHooks::new(
+        Hooks {
+            before_up: Some("exit 1".to_string()),
+            ..Default::default()
+        },
+        &url,
+    )
+    .unwrap();
+    let result = geni::migrate_database_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        &mut hooks,
+    )
+    .await;
+    assert!(result.is_err());
+
+    // The before hook runs before the migrations table is created
+    let mut database = database_drivers::new(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    assert!(database.get_schema_migrations().await.unwrap().is_none());
+
+    let statuses = geni::migration_status(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap();
+    assert!(statuses
+        .iter()
+        .all(|s| s.state == geni::status::MigrationState::Pending));
+
+    let mut hooks = ShellHooks::new(
+        Hooks {
+            after_each_down: Some(format!(
+                "echo \"$GENI_MIGRATION_DIRECTION $GENI_MIGRATION_ID $GENI_DATABASE\" >> {}",
+                hooks_log.display()
+            )),
+            ..Default::default()
+        },
+        &url,
+    )
+    .unwrap();
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+    geni::migrate_down_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        1,
+        &mut hooks,
+    )
+    .await
+    .unwrap();
+
+    let ids = local_migration_ids(&migration_folder_string, "down");
+    assert_eq!(
+        fs::read_to_string(&hooks_log).unwrap(),
+        format!("down {} sqlite\n", ids[ids.len() - 1])
+    );
+}
+
+#[tokio::test]
+async fn test_each_migration_sql_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+    fs::write(
+        tmp_dir.path().join("_before_each.sql"),
+        "CREATE TABLE IF NOT EXISTS each_log (id INTEGER PRIMARY KEY AUTOINCREMENT, event TEXT NOT NULL);\nINSERT INTO each_log (event) VALUES ('before');\n",
+    )
+    .unwrap();
+    fs::write(
+        tmp_dir.path().join("_after_each.sql"),
+        "INSERT INTO each_log (event) VALUES ('after')",
+    )
+    .unwrap();
+
+    let url = format!("sqlite://{}/each.sqlite", migration_folder_string);
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let statuses = geni::migration_status(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap();
+    assert_eq!(statuses.len(), ids.len());
+
+    down(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        &1,
+    )
+    .await
+    .unwrap();
+
+    let db = libsql::Builder::new_local(format!("{}/each.sqlite", migration_folder_string))
+        .build()
+        .await
+        .unwrap();
+    let conn = db.connect().unwrap();
+    let mut rows = conn
+        .query("SELECT event FROM each_log ORDER BY id", ())
+        .await
+        .unwrap();
+    let mut events = vec![];
+    while let Some(row) = rows.next().await.unwrap() {
+        events.push(row.get::<String>(0).unwrap());
+    }
+    assert_eq!(events.len(), (ids.len() + 1) * 2);
+    assert!(events
+        .chunks(2)
+        .all(|pair| pair == ["before".to_string(), "after".to_string()]));
+}
+
+#[tokio::test]
+async fn test_audit_log_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/audit.sqlite", migration_folder_string);
+    let audit_log = tmp_dir.path().join("audit.jsonl");
+    let mut audit = AuditLog::new(
+        audit_log.to_str().unwrap().to_string(),
+        audit::DEFAULT_MAX_BYTES,
+        &url,
+    );
+
+    geni::migrate_database_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        &mut audit,
+    )
+    .await
+    .unwrap();
+    geni::migrate_down_with_observer(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+        2,
+        &mut audit,
+    )
+    .await
+    .unwrap();
+
+    let records = fs::read_to_string(&audit_log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<serde_json::Value>>();
+    let ids = local_migration_ids(&migration_folder_string, "up");
+    assert_eq!(records.len(), ids.len() + 2);
+    assert_eq!(records[0]["id"], ids[0]);
+    assert_eq!(records[0]["direction"], "up");
+    assert_eq!(records[ids.len()]["id"], ids[ids.len() - 1]);
+    assert_eq!(records[ids.len()]["direction"], "down");
+    assert!(records.iter().all(
+        |r| r["outcome"] == "success" && r["checksum"].as_str().unwrap().starts_with("sha256:")
+    ));
+}
+
+#[tokio::test]
+async fn test_load_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+    let ids = local_migration_ids(&migration_folder_string, "up");
+
+    let source = format!("sqlite://{}/source.sqlite", migration_folder_string);
+    up(
+        source,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    // Written after the dump, so it has to run after loading
+    let newest = ids[ids.len() - 1] + 1;
+    for (ending, query) in [
+        (
+            "up",
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));",
+        ),
+        ("down", "DROP TABLE posts;"),
+    ] {
+        fs::write(
+            tmp_dir
+                .path()
+                .join(format!("{}_add_posts.{}.sql", newest, ending)),
+            query,
+        )
+        .unwrap();
+    }
+
+    let target = format!("sqlite://{}/target.sqlite", migration_folder_string);
+    File::create(tmp_dir.path().join("target.sqlite")).unwrap();
+    geni::load_database(
+        target.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        Some(ids[ids.len() - 1]),
+        false,
+    )
+    .await
+    .unwrap();
+
+    let applied = |url: String| {
+        let migration_folder = migration_folder_string.clone();
+        let schema_file = database_schema_file.clone();
+        async move {
+            let mut ids = geni::applied_migrations(
+                url,
+                None,
+                "schema_migrations".to_string(),
+                migration_folder,
+                schema_file,
+                Some(30),
+            )
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id.parse::<i64>().unwrap())
+            .collect::<Vec<i64>>();
+            ids.sort();
+            ids
+        }
+    };
+    assert_eq!(applied(target.clone()).await, ids);
+
+    up(
+        target.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        "target_schema.sql".to_string(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(applied(target.clone()).await.last(), Some(&newest));
+
+    let result = geni::load_database(
+        target,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        None,
+        false,
+    )
+    .await;
+    assert!(
+        result.is_err(),
+        "Loading into a migrated database should fail"
+    );
+}
+
+#[tokio::test]
+async fn test_load_split_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+    let ids = local_migration_ids(&migration_folder_string, "up");
+    let options = DumpOptions {
+        migrations: true,
+        split: true,
+        ..DumpOptions::default()
+    };
+
+    up(
+        format!("sqlite://{}/source.sqlite", migration_folder_string),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+        options.clone(),
+    )
+    .await
+    .unwrap();
+    assert!(!tmp_dir.path().join(&database_schema_file).exists());
+
+    let target = format!("sqlite://{}/target.sqlite", migration_folder_string);
+    File::create(tmp_dir.path().join("target.sqlite")).unwrap();
+    geni::load_database(
+        target.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        None,
+        true,
+    )
+    .await
+    .unwrap();
+
+    let applied = geni::applied_migrations(
+        target.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(applied.len(), ids.len());
+    assert_eq!(
+        geni::schema_drift(
+            target,
+            None,
+            "schema_migrations".to_string(),
+            migration_folder_string,
+            database_schema_file,
+            Some(30),
+            options,
+        )
+        .await
+        .unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_schema_drift_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/drift.sqlite", migration_folder_string);
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let drift = || {
+        geni::schema_drift(
+            url.clone(),
+            None,
+            "schema_migrations".to_string(),
+            migration_folder_string.clone(),
+            database_schema_file.clone(),
+            Some(30),
+            DumpOptions::default(),
+        )
+    };
+    assert_eq!(drift().await.unwrap(), None);
+
+    let mut client = database_drivers::new(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+    )
+    .await
+    .unwrap();
+    client
+        .execute("CREATE INDEX idx_users_name ON users(name);", false)
+        .await
+        .unwrap();
+
+    let diff = drift().await.unwrap().unwrap();
+    assert!(
+        diff.starts_with("--- sqlite_schema.sql\n+++ database\n")
+            && diff.contains("\n+CREATE INDEX idx_users_name ON users(name);\n"),
+        "Unexpected diff:\n{}",
+        diff
+    );
+}
+
+#[tokio::test]
+async fn test_check_dump_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/check.sqlite", migration_folder_string);
+    let check = || {
+        geni::check_dump(
+            url.clone(),
+            None,
+            "schema_migrations".to_string(),
+            migration_folder_string.clone(),
+            database_schema_file.clone(),
+            Some(30),
+            DumpOptions::default(),
+        )
+    };
+
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        false,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+    let schema_path = tmp_dir.path().join(&database_schema_file);
+    assert!(
+        check().await.unwrap().is_some(),
+        "A missing schema file is outdated"
+    );
+    assert!(
+        !schema_path.exists(),
+        "Checking shouldn't write the schema file"
+    );
+
+    geni::dump_database(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+    )
+    .await
+    .unwrap();
+    assert_eq!(check().await.unwrap(), None);
+
+    let committed = fs::read_to_string(&schema_path).unwrap();
+    fs::write(
+        &schema_path,
+        committed.replace("CREATE TABLE users", "CREATE TABLE  users"),
+    )
+    .unwrap();
+    let diff = check().await.unwrap().unwrap();
+    assert!(
+        diff.contains("\n-CREATE TABLE  users"),
+        "Unexpected diff:\n{}",
+        diff
+    );
+}
+
+#[tokio::test]
+async fn test_inspect_schema_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    generate_test_migrations(&migration_folder_string).unwrap();
+
+    let url = format!("sqlite://{}/inspect.sqlite", migration_folder_string);
+    up(
+        url.clone(),
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string.clone(),
+        database_schema_file.clone(),
+        Some(30),
+        true,
+        DumpOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let schema = geni::inspect_schema(
+        url,
+        None,
+        "schema_migrations".to_string(),
+        migration_folder_string,
+        database_schema_file,
+        Some(30),
+    )
+    .await
+    .unwrap();
+
+    let users = schema.tables.iter().find(|t| t.name == "users").unwrap();
+    let columns = users
+        .columns
+        .iter()
+        .map(|c| (c.name.as_str(), c.data_type.as_str(), c.nullable))
+        .collect::<Vec<(&str, &str, bool)>>();
+    assert_eq!(
+        columns,
+        vec![("id", "INTEGER", true), ("name", "TEXT", false)]
+    );
+    assert!(schema.tables.iter().any(|t| t.name == "schema_migrations"));
+    assert!(schema
+        .constraints
+        .iter()
+        .any(|c| c.table == "users" && c.definition == "PRIMARY KEY (id)"));
+
+    let json = serde_json::to_value(&schema).unwrap();
+    assert_eq!(json["tables"][0]["columns"][0]["name"], "id");
+}
+
+#[tokio::test]
+async fn test_diff_migration_sqlite() {
+    let tmp_dir = TempDir::new().unwrap();
+    let migration_folder_string = tmp_dir.path().to_str().unwrap().to_string();
+    let database_schema_file = "sqlite_schema.sql".to_string();
+    fs::write(
+        tmp_dir.path().join("0001_create_users.up.sql"),
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+    )
+    .unwrap();
+    fs::write(
+        tmp_dir.path().join("0001_create_users.down.sql"),
+        "DROP TABLE users;",
+    )
+    .unwrap();
+    fs::write(
+        tmp_dir.path().join("desired.sql"),
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, email TEXT);\n\
+         CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users (id));\n\
+         CREATE INDEX posts_user_id ON posts (user_id);\n",
+    )
+    .unwrap();
+
+    let url = format!("sqlite://{}/diff.sqlite", migration_folder_string);
+    let migrate = || {
+        up(
+            url.clone(),
+            None,
+            "schema_migrations".to_string(),
+            migration_folder_string.clone(),
+            database_schema_file.clone(),
+            Some(30),
+            false,
+            DumpOptions::default(),
+        )
+    };
+    let diff_migration = || {
+        geni::diff_migration(
+            url.clone(),
+            None,
+            "schema_migrations".to_string(),
+            migration_folder_string.clone(),
+            database_schema_file.clone(),
+            Some(30),
+            "desired.sql".to_string(),
+            "add posts",
+            geni::config::VersionScheme::Sequential,
+        )
+    };
+    migrate().await.unwrap();
+
+    diff_migration().await.unwrap();
+    let up_sql = fs::read_to_string(tmp_dir.path().join("0002_add_posts.up.sql")).unwrap();
+    assert!(up_sql.contains("CREATE TABLE \"posts\""), "{}", up_sql);
+    assert!(up_sql.contains("ADD COLUMN \"email\" TEXT"), "{}", up_sql);
+    assert!(up_sql.contains("CREATE INDEX posts_user_id"), "{}", up_sql);
+    let down_sql = fs::read_to_string(tmp_dir.path().join("0002_add_posts.down.sql")).unwrap();
+    assert!(down_sql.contains("DROP TABLE \"posts\";"), "{}", down_sql);
+
+    // Once the migration ran the database matches and no other migration is written
+    migrate().await.unwrap();
+    diff_migration().await.unwrap();
+    assert!(!tmp_dir.path().join("0003_add_posts.up.sql").exists());
+}